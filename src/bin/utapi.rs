@@ -0,0 +1,632 @@
+//! `utapi`, a command-line client for the UploadThing API.
+//!
+//! Credentials are read by `UploadthingConfig` from the `UPLOADTHING_TOKEN` or
+//! `UPLOADTHING_SECRET` environment variables unless `--token` or `--api-key` is given.
+//! Every command prints a human-readable summary, or JSON with `--json`.
+//!
+//! ```text
+//! utapi ls --all --status uploaded
+//! utapi upload ./report.pdf ./logo.png --acl public-read --metadata owner=ops
+//! utapi rm <key>... [--custom-id]
+//! utapi mv <key> <new-name> [--custom-id]
+//! utapi url <key>... [--custom-id]
+//! utapi sign <key> --expires 1h [--custom-id] [--local]
+//! utapi usage
+//! ```
+//!
+//! This binary is only built with the `cli` feature.
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use futures::TryStreamExt;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::{SystemTime, UNIX_EPOCH};
+use utapi_rs::config::UploadthingConfig;
+use utapi_rs::models::{
+    Acl, ContentDisposition, Expiry, FileObj, KeyType, ListAllFilesOpts, ListFilesOpts,
+    PresignedUrl, PresignedUrlOpts, RenameFilesOpts, SingleFileRename, UploadFileOpts,
+    UploadthingFile, UploadthingFileStatus,
+};
+use utapi_rs::UtApi;
+
+/// Manage the files of an UploadThing app.
+#[derive(Parser)]
+#[command(name = "utapi", version)]
+struct Cli {
+    /// An UploadThing token. Defaults to the `UPLOADTHING_TOKEN` environment variable.
+    #[arg(long, global = true, value_name = "TOKEN")]
+    token: Option<String>,
+
+    /// A legacy `sk_...` API key. Defaults to the `UPLOADTHING_SECRET` environment variable.
+    #[arg(long, global = true, value_name = "KEY", conflicts_with = "token")]
+    api_key: Option<String>,
+
+    /// The URL of the UploadThing API.
+    #[arg(long, global = true, value_name = "URL")]
+    host: Option<String>,
+
+    /// Print JSON instead of a human-readable summary.
+    #[arg(long, global = true)]
+    json: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// List files.
+    Ls {
+        /// The maximum number of files to list.
+        #[arg(long, default_value_t = 50, conflicts_with = "all", value_parser = clap::value_parser!(i32).range(1..))]
+        limit: i32,
+
+        /// The number of files to skip.
+        #[arg(long, default_value_t = 0, conflicts_with = "all", value_parser = clap::value_parser!(i32).range(0..))]
+        offset: i32,
+
+        /// List every file, requesting as many pages as needed.
+        #[arg(long)]
+        all: bool,
+
+        /// The number of files requested per page with `--all`.
+        #[arg(long, default_value_t = 500, requires = "all", value_parser = clap::value_parser!(i32).range(1..))]
+        page_size: i32,
+
+        /// Only list files with this status. Can be repeated. Without `--all`, pages are
+        /// requested until `--limit` files match.
+        #[arg(long, value_enum)]
+        status: Vec<StatusFilter>,
+    },
+
+    /// Upload local files.
+    Upload {
+        /// The files to upload.
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+
+        /// Who can access the uploaded files.
+        #[arg(long, value_enum)]
+        acl: Option<AclArg>,
+
+        /// How browsers should present the uploaded files.
+        #[arg(long, value_enum)]
+        content_disposition: Option<ContentDispositionArg>,
+
+        /// Metadata attached to every file, as `KEY=VALUE`. Can be repeated.
+        #[arg(long, value_name = "KEY=VALUE", value_parser = parse_metadata)]
+        metadata: Vec<(String, String)>,
+
+        /// The maximum number of files uploaded at once.
+        #[arg(long)]
+        concurrency: Option<usize>,
+
+        /// Return once the files are uploaded, without waiting for UploadThing to process them.
+        #[arg(long)]
+        no_wait: bool,
+    },
+
+    /// Delete files.
+    Rm {
+        /// The files to delete.
+        #[arg(required = true)]
+        keys: Vec<String>,
+
+        #[command(flatten)]
+        key_type: KeyTypeArg,
+    },
+
+    /// Rename a file.
+    Mv {
+        /// The file to rename.
+        key: String,
+
+        /// The new name of the file.
+        new_name: String,
+
+        #[command(flatten)]
+        key_type: KeyTypeArg,
+    },
+
+    /// Print the URLs of files.
+    Url {
+        /// The files to print the URLs of.
+        #[arg(required = true)]
+        keys: Vec<String>,
+
+        #[command(flatten)]
+        key_type: KeyTypeArg,
+    },
+
+    /// Print a URL that grants access to a private file until it expires.
+    Sign {
+        /// The file to sign a URL for.
+        key: String,
+
+        /// How long the URL stays valid, e.g. `90s`, `15m`, `1h` or `7d`.
+        #[arg(long)]
+        expires: Option<Expiry>,
+
+        /// Sign the URL locally with the API key instead of requesting it from UploadThing.
+        /// Needs an `UPLOADTHING_TOKEN`.
+        #[arg(long)]
+        local: bool,
+
+        #[command(flatten)]
+        key_type: KeyTypeArg,
+    },
+
+    /// Print the storage usage of the app.
+    Usage,
+}
+
+#[derive(Args)]
+struct KeyTypeArg {
+    /// Treat the keys as custom IDs instead of file keys.
+    #[arg(long)]
+    custom_id: bool,
+}
+
+impl KeyTypeArg {
+    fn key_type(&self) -> KeyType {
+        if self.custom_id {
+            KeyType::CustomId
+        } else {
+            KeyType::FileKey
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum StatusFilter {
+    Uploaded,
+    Uploading,
+    Failed,
+    DeletionPending,
+}
+
+impl StatusFilter {
+    fn matches(self, status: &UploadthingFileStatus) -> bool {
+        matches!(
+            (self, status),
+            (StatusFilter::Uploaded, UploadthingFileStatus::Uploaded)
+                | (StatusFilter::Uploading, UploadthingFileStatus::Uploading)
+                | (StatusFilter::Failed, UploadthingFileStatus::Failed)
+                | (
+                    StatusFilter::DeletionPending,
+                    UploadthingFileStatus::DeletionPending
+                )
+        )
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum AclArg {
+    Private,
+    PublicRead,
+}
+
+impl From<AclArg> for Acl {
+    fn from(acl: AclArg) -> Acl {
+        match acl {
+            AclArg::Private => Acl::Private,
+            AclArg::PublicRead => Acl::PublicRead,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ContentDispositionArg {
+    Inline,
+    Attachment,
+}
+
+impl From<ContentDispositionArg> for ContentDisposition {
+    fn from(disposition: ContentDispositionArg) -> ContentDisposition {
+        match disposition {
+            ContentDispositionArg::Inline => ContentDisposition::Inline,
+            ContentDispositionArg::Attachment => ContentDisposition::Attachment,
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match run(cli).await {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(cli: Cli) -> Result<ExitCode, Box<dyn Error>> {
+    // Without explicit credentials the builder reads them from the environment.
+    let mut builder = UploadthingConfig::builder();
+    if let Some(token) = &cli.token {
+        builder = builder.token(token);
+    }
+    if let Some(api_key) = &cli.api_key {
+        builder = builder.api_key(api_key);
+    }
+    if let Some(host) = &cli.host {
+        builder = builder.host(host);
+    }
+    if let Command::Upload {
+        concurrency: Some(concurrency),
+        ..
+    } = &cli.command
+    {
+        // The client-wide limit also caps each call, so raise it for this upload.
+        builder = builder.max_concurrency(*concurrency);
+    }
+    let api = UtApi::try_from_config(builder.build()?)?;
+    let json = cli.json;
+
+    match cli.command {
+        Command::Ls {
+            limit,
+            offset,
+            all,
+            page_size,
+            status,
+        } => {
+            let wanted = |file: &UploadthingFile| {
+                status.is_empty() || status.iter().any(|s| s.matches(&file.status))
+            };
+            let (files, has_more, next_offset) = if all {
+                let opts = ListAllFilesOpts {
+                    page_size,
+                    prefetch: true,
+                };
+                let files: Vec<UploadthingFile> = api
+                    .list_all_files(Some(opts))
+                    .try_filter(|file| futures::future::ready(wanted(file)))
+                    .try_collect()
+                    .await?;
+                (files, false, 0)
+            } else {
+                // With a status filter, pages are requested until `limit` files match, so
+                // `next_offset` is where the files that were not looked at yet start.
+                let limit_files = limit as usize;
+                let mut files = Vec::new();
+                let mut next_offset = offset;
+                loop {
+                    let opts = ListFilesOpts {
+                        limit: Some(limit),
+                        offset: Some(next_offset),
+                    };
+                    let response = api.list_files(Some(opts)).await?;
+                    let mut has_more =
+                        response.has_more.unwrap_or(false) && !response.files.is_empty();
+                    for file in response.files {
+                        if files.len() == limit_files {
+                            has_more = true;
+                            break;
+                        }
+                        next_offset = next_offset.saturating_add(1);
+                        if wanted(&file) {
+                            files.push(file);
+                        }
+                    }
+                    if !has_more || files.len() == limit_files {
+                        break (files, has_more, next_offset);
+                    }
+                }
+            };
+
+            if json {
+                let files: Vec<Value> = files.iter().map(file_json).collect();
+                let next_offset = has_more.then_some(next_offset);
+                print_json(&json!({
+                    "files": files,
+                    "hasMore": has_more,
+                    "nextOffset": next_offset,
+                }));
+            } else {
+                print_files(&files);
+                if has_more {
+                    eprintln!("more files follow; use --offset {} or --all", next_offset);
+                }
+            }
+        }
+
+        Command::Upload {
+            paths,
+            acl,
+            content_disposition,
+            metadata,
+            concurrency,
+            no_wait,
+        } => {
+            let mut files = Vec::with_capacity(paths.len());
+            for path in paths {
+                let name = path.file_name().and_then(|name| name.to_str());
+                let Some(name) = name.filter(|_| path.is_file()) else {
+                    return Err(format!("{} is not a file", path.display()).into());
+                };
+                files.push(FileObj::from_path(name.to_string(), path.clone()));
+            }
+            let opts = UploadFileOpts {
+                metadata: (!metadata.is_empty())
+                    .then(|| metadata.into_iter().collect::<HashMap<_, _>>()),
+                content_disposition: content_disposition.map(ContentDisposition::from),
+                acl: acl.map(Acl::from),
+                concurrency,
+                ..UploadFileOpts::default()
+            };
+
+            let report = api.upload_files(files, Some(opts), !no_wait).await?;
+            if json {
+                let results: Vec<Value> = report
+                    .results
+                    .iter()
+                    .map(|upload| match &upload.result {
+                        Ok(file) => json!({
+                            "name": upload.name,
+                            "key": file.key,
+                            "url": file.url,
+                            "size": file.size,
+                            "customId": file.custom_id,
+                        }),
+                        Err(e) => json!({ "name": upload.name, "error": e.to_string() }),
+                    })
+                    .collect();
+                print_json(&json!(results));
+            } else {
+                for upload in &report.results {
+                    match &upload.result {
+                        Ok(file) => {
+                            println!("uploaded {}  {}  {}", upload.name, file.key, file.url)
+                        }
+                        Err(e) => eprintln!("failed   {}: {}", upload.name, e),
+                    }
+                }
+            }
+            if !report.is_success() {
+                return Ok(ExitCode::FAILURE);
+            }
+        }
+
+        Command::Rm { keys, key_type } => {
+            let requested = keys.len();
+            let response = api.delete_files(keys, key_type.key_type()).await?;
+            if json {
+                print_json(&json!({
+                    "success": response.success,
+                    "deletedCount": response.deleted_count,
+                }));
+            } else if response.success {
+                let deleted = response.deleted_count.unwrap_or(requested as u64);
+                println!("deleted {} of {} files", deleted, requested);
+            }
+            if !response.success {
+                return Err("UploadThing did not delete the files".into());
+            }
+        }
+
+        Command::Mv {
+            key,
+            new_name,
+            key_type,
+        } => {
+            let rename = match key_type.key_type() {
+                KeyType::CustomId => SingleFileRename::by_custom_id(key.clone(), new_name.clone()),
+                KeyType::FileKey => SingleFileRename::new(key.clone(), new_name.clone()),
+            };
+            api.rename_files(RenameFilesOpts {
+                updates: vec![rename],
+            })
+            .await?;
+            if json {
+                print_json(&json!({ "key": key, "newName": new_name }));
+            } else {
+                println!("renamed {} to {}", key, new_name);
+            }
+        }
+
+        Command::Url { keys, key_type } => {
+            let response = api.get_file_urls(keys, key_type.key_type()).await?;
+            if json {
+                let urls: Vec<Value> = response
+                    .data
+                    .iter()
+                    .map(|url| json!({ "key": url.key, "url": url.url }))
+                    .collect();
+                print_json(&json!(urls));
+            } else {
+                for url in &response.data {
+                    println!("{}  {}", url.key, url.url);
+                }
+            }
+        }
+
+        Command::Sign {
+            key,
+            expires,
+            local,
+            key_type,
+        } => {
+            let signed: PresignedUrl = if local {
+                // A local signature covers the file key in the URL; a custom ID would sign
+                // a URL that never resolves.
+                if let KeyType::CustomId = key_type.key_type() {
+                    return Err(
+                        "--local needs a file key and cannot be used with --custom-id".into(),
+                    );
+                }
+                api.generate_signed_url(&key, expires)?
+            } else {
+                let opts = match key_type.key_type() {
+                    KeyType::CustomId => PresignedUrlOpts::by_custom_id(key),
+                    KeyType::FileKey => PresignedUrlOpts::new(key),
+                };
+                let opts = match expires {
+                    Some(expires) => opts.with_expires_in(expires),
+                    None => opts,
+                };
+                api.get_presigned_url(opts).await?
+            };
+            if json {
+                print_json(&json!({
+                    "key": signed.key,
+                    "url": signed.url,
+                    "expiresAt": signed.expires_at.map(unix_millis),
+                }));
+            } else {
+                println!("{}", signed.url);
+                if let Some(expires_at) = signed.expires_at {
+                    eprintln!("expires {}", httpdate::fmt_http_date(expires_at));
+                }
+            }
+        }
+
+        Command::Usage => {
+            let usage = api.get_usage_info().await?;
+            if json {
+                print_json(&json!({
+                    "totalBytes": usage.total_bytes,
+                    "appTotalBytes": usage.app_total_bytes,
+                    "filesUploaded": usage.files_uploaded,
+                    "limitBytes": usage.limit_bytes,
+                }));
+            } else {
+                let readable = |readable: Option<String>, bytes: f64| {
+                    readable.unwrap_or_else(|| format_size(bytes.max(0.0) as u64))
+                };
+                println!("files  {}", usage.files_uploaded);
+                println!(
+                    "used   {}",
+                    readable(usage.total_readable, usage.total_bytes as f64)
+                );
+                println!(
+                    "app    {}",
+                    readable(usage.app_total_readable, usage.app_total_bytes as f64)
+                );
+                println!(
+                    "limit  {}",
+                    readable(usage.limit_readable, usage.limit_bytes as f64)
+                );
+            }
+        }
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Parses a `KEY=VALUE` metadata entry.
+fn parse_metadata(entry: &str) -> Result<(String, String), String> {
+    match entry.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => Err(format!("expected KEY=VALUE, got {:?}", entry)),
+    }
+}
+
+/// Prints files as a table, one file per line.
+fn print_files(files: &[UploadthingFile]) {
+    let key_width = files.iter().map(|file| file.key.len()).max().unwrap_or(0);
+    for file in files {
+        let size = file.size.map_or_else(|| "-".to_string(), format_size);
+        let uploaded = file
+            .uploaded_at
+            .map_or_else(|| "-".to_string(), httpdate::fmt_http_date);
+        let custom_id = file
+            .custom_id
+            .as_ref()
+            .map(|id| format!("  [{}]", id))
+            .unwrap_or_default();
+        println!(
+            "{:<key_width$}  {:>9}  {:<16}  {:<29}  {}{}",
+            file.key,
+            size,
+            file.status.as_str(),
+            uploaded,
+            file.name,
+            custom_id,
+        );
+    }
+}
+
+/// Returns a file in the JSON output of `ls`.
+fn file_json(file: &UploadthingFile) -> Value {
+    json!({
+        "key": file.key,
+        "id": file.id,
+        "customId": file.custom_id,
+        "name": file.name,
+        "size": file.size,
+        "status": file.status.as_str(),
+        "uploadedAt": file.uploaded_at.map(unix_millis),
+    })
+}
+
+fn print_json(value: &Value) {
+    println!(
+        "{}",
+        serde_json::to_string_pretty(value).expect("JSON values can be serialized")
+    );
+}
+
+/// Formats a byte count with a binary unit, e.g. `1.5 MB`.
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+/// Returns the milliseconds since the Unix epoch, as the UploadThing API spells times.
+fn unix_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_metadata_entries() {
+        assert_eq!(
+            parse_metadata("owner=ops"),
+            Ok(("owner".to_string(), "ops".to_string()))
+        );
+        // Only the first `=` separates the key from the value.
+        assert_eq!(
+            parse_metadata("query=a=b"),
+            Ok(("query".to_string(), "a=b".to_string()))
+        );
+        assert_eq!(
+            parse_metadata("empty="),
+            Ok(("empty".to_string(), String::new()))
+        );
+        assert!(parse_metadata("=value").is_err());
+        assert!(parse_metadata("no-separator").is_err());
+    }
+
+    #[test]
+    fn formats_sizes_with_binary_units() {
+        assert_eq!(format_size(0), "0 B");
+        assert_eq!(format_size(1023), "1023 B");
+        assert_eq!(format_size(1024), "1.0 KB");
+        assert_eq!(format_size(1536), "1.5 KB");
+        assert_eq!(format_size(5 * 1024 * 1024), "5.0 MB");
+        assert_eq!(format_size(3 * 1024u64.pow(3)), "3.0 GB");
+        // Sizes beyond the largest unit stay in it.
+        assert_eq!(format_size(2048 * 1024u64.pow(4)), "2048.0 TB");
+    }
+}
@@ -0,0 +1,456 @@
+use crate::config::UploadthingConfig;
+use crate::error::{ConfigError, UtApiError};
+use crate::models::{
+    DeleteFileResponse, Expiry, FileObj, KeyType, ListAllFilesOpts, ListFilesOpts, PresignedUrl,
+    PresignedUrlOpts, RenameFilesOpts, UploadFileOpts, UploadReport, UploadthingFile,
+    UploadthingFileResponse, UploadthingUrlsResponse, UploadthingUsageInfo, UrlUpload,
+};
+use crate::rate_limit::RateLimit;
+use futures::stream::BoxStream;
+use futures::StreamExt;
+use reqwest::Client;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::SystemTime;
+use tokio::runtime::Runtime;
+
+/// A synchronous client for the UploadThing API.
+///
+/// Every method blocks until the corresponding method of `utapi_rs::UtApi` completes.
+/// Requests run on a runtime with a single worker thread that is owned by the client and
+/// shared by its clones, so background work such as concurrent uploads and prefetched
+/// pages keeps making progress.
+///
+/// # Examples
+///
+/// ```no_run
+/// use utapi_rs::blocking::UtApi;
+/// use utapi_rs::models::{KeyType, ListFilesOpts};
+///
+/// let api = UtApi::try_new(None)?;
+/// let files = api.list_files(Some(ListFilesOpts {
+///     limit: Some(10),
+///     offset: None,
+/// }))?;
+/// println!("Files: {:?}", files);
+///
+/// api.delete_files(vec!["file_key_to_delete".to_string()], KeyType::FileKey)?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+///
+/// # Panics
+///
+/// The methods block the current thread and panic when called from within an async
+/// runtime, as does dropping the last clone of the client there. Async code should use
+/// `utapi_rs::UtApi` instead.
+#[derive(Clone)]
+pub struct UtApi {
+    /// The async client that performs the requests.
+    inner: crate::UtApi,
+
+    /// The runtime the requests of `inner` run on.
+    runtime: Arc<Runtime>,
+}
+
+impl UtApi {
+    /// Creates a new instance of the blocking `UtApi`.
+    ///
+    /// # Arguments
+    ///
+    /// * `api_key` - An `Option<String>` that holds the API key for authentication.
+    ///   If `None`, the API key is retrieved from the environment.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use utapi_rs::blocking::UtApi;
+    ///
+    /// let api = UtApi::new(Some("your_api_key".to_string()));
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if the API key is not provided and is also not set in the environment,
+    /// or if the client or its runtime cannot be created. See `UtApi::try_new`.
+    pub fn new(api_key: Option<String>) -> UtApi {
+        UtApi::try_new(api_key).unwrap_or_else(|e| panic!("failed to create UtApi: {}", e))
+    }
+
+    /// Creates a new instance of the blocking `UtApi`, returning an error instead of
+    /// panicking if it cannot be configured.
+    ///
+    /// # Arguments
+    ///
+    /// * `api_key` - An `Option<String>` that holds the API key for authentication.
+    ///   If `None`, the credentials are read from the `UPLOADTHING_TOKEN` environment variable,
+    ///   falling back to `UPLOADTHING_SECRET`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use utapi_rs::blocking::UtApi;
+    ///
+    /// let api = UtApi::try_new(Some("your_api_key".to_string()))?;
+    /// # Ok::<(), utapi_rs::ConfigError>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of `utapi_rs::UtApi::try_new`, or `ConfigError::Runtime` if the
+    /// runtime cannot be started.
+    pub fn try_new(api_key: Option<String>) -> Result<UtApi, ConfigError> {
+        UtApi::from_async(crate::UtApi::try_new(api_key)?)
+    }
+
+    /// Creates a new instance of the blocking `UtApi` from a given `UploadthingConfig`.
+    ///
+    /// The configuration is used as is; use `UtApi::try_from_config` to validate it first.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - An `UploadthingConfig` instance containing the configuration for the service.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the proxy URL is invalid, or the client or its runtime cannot be created.
+    /// Use `UtApi::try_from_config` to handle these errors.
+    pub fn from_config(config: UploadthingConfig) -> UtApi {
+        UtApi::from_async(crate::UtApi::from_config(config))
+            .unwrap_or_else(|e| panic!("failed to create UtApi: {}", e))
+    }
+
+    /// Creates a new instance of the blocking `UtApi` that sends its requests through the
+    /// given `Client`.
+    ///
+    /// The timeout, proxy and connection pool settings of `config` are not applied to
+    /// `client`. See `utapi_rs::UtApi::with_client`.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - An `UploadthingConfig` instance containing the configuration for the service.
+    /// * `client` - The async `reqwest::Client` used for all requests.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConfigError::Runtime` if the runtime cannot be started.
+    pub fn with_client(config: UploadthingConfig, client: Client) -> Result<UtApi, ConfigError> {
+        UtApi::from_async(crate::UtApi::with_client(config, client))
+    }
+
+    /// Creates a new instance of the blocking `UtApi` from a given `UploadthingConfig`
+    /// after checking it with `UploadthingConfig::validate`.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - An `UploadthingConfig` instance containing the configuration for the service.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use utapi_rs::blocking::UtApi;
+    /// use utapi_rs::config::UploadthingConfig;
+    ///
+    /// let config = UploadthingConfig::builder().api_key("your_api_key").build()?;
+    /// let api = UtApi::try_from_config(config)?;
+    /// # Ok::<(), utapi_rs::ConfigError>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns a `ConfigError` if the configuration is invalid, the HTTP client cannot be
+    /// initialized or the runtime cannot be started.
+    pub fn try_from_config(config: UploadthingConfig) -> Result<UtApi, ConfigError> {
+        UtApi::from_async(crate::UtApi::try_from_config(config)?)
+    }
+
+    /// Wraps an async `UtApi` in a blocking client with a runtime of its own.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConfigError::Runtime` if the runtime cannot be started.
+    pub fn from_async(inner: crate::UtApi) -> Result<UtApi, ConfigError> {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .thread_name("utapi-blocking")
+            .enable_all()
+            .build()
+            .map_err(ConfigError::Runtime)?;
+        Ok(UtApi {
+            inner,
+            runtime: Arc::new(runtime),
+        })
+    }
+
+    /// Returns the async client this client wraps, e.g. to call
+    /// `utapi_rs::UtApi::request_uploadthing` through `UtApi::block_on`.
+    pub fn as_async(&self) -> &crate::UtApi {
+        &self.inner
+    }
+
+    /// Runs a future on the runtime of this client and blocks until it completes.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use utapi_rs::blocking::UtApi;
+    ///
+    /// let api = UtApi::new(None);
+    /// let body = api.block_on(async {
+    ///     let response = api.as_async().request_uploadthing("/api/getUsageInfo", &()).await?;
+    ///     Ok::<_, utapi_rs::UtApiError>(response.text().await?)
+    /// })?;
+    /// # Ok::<(), utapi_rs::UtApiError>(())
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if called from within an async runtime.
+    pub fn block_on<F: Future>(&self, future: F) -> F::Output {
+        self.runtime.block_on(future)
+    }
+
+    /// Returns the rate-limit state reported by the UploadThing API on its most recent
+    /// response that carried rate-limit headers. See `utapi_rs::UtApi::rate_limit`.
+    pub fn rate_limit(&self) -> Option<RateLimit> {
+        self.inner.rate_limit()
+    }
+
+    /// Deletes a list of files. See `utapi_rs::UtApi::delete_files`.
+    ///
+    /// # Parameters
+    ///
+    /// * `keys`: A `Vec<String>` containing the keys of the files to be deleted.
+    /// * `key_type`: Whether `keys` are file keys or custom IDs.
+    ///
+    /// # Errors
+    ///
+    /// Returns `UtApiError::Api` if the response status is not a success, or
+    /// `UtApiError::Deserialize` if the response cannot be deserialized into a `DeleteFileResponse`.
+    pub fn delete_files(
+        &self,
+        keys: Vec<String>,
+        key_type: KeyType,
+    ) -> Result<DeleteFileResponse, UtApiError> {
+        self.block_on(self.inner.delete_files(keys, key_type))
+    }
+
+    /// Retrieves the URLs for a list of file keys or custom IDs.
+    /// See `utapi_rs::UtApi::get_file_urls`.
+    ///
+    /// # Parameters
+    ///
+    /// * `keys`: A `Vec<String>` containing the keys of the files whose URLs are to be retrieved.
+    /// * `key_type`: Whether `keys` are file keys or custom IDs.
+    ///
+    /// # Errors
+    ///
+    /// Returns `UtApiError::Api` if the response status is not a success, or
+    /// `UtApiError::Deserialize` if the response cannot be deserialized into a `UploadthingUrlsResponse`.
+    pub fn get_file_urls(
+        &self,
+        keys: Vec<String>,
+        key_type: KeyType,
+    ) -> Result<UploadthingUrlsResponse, UtApiError> {
+        self.block_on(self.inner.get_file_urls(keys, key_type))
+    }
+
+    /// Lists one page of files. See `utapi_rs::UtApi::list_files`.
+    ///
+    /// # Parameters
+    ///
+    /// * `opts`: An optional `ListFilesOpts` struct with parameters to control pagination.
+    ///
+    /// # Errors
+    ///
+    /// Returns `UtApiError::Api` if the response status is not a success, or
+    /// `UtApiError::Deserialize` if the response cannot be deserialized into a `UploadthingFileResponse`.
+    pub fn list_files(
+        &self,
+        opts: Option<ListFilesOpts>,
+    ) -> Result<UploadthingFileResponse, UtApiError> {
+        self.block_on(self.inner.list_files(opts))
+    }
+
+    /// Lists every file, one page at a time. See `utapi_rs::UtApi::list_all_files`.
+    ///
+    /// # Parameters
+    ///
+    /// * `opts`: An optional `ListAllFilesOpts` struct with the page size and prefetching.
+    ///
+    /// # Returns
+    ///
+    /// An `Iterator` of the files that requests the next page when the current one is
+    /// exhausted. It ends after the first error.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use utapi_rs::blocking::UtApi;
+    ///
+    /// let api = UtApi::new(None);
+    /// for file in api.list_all_files(None) {
+    ///     let file = file?;
+    ///     println!("{} {}", file.key, file.name);
+    /// }
+    /// # Ok::<(), utapi_rs::UtApiError>(())
+    /// ```
+    pub fn list_all_files(&self, opts: Option<ListAllFilesOpts>) -> AllFiles {
+        AllFiles {
+            files: self.inner.list_all_files(opts).boxed(),
+            runtime: self.runtime.clone(),
+        }
+    }
+
+    /// Renames files. See `utapi_rs::UtApi::rename_files`.
+    ///
+    /// # Parameters
+    ///
+    /// * `files`: A `RenameFilesOpts` struct with the file keys and new names for renaming.
+    ///
+    /// # Errors
+    ///
+    /// Returns `UtApiError::Api` if the response status is not a success.
+    pub fn rename_files(&self, files: RenameFilesOpts) -> Result<(), UtApiError> {
+        self.block_on(self.inner.rename_files(files))
+    }
+
+    /// Gets usage information for the current account. See `utapi_rs::UtApi::get_usage_info`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `UtApiError::Api` if the response status is not a success, or
+    /// `UtApiError::Deserialize` if the response cannot be deserialized into an `UploadthingUsageInfo`.
+    pub fn get_usage_info(&self) -> Result<UploadthingUsageInfo, UtApiError> {
+        self.block_on(self.inner.get_usage_info())
+    }
+
+    /// Requests a presigned URL for a file. See `utapi_rs::UtApi::get_presigned_url`.
+    ///
+    /// # Parameters
+    ///
+    /// * `opts`: A `PresignedUrlOpts` struct containing options for the presigned URL,
+    ///   including the file key or custom ID and the expiration time.
+    ///
+    /// # Errors
+    ///
+    /// Returns `UtApiError::InvalidInput` if `expires_in` is shorter than a second or
+    /// longer than 7 days, or another `UtApiError` if the request fails.
+    pub fn get_presigned_url(&self, opts: PresignedUrlOpts) -> Result<PresignedUrl, UtApiError> {
+        self.block_on(self.inner.get_presigned_url(opts))
+    }
+
+    /// Generates a signed URL to access a file without calling the UploadThing API.
+    /// See `utapi_rs::UtApi::generate_signed_url`.
+    ///
+    /// # Parameters
+    ///
+    /// * `key`: The file key or custom ID of the file.
+    /// * `expires_in`: How long the URL stays valid, at most `Expiry::MAX` (7 days).
+    ///   Defaults to one hour.
+    ///
+    /// # Errors
+    ///
+    /// Returns `UtApiError::InvalidInput` if `expires_in` is shorter than a second or
+    /// longer than 7 days, or if no app ID or API key is configured.
+    pub fn generate_signed_url(
+        &self,
+        key: &str,
+        expires_in: Option<Expiry>,
+    ) -> Result<PresignedUrl, UtApiError> {
+        self.inner.generate_signed_url(key, expires_in)
+    }
+
+    /// Checks that a URL was signed with this client's API key and has not expired.
+    /// See `utapi_rs::UtApi::verify_signed_url`.
+    ///
+    /// # Parameters
+    ///
+    /// * `url`: The signed URL.
+    ///
+    /// # Returns
+    ///
+    /// When the URL expires.
+    ///
+    /// # Errors
+    ///
+    /// Returns `UtApiError::InvalidInput` with the reason if the URL is not signed, its
+    /// signature does not match, or it has expired.
+    pub fn verify_signed_url(&self, url: &str) -> Result<SystemTime, UtApiError> {
+        self.inner.verify_signed_url(url)
+    }
+
+    /// Uploads files. See `utapi_rs::UtApi::upload_files`.
+    ///
+    /// # Parameters
+    ///
+    /// * `files`: The files to upload.
+    /// * `opts`: Optional `UploadFileOpts` applied to every file.
+    /// * `wait_until_done`: Whether to wait until UploadThing has processed each file.
+    ///
+    /// # Returns
+    ///
+    /// An `UploadReport` with one entry per file, in the same order as `files`. Files of a
+    /// batch that UploadThing rejected are reported as `UtApiError::BatchRejected`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `UtApiError::InvalidInput` if `opts` holds metadata and the client uses
+    /// `UploadProtocol::Ingest`.
+    pub fn upload_files(
+        &self,
+        files: Vec<FileObj>,
+        opts: Option<UploadFileOpts>,
+        wait_until_done: bool,
+    ) -> Result<UploadReport, UtApiError> {
+        self.block_on(self.inner.upload_files(files, opts, wait_until_done))
+    }
+
+    /// Downloads files from remote URLs and uploads them.
+    /// See `utapi_rs::UtApi::upload_files_from_url`.
+    ///
+    /// # Parameters
+    ///
+    /// * `urls`: The URLs to upload, each with an optional override for the file name.
+    /// * `opts`: Optional `UploadFileOpts` applied to every file.
+    /// * `wait_until_done`: Whether to wait until UploadThing has processed each file.
+    ///
+    /// # Returns
+    ///
+    /// An `UploadReport` with one entry per URL, in the same order as `urls`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `UtApiError::InvalidInput` if `opts` holds metadata and the client uses
+    /// `UploadProtocol::Ingest`.
+    pub fn upload_files_from_url(
+        &self,
+        urls: Vec<UrlUpload>,
+        opts: Option<UploadFileOpts>,
+        wait_until_done: bool,
+    ) -> Result<UploadReport, UtApiError> {
+        self.block_on(
+            self.inner
+                .upload_files_from_url(urls, opts, wait_until_done),
+        )
+    }
+}
+
+/// The files listed by `UtApi::list_all_files`.
+///
+/// Each call to `next` blocks until the file is available, requesting the next page
+/// when the current one is exhausted.
+pub struct AllFiles {
+    /// The stream of files returned by the async client.
+    files: BoxStream<'static, Result<UploadthingFile, UtApiError>>,
+
+    /// The runtime the stream is polled on.
+    runtime: Arc<Runtime>,
+}
+
+impl Iterator for AllFiles {
+    type Item = Result<UploadthingFile, UtApiError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.runtime.block_on(self.files.next())
+    }
+}
@@ -0,0 +1,347 @@
+use reqwest::{Response, StatusCode};
+use serde::Deserialize;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::rate_limit::{parse_retry_after, RateLimit};
+
+/// The error type returned by every fallible operation on `UtApi`.
+///
+/// Each variant describes a distinct failure domain so that callers can
+/// match on it and decide whether to retry, surface the problem to a user,
+/// or give up. The type is `Send + Sync` and can be moved across
+/// `tokio::spawn` boundaries.
+#[derive(Debug, thiserror::Error)]
+pub enum UtApiError {
+    /// The HTTP request could not be sent or the response could not be read,
+    /// e.g. because of a connection reset, DNS failure or timeout.
+    #[error("transport error: {0}")]
+    Transport(#[from] reqwest::Error),
+
+    /// The UploadThing API answered with a non-success status code.
+    ///
+    /// The `error`, `code` and `data` fields are taken from the JSON error
+    /// body returned by UploadThing when it could be parsed.
+    #[error("UploadThing API returned {status}: {}", .error.as_deref().unwrap_or("no error message"))]
+    Api {
+        /// The HTTP status code of the response.
+        status: StatusCode,
+        /// The human readable `error` message, or the raw body if it was not JSON.
+        error: Option<String>,
+        /// The machine readable `code`, e.g. `BAD_REQUEST`.
+        code: Option<String>,
+        /// Any additional `data` attached to the error.
+        data: Option<serde_json::Value>,
+        /// How long the server asked to wait before retrying, from the `Retry-After` header
+        /// or, for a `429` response without one, from the `x-ratelimit-reset` header.
+        retry_after: Option<Duration>,
+    },
+
+    /// The storage destination of a presigned upload rejected the file.
+    #[error("upload rejected with {status}: {body}")]
+    Upload {
+        /// The HTTP status code of the response.
+        status: StatusCode,
+        /// The raw response body, usually an XML error document.
+        body: String,
+    },
+
+    /// UploadThing rejected the batch of files this file was registered in.
+    ///
+    /// Uploads are registered in batches of `upload_batch_size`; the shared error of the
+    /// batch is kept behind an `Arc` so that every file of the batch can report it.
+    #[error("upload batch was rejected: {0}")]
+    BatchRejected(Arc<UtApiError>),
+
+    /// A remote file passed to `upload_files_from_url` could not be fetched.
+    #[error("fetching {url} returned {status}")]
+    Fetch {
+        /// The URL that was requested.
+        url: String,
+        /// The HTTP status code of the response.
+        status: StatusCode,
+    },
+
+    /// A response body could not be deserialized into the expected model.
+    #[error("failed to deserialize response: {0}")]
+    Deserialize(#[from] serde_json::Error),
+
+    /// Reading a local file failed.
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    /// The arguments passed to a method were rejected before any request was made.
+    #[error("invalid input: {0}")]
+    InvalidInput(String),
+
+    /// A file was uploaded, but UploadThing did not report it as processed before polling
+    /// gave up.
+    #[error("UploadThing did not finish processing file {key} in time")]
+    PollTimeout {
+        /// The key of the file.
+        key: String,
+    },
+
+    /// The operation was cancelled before it could complete.
+    #[error("operation cancelled")]
+    Cancelled,
+
+    /// A spawned upload task panicked or was aborted.
+    #[error("upload task failed: {0}")]
+    Task(#[from] tokio::task::JoinError),
+}
+
+/// The error returned when an `UploadthingConfig` is invalid or a client cannot be
+/// created from it.
+///
+/// It is returned by `UploadthingConfigBuilder::build`, `UploadthingConfig::validate`,
+/// `UtApi::try_new` and `UtApi::try_from_config`, and by their `blocking::UtApi`
+/// counterparts, so that a misconfiguration can be reported at startup instead of panicking.
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    /// No API key was provided and neither `UPLOADTHING_TOKEN` nor `UPLOADTHING_SECRET` is set.
+    #[error("no API key provided and neither UPLOADTHING_TOKEN nor UPLOADTHING_SECRET is set")]
+    MissingApiKey,
+
+    /// An `UPLOADTHING_TOKEN` could not be decoded or is missing required fields.
+    /// The token itself is never included in the message.
+    #[error("invalid UploadThing token: {0}")]
+    InvalidToken(String),
+
+    /// The API key is empty or cannot be sent in an HTTP header.
+    /// The key itself is never included in the message.
+    #[error("invalid API key: {0}")]
+    InvalidApiKey(String),
+
+    /// The host is not an absolute `http` or `https` URL.
+    #[error("invalid host {host:?}: {reason}")]
+    InvalidHost {
+        /// The configured host.
+        host: String,
+        /// Why the host was rejected.
+        reason: String,
+    },
+
+    /// The ingest upload protocol was selected, but the app ID, a region or a valid
+    /// ingest URL is missing.
+    #[error("cannot upload through the ingest protocol: {0}")]
+    InvalidIngest(String),
+
+    /// The configured proxy URL cannot be used.
+    /// The URL itself is never included in the message, as it may hold credentials.
+    #[error("invalid proxy URL: {0}")]
+    InvalidProxy(String),
+
+    /// A configured header value, such as the user agent, cannot be sent in an HTTP header.
+    #[error("invalid {name} header value")]
+    InvalidHeader {
+        /// The name of the header.
+        name: &'static str,
+    },
+
+    /// The HTTP client could not be initialized.
+    #[error("failed to build HTTP client: {0}")]
+    Client(#[from] reqwest::Error),
+
+    /// The runtime of the blocking client could not be started.
+    #[error("failed to start the runtime of the blocking client: {0}")]
+    Runtime(#[source] std::io::Error),
+}
+
+/// The error returned when a webhook request cannot be trusted or understood.
+///
+/// It is returned by `WebhookVerifier::verify` and `WebhookVerifier::mark_processed`.
+/// Every variant except `Deserialize` means the request should be rejected, typically
+/// with a `401` or `403` response.
+#[derive(Debug, thiserror::Error)]
+pub enum WebhookError {
+    /// The request has no `x-uploadthing-signature` header.
+    #[error("the request is not signed")]
+    MissingSignature,
+
+    /// The signature does not match the body, so the request was not sent by UploadThing
+    /// or was modified on the way.
+    #[error("invalid webhook signature")]
+    InvalidSignature,
+
+    /// The request is older, or further in the future, than the verifier tolerates.
+    #[error("the request timestamp is {age:?} off, more than the {tolerance:?} tolerated")]
+    Stale {
+        /// How far the request timestamp is from the current time.
+        age: Duration,
+        /// The tolerance of the verifier.
+        tolerance: Duration,
+    },
+
+    /// The timestamp field of the body cannot be parsed.
+    #[error("invalid webhook timestamp: {0}")]
+    InvalidTimestamp(String),
+
+    /// The body has no timestamp, and the verifier requires one.
+    #[error("the request has no timestamp")]
+    MissingTimestamp,
+
+    /// A request with the same signature has already been marked as processed.
+    #[error("the request has already been processed")]
+    Replayed,
+
+    /// The signature is valid, but the body is not a webhook payload this crate understands.
+    #[error("invalid webhook payload: {0}")]
+    Deserialize(#[from] serde_json::Error),
+}
+
+/// The JSON error body returned by the UploadThing API.
+#[derive(Debug, Deserialize)]
+struct ApiErrorBody {
+    error: Option<String>,
+    code: Option<String>,
+    data: Option<serde_json::Value>,
+}
+
+impl UtApiError {
+    /// Builds an `UtApiError::Api` from a non-success `Response`.
+    ///
+    /// The body is parsed as an UploadThing error object when possible; otherwise
+    /// the raw body text is used as the error message.
+    pub(crate) async fn from_response(response: Response) -> UtApiError {
+        let status = response.status();
+        let retry_after = parse_retry_after(response.headers()).or_else(|| {
+            // Without `Retry-After`, a rate-limited request may be retried once the window resets.
+            if status != StatusCode::TOO_MANY_REQUESTS {
+                return None;
+            }
+            RateLimit::from_headers(response.headers())?.reset_in()
+        });
+        let body = match response.text().await {
+            Ok(body) => body,
+            Err(e) => return UtApiError::Transport(e),
+        };
+
+        match serde_json::from_str::<ApiErrorBody>(&body) {
+            Ok(parsed) => UtApiError::Api {
+                status,
+                error: parsed.error,
+                code: parsed.code,
+                data: parsed.data,
+                retry_after,
+            },
+            Err(_) => UtApiError::Api {
+                status,
+                error: (!body.is_empty()).then_some(body),
+                code: None,
+                data: None,
+                retry_after,
+            },
+        }
+    }
+
+    /// Builds an `UtApiError::Upload` from a non-success response of a presigned upload.
+    pub(crate) async fn from_upload_response(response: Response) -> UtApiError {
+        let status = response.status();
+        match response.text().await {
+            Ok(body) => UtApiError::Upload { status, body },
+            Err(e) => UtApiError::Transport(e),
+        }
+    }
+
+    /// Returns the HTTP status code associated with this error, if any.
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            UtApiError::Api { status, .. }
+            | UtApiError::Upload { status, .. }
+            | UtApiError::Fetch { status, .. } => Some(*status),
+            UtApiError::Transport(e) => e.status(),
+            UtApiError::BatchRejected(e) => e.status(),
+            _ => None,
+        }
+    }
+
+    /// Returns how long the server asked to wait before retrying, if it said so.
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            UtApiError::Api { retry_after, .. } => *retry_after,
+            UtApiError::BatchRejected(e) => e.retry_after(),
+            _ => None,
+        }
+    }
+}
+
+// `UtApiError` must stay usable across `tokio::spawn`.
+const _: fn() = || {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<UtApiError>();
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(status: u16, headers: &[(&str, &str)], body: &str) -> Response {
+        let mut builder = http::Response::builder().status(status);
+        for (name, value) in headers {
+            builder = builder.header(*name, *value);
+        }
+        Response::from(builder.body(body.to_string()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn reads_the_uploadthing_error_body() {
+        let body = r#"{"error":"File not found","code":"NOT_FOUND","data":{"key":"abc"}}"#;
+        let error = UtApiError::from_response(response(404, &[], body)).await;
+        let UtApiError::Api {
+            status,
+            error,
+            code,
+            data,
+            retry_after,
+        } = error
+        else {
+            panic!("expected an API error, got {:?}", error);
+        };
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(error.as_deref(), Some("File not found"));
+        assert_eq!(code.as_deref(), Some("NOT_FOUND"));
+        assert_eq!(data.unwrap()["key"], "abc");
+        assert_eq!(retry_after, None);
+    }
+
+    #[tokio::test]
+    async fn keeps_a_body_that_is_not_json_as_the_message() {
+        let error = UtApiError::from_response(response(502, &[], "Bad Gateway")).await;
+        assert!(matches!(
+            error,
+            UtApiError::Api { error: Some(ref message), code: None, .. } if message == "Bad Gateway"
+        ));
+        let error = UtApiError::from_response(response(502, &[], "")).await;
+        assert!(matches!(error, UtApiError::Api { error: None, .. }));
+    }
+
+    #[tokio::test]
+    async fn reads_retry_after() {
+        let error = UtApiError::from_response(response(503, &[("retry-after", "7")], "")).await;
+        assert_eq!(error.retry_after(), Some(Duration::from_secs(7)));
+        assert_eq!(error.status(), Some(StatusCode::SERVICE_UNAVAILABLE));
+
+        // Retry-After wins over the rate-limit window.
+        let headers = [("retry-after", "7"), ("x-ratelimit-reset", "30")];
+        let error = UtApiError::from_response(response(429, &headers, "")).await;
+        assert_eq!(error.retry_after(), Some(Duration::from_secs(7)));
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_the_rate_limit_reset_on_429_only() {
+        let headers = [("x-ratelimit-remaining", "0"), ("x-ratelimit-reset", "30")];
+        let error = UtApiError::from_response(response(429, &headers, "")).await;
+        let wait = error.retry_after().unwrap();
+        assert!(wait <= Duration::from_secs(30) && wait >= Duration::from_secs(29));
+
+        let error = UtApiError::from_response(response(503, &headers, "")).await;
+        assert_eq!(error.retry_after(), None);
+
+        // The wait is passed through errors that wrap a batch rejection.
+        let error = UtApiError::from_response(response(429, &[("retry-after", "3")], "")).await;
+        let rejected = UtApiError::BatchRejected(Arc::new(error));
+        assert_eq!(rejected.retry_after(), Some(Duration::from_secs(3)));
+        assert_eq!(rejected.status(), Some(StatusCode::TOO_MANY_REQUESTS));
+    }
+}
@@ -3,21 +3,61 @@
 //! The library offers various functionalities such as file uploading, file management, and
 //! retrieving file URLs, which are designed to be used server-side.
 
+// Logging macros must be defined before the modules that use them.
+#[macro_use]
+mod trace;
+
 /// This module defines the configuration structures for `utapi-rs`.
 /// It includes all necessary configurations required to initialize and run the service.
 pub mod config;
 
+/// This module defines the error type returned by the `utapi-rs` API.
+/// It distinguishes transport, API, deserialization, I/O, validation and
+/// cancellation failures so callers can decide how to handle each of them.
+pub mod error;
+
 /// This module contains the data models used throughout the `utapi-rs` application.
 /// These models represent the core data structures that are manipulated and stored
 /// by the service.
 pub mod models;
 
+/// This module describes the rate-limit state reported by the UploadThing API.
+pub mod rate_limit;
+
+// URL signing, webhook signatures and file key generation for the ingest upload protocol.
+mod signing;
+
+/// This module defines the `RetryPolicy` that controls how failed requests are retried.
+pub mod retry;
+
 /// The core API module providing the main functionality of the `utapi-rs` service.
 /// This module includes all the API endpoints and related logic to perform
 /// the intended operations.
 pub mod utapi;
 
+/// This module verifies and parses the webhook callbacks UploadThing sends to an app's
+/// server once files have been uploaded.
+pub mod webhook;
+
+/// This module provides a synchronous client with the same methods as `UtApi`, for
+/// command-line tools and build scripts that do not run an async runtime.
+#[cfg(feature = "blocking")]
+pub mod blocking;
+
+/// This module provides an in-process fake of the UploadThing API, so that code built on
+/// `UtApi` can be tested without network access.
+#[cfg(feature = "testing")]
+pub mod testing;
+
 /// Re-export the `UtApi` struct at the root of the crate for easier access by consumers.
 /// This allows users of the `utapi-rs` library to interact with the API without
 /// needing to traverse the module hierarchy.
 pub use utapi::UtApi;
+
+/// Re-export `reqwest` so that clients passed to `UtApi::with_client` are built with
+/// the same version this crate uses.
+pub use reqwest;
+
+/// Re-export the `UtApiError`, `ConfigError` and `WebhookError` types at the root of the crate so they
+/// can be matched on without importing the `error` module.
+pub use error::{ConfigError, UtApiError, WebhookError};
@@ -1,4 +1,5 @@
 use serde::Deserialize;
+use std::collections::HashMap;
 
 /// Represents the response received from an attempt to delete a file.
 ///
@@ -9,4 +10,10 @@ use serde::Deserialize;
 pub struct DeleteFileResponse {
     /// A boolean indicating whether the file was successfully deleted.
     pub success: bool,
+    /// The number of files that were deleted, if the API reported it.
+    #[serde(rename = "deletedCount", default)]
+    pub deleted_count: Option<u64>,
+    /// Fields returned by the API that this version of the crate does not know about.
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
 }
@@ -1,11 +1,51 @@
-use serde::Serialize;
+use serde::ser::SerializeMap;
+use serde::{Serialize, Serializer};
+
+/// What the keys passed to the file management methods of `UtApi` refer to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum KeyType {
+    /// The key UploadThing assigned to the file when it was uploaded.
+    #[default]
+    FileKey,
+    /// The custom ID set with `FileObj::with_custom_id` when the file was uploaded.
+    CustomId,
+}
+
+impl KeyType {
+    /// The name of the API field that holds a single key of this type.
+    pub(crate) fn field(&self) -> &'static str {
+        match self {
+            KeyType::FileKey => "fileKey",
+            KeyType::CustomId => "customId",
+        }
+    }
+
+    /// The name of the API field that holds a list of keys of this type.
+    pub(crate) fn list_field(&self) -> &'static str {
+        match self {
+            KeyType::FileKey => "fileKeys",
+            KeyType::CustomId => "customIds",
+        }
+    }
+}
 
 /// A payload structure representing a list of keys associated with files.
-#[derive(Serialize)]
+///
+/// The keys are sent as `fileKeys` or `customIds`, depending on `key_type`.
 pub struct FileKeysPayload {
-    /// A vector of strings where each string is a unique key for a file.
-    #[serde(rename(serialize = "fileKeys"))]
-    pub file_keys: Vec<String>,
+    /// A vector of strings where each string is a file key or custom ID.
+    pub keys: Vec<String>,
+    /// What `keys` refer to.
+    pub key_type: KeyType,
+}
+
+impl Serialize for FileKeysPayload {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(1))?;
+        map.serialize_entry(self.key_type.list_field(), &self.keys)?;
+        map.end()
+    }
 }
 
 /// Options for listing files, allowing for pagination.
@@ -30,16 +70,74 @@ impl Default for ListFilesOpts {
     }
 }
 
+/// The default value of `ListAllFilesOpts::page_size`.
+pub const DEFAULT_LIST_PAGE_SIZE: i32 = 500;
+
+/// Options for `UtApi::list_all_files`.
+#[derive(Debug, Clone)]
+pub struct ListAllFilesOpts {
+    /// The number of files requested per page.
+    pub page_size: i32,
+
+    /// Whether to request the next page in the background while the current one
+    /// is being consumed.
+    pub prefetch: bool,
+}
+
+impl Default for ListAllFilesOpts {
+    /// Provides default values for `ListAllFilesOpts`.
+    fn default() -> Self {
+        ListAllFilesOpts {
+            page_size: DEFAULT_LIST_PAGE_SIZE,
+            prefetch: false,
+        }
+    }
+}
+
 /// A structure representing a request to rename a single file.
-#[derive(Serialize)]
 pub struct SingleFileRename {
-    /// The unique key of the file to be renamed.
-    pub file_key: String,
+    /// The file key or custom ID of the file to be renamed.
+    pub key: String,
+
+    /// What `key` refers to.
+    pub key_type: KeyType,
 
     /// The new name to be assigned to the file.
     pub new_name: String,
 }
 
+impl SingleFileRename {
+    /// Creates a request to rename the file with the given file key.
+    pub fn new(file_key: impl Into<String>, new_name: impl Into<String>) -> SingleFileRename {
+        SingleFileRename {
+            key: file_key.into(),
+            key_type: KeyType::FileKey,
+            new_name: new_name.into(),
+        }
+    }
+
+    /// Creates a request to rename the file with the given custom ID.
+    pub fn by_custom_id(
+        custom_id: impl Into<String>,
+        new_name: impl Into<String>,
+    ) -> SingleFileRename {
+        SingleFileRename {
+            key: custom_id.into(),
+            key_type: KeyType::CustomId,
+            new_name: new_name.into(),
+        }
+    }
+}
+
+impl Serialize for SingleFileRename {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(2))?;
+        map.serialize_entry(self.key_type.field(), &self.key)?;
+        map.serialize_entry("newName", &self.new_name)?;
+        map.end()
+    }
+}
+
 /// Options for renaming multiple files in a single operation.
 #[derive(Serialize)]
 pub struct RenameFilesOpts {
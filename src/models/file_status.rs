@@ -1,7 +1,23 @@
 use serde::Deserialize;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(from = "String")]
 /// Represents the status of a file being uploaded.
+///
+/// Statuses that this version of the crate does not know about are kept as `Unknown`,
+/// so new statuses added to the API do not break deserialization.
+///
+/// # Examples
+///
+/// ```
+/// use utapi_rs::models::UploadthingFileStatus;
+///
+/// let status: UploadthingFileStatus = serde_json::from_str("\"Deletion Pending\"").unwrap();
+/// assert_eq!(status, UploadthingFileStatus::DeletionPending);
+///
+/// let status: UploadthingFileStatus = serde_json::from_str("\"Archived\"").unwrap();
+/// assert_eq!(status, UploadthingFileStatus::Unknown("Archived".to_string()));
+/// ```
 pub enum UploadthingFileStatus {
     /// The file is marked for deletion but not yet deleted.
     DeletionPending,
@@ -11,4 +27,38 @@ pub enum UploadthingFileStatus {
     Uploaded,
     /// The file is currently being uploaded.
     Uploading,
+    /// A status this version of the crate does not know about, as sent by the API.
+    Unknown(String),
+}
+
+impl UploadthingFileStatus {
+    /// Returns the status as the API spells it.
+    pub fn as_str(&self) -> &str {
+        match self {
+            UploadthingFileStatus::DeletionPending => "Deletion Pending",
+            UploadthingFileStatus::Failed => "Failed",
+            UploadthingFileStatus::Uploaded => "Uploaded",
+            UploadthingFileStatus::Uploading => "Uploading",
+            UploadthingFileStatus::Unknown(status) => status,
+        }
+    }
+}
+
+impl From<String> for UploadthingFileStatus {
+    /// Parses a status sent by the API. Statuses that are not known are kept as `Unknown`.
+    fn from(status: String) -> Self {
+        match status.as_str() {
+            "Deletion Pending" | "DeletionPending" => UploadthingFileStatus::DeletionPending,
+            "Failed" => UploadthingFileStatus::Failed,
+            "Uploaded" => UploadthingFileStatus::Uploaded,
+            "Uploading" => UploadthingFileStatus::Uploading,
+            _ => UploadthingFileStatus::Unknown(status),
+        }
+    }
+}
+
+impl std::fmt::Display for UploadthingFileStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
 }
@@ -1,4 +1,5 @@
 use serde::Deserialize;
+use std::collections::HashMap;
 
 // Represents a single upload URL and its associated key.
 #[derive(Debug, Deserialize)]
@@ -7,6 +8,9 @@ pub struct UploadthingUrl {
     pub url: String,
     /// The key associated with the uploaded thing, used for referencing it.
     pub key: String,
+    /// Fields returned by the API that this version of the crate does not know about.
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
 }
 
 // A response containing a list of upload URLs and their keys.
@@ -14,4 +18,7 @@ pub struct UploadthingUrl {
 pub struct UploadthingUrlsResponse {
     /// A vector of `UploadthingUrl` objects, each containing a URL and key.
     pub data: Vec<UploadthingUrl>,
+    /// Fields returned by the API that this version of the crate does not know about.
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
 }
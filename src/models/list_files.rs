@@ -1,21 +1,40 @@
 use serde::Deserialize;
+use std::collections::HashMap;
+use std::time::SystemTime;
 
+use crate::models::timestamp::deserialize_millis;
 use crate::models::UploadthingFileStatus;
 
 /// A representation of an uploaded file within the system.
 ///
 /// This struct is used to deserialize information about files that have been
 /// uploaded, including their key, unique identifier, and current status.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct UploadthingFile {
     /// A unique key associated with the file, typically used for retrieval.
     pub key: String,
     /// A unique identifier for the file, often a UUID.
     pub id: String,
+    /// The custom identifier assigned to the file when it was uploaded, if any.
+    #[serde(rename = "customId", default)]
+    pub custom_id: Option<String>,
     /// The current status of the file, indicating whether it's pending, completed, etc.
     pub status: UploadthingFileStatus,
     /// The name of the file.
     pub name: String,
+    /// The size of the file in bytes.
+    #[serde(default)]
+    pub size: Option<u64>,
+    /// When the file was uploaded.
+    #[serde(
+        rename = "uploadedAt",
+        default,
+        deserialize_with = "deserialize_millis"
+    )]
+    pub uploaded_at: Option<SystemTime>,
+    /// Fields returned by the API that this version of the crate does not know about.
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
 }
 
 /// A response structure containing a list of `UploadthingFile` objects.
@@ -26,4 +45,10 @@ pub struct UploadthingFile {
 pub struct UploadthingFileResponse {
     /// A vector of `UploadthingFile` objects representing each file's data.
     pub files: Vec<UploadthingFile>,
+    /// Whether more files follow this page, or `None` if the API did not say.
+    #[serde(default, rename = "hasMore")]
+    pub has_more: Option<bool>,
+    /// Fields returned by the API that this version of the crate does not know about.
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
 }
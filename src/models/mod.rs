@@ -6,7 +6,9 @@ pub use delete_file::DeleteFileResponse;
 // Module for defining options related to file operations.
 pub mod file_opts;
 // Exports types related to file keys payload, listing, and renaming options.
-pub use file_opts::{FileKeysPayload, ListFilesOpts, RenameFilesOpts, SingleFileRename};
+pub use file_opts::{
+    FileKeysPayload, KeyType, ListAllFilesOpts, ListFilesOpts, RenameFilesOpts, SingleFileRename,
+};
 
 // Module for retrieving URLs for uploading.
 pub mod get_urls;
@@ -18,6 +20,9 @@ pub mod list_files;
 // Exports types related to the response of listing files.
 pub use list_files::{UploadthingFile, UploadthingFileResponse};
 
+// Deserialization helpers for timestamps sent by the API.
+mod timestamp;
+
 // Module for checking the status of a file.
 pub mod file_status;
 // Exports the `UploadthingFileStatus` type for external use.
@@ -31,10 +36,15 @@ pub use usage_info::UploadthingUsageInfo;
 // Module for generating presigned URLs for secure file access.
 pub mod presigned_url;
 // Exports types related to presigned URL options and responses.
-pub use presigned_url::{PresignedUrlOpts, PresignedUrlResponse};
+pub use presigned_url::{Expiry, PresignedUrl, PresignedUrlOpts, PresignedUrlResponse};
+
+// Module for reporting the progress of file uploads.
+pub mod upload_progress;
+// Exports the upload progress event types for external use.
+pub use upload_progress::{UploadEvent, UploadEventKind};
 
 pub mod upload_files;
 pub use upload_files::{
-    Acl, ContentDisposition, FileObj, FileUpload, UploadFileOpts, UploadFileResponse,
-    UploadFileResponseData,
+    Acl, ContentDisposition, FileObj, FileSource, FileUpload, IngestUploadResponse, UploadFileOpts,
+    UploadFileResponse, UploadFileResponseData, UploadReport, UploadResult, UrlUpload,
 };
@@ -1,16 +1,156 @@
-use serde::{Deserialize, Serialize};
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Serialize, Serializer};
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime};
+
+use crate::error::UtApiError;
+use crate::models::KeyType;
+
+/// How long a presigned or signed file URL stays valid.
+///
+/// An `Expiry` can be created from a `Duration` or parsed from a string such as `"90s"`,
+/// `"30m"`, `"1h"`, `"2 days"` or `"3600"` (seconds). UploadThing accepts expiries
+/// between one second and `Expiry::MAX`, which is checked when a URL is requested.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use utapi_rs::models::Expiry;
+///
+/// let expiry: Expiry = "1h".parse().unwrap();
+/// assert_eq!(expiry, Expiry::from(Duration::from_secs(3600)));
+/// assert!("8d".parse::<Expiry>().is_ok());
+/// assert!("soon".parse::<Expiry>().is_err());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Expiry(Duration);
+
+impl Expiry {
+    /// The longest expiry UploadThing accepts: 7 days.
+    pub const MAX: Expiry = Expiry(Duration::from_secs(7 * 24 * 60 * 60));
+
+    /// Creates an expiry of the given number of seconds.
+    pub fn from_secs(seconds: u64) -> Expiry {
+        Expiry(Duration::from_secs(seconds))
+    }
+
+    /// Returns the expiry as a `Duration`.
+    pub fn as_duration(&self) -> Duration {
+        self.0
+    }
+
+    /// Returns the expiry in whole seconds, or an error if UploadThing would reject it.
+    pub(crate) fn validated_secs(&self) -> Result<u64, UtApiError> {
+        let seconds = self.0.as_secs();
+        if seconds == 0 || *self > Expiry::MAX {
+            return Err(UtApiError::InvalidInput(format!(
+                "expiresIn must be between 1 and {} seconds",
+                Expiry::MAX.0.as_secs()
+            )));
+        }
+        Ok(seconds)
+    }
+}
+
+impl From<Duration> for Expiry {
+    fn from(duration: Duration) -> Expiry {
+        Expiry(duration)
+    }
+}
+
+impl FromStr for Expiry {
+    type Err = UtApiError;
+
+    /// Parses a number followed by an optional unit: `s`, `m`, `h`, `d` or `w`, or a
+    /// longer spelling such as `min`, `hours` or `days`. A bare number is in seconds.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || UtApiError::InvalidInput(format!("invalid expiry {:?}", s));
+        let s = s.trim();
+        let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+        let (number, unit) = s.split_at(split);
+        let number: u64 = number.parse().map_err(|_| invalid())?;
+        let unit_secs = match unit.trim().to_ascii_lowercase().as_str() {
+            "" | "s" | "sec" | "secs" | "second" | "seconds" => 1,
+            "m" | "min" | "mins" | "minute" | "minutes" => 60,
+            "h" | "hr" | "hrs" | "hour" | "hours" => 60 * 60,
+            "d" | "day" | "days" => 24 * 60 * 60,
+            "w" | "week" | "weeks" => 7 * 24 * 60 * 60,
+            _ => return Err(invalid()),
+        };
+        let seconds = number.checked_mul(unit_secs).ok_or_else(invalid)?;
+        Ok(Expiry::from_secs(seconds))
+    }
+}
+
+impl TryFrom<&str> for Expiry {
+    type Error = UtApiError;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
 
 /// A structure representing the options to generate a presigned URL.
 ///
 /// This structure holds the necessary information to create a presigned URL
 /// which can be used to access a file without requiring further authentication.
-#[derive(Serialize)]
 pub struct PresignedUrlOpts {
-    /// The unique key of the file for which the presigned URL will be generated.
-    pub file_key: String,
-    /// Optional expiration time in seconds for the presigned URL.
+    /// The file key or custom ID of the file for which the presigned URL will be generated.
+    pub key: String,
+    /// What `key` refers to.
+    pub key_type: KeyType,
+    /// Optional expiration time for the presigned URL.
     /// If `None`, a default value will be used.
-    pub expires_in: Option<i32>,
+    pub expires_in: Option<Expiry>,
+}
+
+impl PresignedUrlOpts {
+    /// Creates options for the file with the given file key, using the default expiration.
+    pub fn new(file_key: impl Into<String>) -> PresignedUrlOpts {
+        PresignedUrlOpts {
+            key: file_key.into(),
+            key_type: KeyType::FileKey,
+            expires_in: None,
+        }
+    }
+
+    /// Creates options for the file with the given custom ID, using the default expiration.
+    pub fn by_custom_id(custom_id: impl Into<String>) -> PresignedUrlOpts {
+        PresignedUrlOpts {
+            key: custom_id.into(),
+            key_type: KeyType::CustomId,
+            expires_in: None,
+        }
+    }
+
+    /// Sets how long the presigned URL stays valid.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use utapi_rs::models::{Expiry, PresignedUrlOpts};
+    ///
+    /// let opts = PresignedUrlOpts::new("file_key").with_expires_in(Duration::from_secs(600));
+    /// let opts = PresignedUrlOpts::new("file_key").with_expires_in("1h".parse::<Expiry>().unwrap());
+    /// ```
+    pub fn with_expires_in(mut self, expires_in: impl Into<Expiry>) -> PresignedUrlOpts {
+        self.expires_in = Some(expires_in.into());
+        self
+    }
+}
+
+impl Serialize for PresignedUrlOpts {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry(self.key_type.field(), &self.key)?;
+        if let Some(expires_in) = self.expires_in {
+            map.serialize_entry("expiresIn", &expires_in.as_duration().as_secs())?;
+        }
+        map.end()
+    }
 }
 
 /// A structure representing the response received after successfully generating
@@ -22,4 +162,32 @@ pub struct PresignedUrlOpts {
 pub struct PresignedUrlResponse {
     /// The presigned URL that can be used to access the file.
     pub url: String,
+    /// Fields returned by the API that this version of the crate does not know about.
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
+}
+
+/// A URL that grants access to a file until it expires.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PresignedUrl {
+    /// The URL of the file.
+    pub url: String,
+    /// When the URL stops working, if known. It is read from the URL when possible,
+    /// and otherwise derived from the requested expiry.
+    pub expires_at: Option<SystemTime>,
+    /// The file key or custom ID the URL was requested for.
+    pub key: String,
+}
+
+impl PresignedUrl {
+    /// Returns `true` if the URL expires within `margin` from now, so that it should be
+    /// replaced before it is handed out. Returns `false` if the expiry is unknown, and
+    /// `true` for a known expiry and a margin too large to be represented.
+    pub fn expires_within(&self, margin: Duration) -> bool {
+        self.expires_at.is_some_and(|expires_at| {
+            SystemTime::now()
+                .checked_add(margin)
+                .is_none_or(|limit| expires_at <= limit)
+        })
+    }
 }
@@ -0,0 +1,77 @@
+use serde::{Deserialize, Deserializer};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Deserializes an optional timestamp given in milliseconds since the Unix epoch,
+/// as used by the UploadThing API.
+///
+/// Negative values and values too large for a `SystemTime` deserialize to `None`.
+pub(crate) fn deserialize_millis<'de, D>(deserializer: D) -> Result<Option<SystemTime>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let millis = Option::<serde_json::Number>::deserialize(deserializer)?;
+    let offset = millis.and_then(|millis| match millis.as_u64() {
+        Some(millis) => Some(Duration::from_millis(millis)),
+        // Fractional and negative values are not expected, but are not worth failing over.
+        None => millis
+            .as_f64()
+            .filter(|millis| *millis >= 0.0)
+            .and_then(|millis| Duration::try_from_secs_f64(millis / 1000.0).ok()),
+    });
+    Ok(offset.and_then(|offset| UNIX_EPOCH.checked_add(offset)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Deserialize)]
+    struct Timestamped {
+        #[serde(default, deserialize_with = "deserialize_millis")]
+        at: Option<SystemTime>,
+    }
+
+    fn parse(json: &str) -> Option<SystemTime> {
+        serde_json::from_str::<Timestamped>(json).unwrap().at
+    }
+
+    #[test]
+    fn reads_integer_milliseconds() {
+        assert_eq!(
+            parse(r#"{"at":1700000000123}"#),
+            Some(UNIX_EPOCH + Duration::from_millis(1_700_000_000_123))
+        );
+        assert_eq!(parse(r#"{"at":0}"#), Some(UNIX_EPOCH));
+    }
+
+    #[test]
+    fn reads_fractional_milliseconds() {
+        let at = parse(r#"{"at":1500.5}"#).unwrap();
+        let offset = at.duration_since(UNIX_EPOCH).unwrap();
+        assert_eq!(offset.as_micros(), 1_500_500);
+    }
+
+    #[test]
+    fn reads_missing_and_null_values_as_none() {
+        assert_eq!(parse(r#"{"at":null}"#), None);
+        assert_eq!(parse("{}"), None);
+    }
+
+    #[test]
+    fn reads_negative_values_as_none() {
+        assert_eq!(parse(r#"{"at":-1}"#), None);
+        assert_eq!(parse(r#"{"at":-1.5}"#), None);
+    }
+
+    #[test]
+    fn reads_out_of_range_values_as_none() {
+        // Too large for a `Duration`, and too large to add to the Unix epoch.
+        assert_eq!(parse(r#"{"at":1e25}"#), None);
+        assert_eq!(parse(r#"{"at":1e22}"#), None);
+    }
+
+    #[test]
+    fn rejects_values_that_are_not_numbers() {
+        assert!(serde_json::from_str::<Timestamped>(r#"{"at":"yesterday"}"#).is_err());
+    }
+}
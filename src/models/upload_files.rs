@@ -1,47 +1,279 @@
+use bytes::Bytes;
 use std::{collections::HashMap, path::PathBuf};
+use tokio::io::AsyncRead;
+use tokio::sync::mpsc::UnboundedSender;
+use tokio_util::sync::CancellationToken;
+
+use crate::error::UtApiError;
+use crate::models::UploadEvent;
 
 #[derive(serde::Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum ContentDisposition {
     Inline,
     Attachment,
 }
 
 #[derive(serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum Acl {
     Private,
     PublicRead,
 }
 
+/// Where the contents of a file to upload come from.
+pub enum FileSource {
+    /// A file on the local filesystem, streamed from disk during the upload.
+    Path(PathBuf),
+    /// A buffer that is already held in memory.
+    Bytes(Bytes),
+    /// An async reader that yields exactly `size` bytes. A reader that yields more or
+    /// fewer bytes fails its upload with `UtApiError::InvalidInput`.
+    ///
+    /// Readers can only be consumed once, so uploads from a reader are never retried.
+    Reader {
+        reader: Box<dyn AsyncRead + Send + Unpin>,
+        size: u64,
+    },
+}
+
+impl FileSource {
+    /// Returns the size in bytes of the file contents.
+    pub(crate) async fn size(&self) -> std::io::Result<u64> {
+        match self {
+            FileSource::Path(path) => Ok(tokio::fs::metadata(path).await?.len()),
+            FileSource::Bytes(bytes) => Ok(bytes.len() as u64),
+            FileSource::Reader { size, .. } => Ok(*size),
+        }
+    }
+
+    /// Returns a copy of the source if its contents can be read more than once,
+    /// e.g. to retry an upload. Returns `None` for `Reader` sources.
+    pub(crate) fn try_clone(&self) -> Option<FileSource> {
+        match self {
+            FileSource::Path(path) => Some(FileSource::Path(path.clone())),
+            FileSource::Bytes(bytes) => Some(FileSource::Bytes(bytes.clone())),
+            FileSource::Reader { .. } => None,
+        }
+    }
+
+    /// Turns the source into a reader over the file contents.
+    pub(crate) async fn into_reader(self) -> std::io::Result<Box<dyn AsyncRead + Send + Unpin>> {
+        match self {
+            FileSource::Path(path) => Ok(Box::new(tokio::fs::File::open(path).await?)),
+            FileSource::Bytes(bytes) => Ok(Box::new(std::io::Cursor::new(bytes))),
+            FileSource::Reader { reader, .. } => Ok(reader),
+        }
+    }
+}
+
+impl std::fmt::Debug for FileSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FileSource::Path(path) => f.debug_tuple("Path").field(path).finish(),
+            FileSource::Bytes(bytes) => f.debug_tuple("Bytes").field(&bytes.len()).finish(),
+            FileSource::Reader { size, .. } => f
+                .debug_struct("Reader")
+                .field("size", size)
+                .finish_non_exhaustive(),
+        }
+    }
+}
+
+/// A file to upload with `UtApi::upload_files`.
 #[derive(Debug)]
 pub struct FileObj {
+    /// The name the file is stored under in UploadThing.
     pub name: String,
-    pub path: PathBuf,
+    /// Where the file contents are read from.
+    pub source: FileSource,
+    /// The MIME type of the file. When `None`, it is guessed from the file name.
+    pub content_type: Option<String>,
+    /// A custom ID for the file, such as the ID of a database record, that can be used
+    /// instead of the file key to manage the file later. It must be unique within the app.
+    pub custom_id: Option<String>,
 }
 
-#[derive(serde::Serialize)]
+impl FileObj {
+    /// Creates a `FileObj` that streams its contents from a file on disk.
+    pub fn from_path(name: impl Into<String>, path: impl Into<PathBuf>) -> FileObj {
+        FileObj {
+            name: name.into(),
+            source: FileSource::Path(path.into()),
+            content_type: None,
+            custom_id: None,
+        }
+    }
+
+    /// Creates a `FileObj` from an in-memory buffer such as `Bytes` or `Vec<u8>`.
+    pub fn from_bytes(name: impl Into<String>, bytes: impl Into<Bytes>) -> FileObj {
+        FileObj {
+            name: name.into(),
+            source: FileSource::Bytes(bytes.into()),
+            content_type: None,
+            custom_id: None,
+        }
+    }
+
+    /// Creates a `FileObj` from an async reader that yields exactly `size` bytes.
+    pub fn from_reader(
+        name: impl Into<String>,
+        reader: impl AsyncRead + Send + Unpin + 'static,
+        size: u64,
+    ) -> FileObj {
+        FileObj {
+            name: name.into(),
+            source: FileSource::Reader {
+                reader: Box::new(reader),
+                size,
+            },
+            content_type: None,
+            custom_id: None,
+        }
+    }
+
+    /// Sets the MIME type of the file instead of guessing it from the file name.
+    pub fn with_content_type(mut self, content_type: impl Into<String>) -> FileObj {
+        self.content_type = Some(content_type.into());
+        self
+    }
+
+    /// Sets a custom ID for the file, which `KeyType::CustomId` refers to.
+    pub fn with_custom_id(mut self, custom_id: impl Into<String>) -> FileObj {
+        self.custom_id = Some(custom_id.into());
+        self
+    }
+
+    /// Returns the declared MIME type, or one guessed from the path or name of the file.
+    pub(crate) fn mime_type(&self) -> String {
+        if let Some(content_type) = &self.content_type {
+            return content_type.clone();
+        }
+        let guess = match &self.source {
+            FileSource::Path(path) => mime_guess::from_path(path),
+            _ => mime_guess::from_path(&self.name),
+        };
+        guess.first_or_octet_stream().to_string()
+    }
+}
+
+/// A remote file to upload with `UtApi::upload_files_from_url`.
+#[derive(Debug, Clone)]
+pub struct UrlUpload {
+    /// The URL the file is downloaded from.
+    pub url: String,
+    /// The name the file is stored under. When `None`, it is derived from the response.
+    pub name: Option<String>,
+}
+
+impl UrlUpload {
+    /// Creates an `UrlUpload` whose name is derived from the response.
+    pub fn new(url: impl Into<String>) -> UrlUpload {
+        UrlUpload {
+            url: url.into(),
+            name: None,
+        }
+    }
+
+    /// Overrides the name the file is stored under.
+    pub fn with_name(mut self, name: impl Into<String>) -> UrlUpload {
+        self.name = Some(name.into());
+        self
+    }
+}
+
+impl From<&str> for UrlUpload {
+    fn from(url: &str) -> UrlUpload {
+        UrlUpload::new(url)
+    }
+}
+
+impl From<String> for UrlUpload {
+    fn from(url: String) -> UrlUpload {
+        UrlUpload::new(url)
+    }
+}
+
+#[derive(serde::Serialize, Default)]
 pub struct UploadFileOpts {
+    /// Metadata stored with every file. Not supported with `UploadProtocol::Ingest`.
     pub metadata: Option<HashMap<String, String>>,
     #[serde(rename(serialize = "contentDisposition"))]
     pub content_disposition: Option<ContentDisposition>,
     pub acl: Option<Acl>,
+    /// The maximum number of parts of a single file uploaded at once when UploadThing
+    /// requests a multipart upload. Defaults to 4.
+    #[serde(skip)]
+    pub part_concurrency: Option<usize>,
+    /// The maximum number of files of this call uploaded at once. Defaults to
+    /// `UploadthingConfig::max_concurrency`, which still limits the client as a whole:
+    /// a larger value is lowered to `max_concurrency`, with a warning when the `tracing`
+    /// feature is enabled. Raise `max_concurrency` to upload more files at once.
+    #[serde(skip)]
+    pub concurrency: Option<usize>,
+    /// A channel that receives an `UploadEvent` whenever the state of a file changes.
+    #[serde(skip)]
+    pub progress: Option<UnboundedSender<UploadEvent>>,
+    /// A token that aborts in-flight uploads and polling when cancelled.
+    /// Files that did not finish are reported as `UtApiError::Cancelled`.
+    #[serde(skip)]
+    pub cancellation_token: Option<CancellationToken>,
 }
 
 #[derive(Debug, serde::Deserialize, Clone)]
 pub struct UploadFileResponse {
     pub data: Vec<UploadFileResponseData>,
+    /// Fields returned by the API that this version of the crate does not know about.
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
 }
 
 #[derive(Debug, serde::Deserialize, Clone)]
 pub struct UploadFileResponseData {
+    #[serde(default)]
     pub fields: serde_json::Value,
     #[serde(rename = "fileUrl")]
     pub file_url: String,
     pub key: String,
-    #[serde(rename = "presignedUrl")]
+    #[serde(rename = "presignedUrl", default)]
     pub presigned_url: String,
     pub url: Option<String>,
+    /// The presigned part URLs, present when UploadThing requests a multipart upload.
     pub urls: Option<Vec<String>>,
+    /// The multipart upload id, used to complete or abort the upload.
+    #[serde(rename = "uploadId")]
+    pub upload_id: Option<String>,
+    /// The size in bytes of every part except the last one.
+    #[serde(rename = "chunkSize")]
     pub chunk_size: Option<u64>,
+    /// The number of parts the file is split into.
+    #[serde(rename = "chunkCount")]
+    pub chunk_count: Option<u64>,
+    /// Fields returned by the API that this version of the crate does not know about.
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
+}
+
+/// The response of an ingest server to a completed `PUT` upload.
+#[derive(Debug, serde::Deserialize, Clone)]
+pub struct IngestUploadResponse {
+    /// The URL the file is served from.
+    pub url: String,
+    /// The app-specific URL of the file.
+    #[serde(rename = "appUrl", default)]
+    pub app_url: Option<String>,
+    /// The URL of the file on the UploadThing file server, when provided.
+    #[serde(rename = "ufsUrl", default)]
+    pub ufs_url: Option<String>,
+    /// The hash of the uploaded contents.
+    #[serde(rename = "fileHash", default)]
+    pub file_hash: Option<String>,
+    /// Data returned by the app's upload callback, if any.
+    #[serde(rename = "serverData", default)]
+    pub server_data: Option<serde_json::Value>,
+    /// Fields returned by the API that this version of the crate does not know about.
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
 }
 
 fn default_string() -> String {
@@ -69,6 +301,9 @@ pub struct Fields {
     pub bucket: String,
     #[serde(default = "default_string")]
     pub key: String,
+    /// Fields returned by the API that this version of the crate does not know about.
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
 }
 
 #[derive(Debug, serde::Deserialize, Clone)]
@@ -77,4 +312,48 @@ pub struct FileUpload {
     pub url: String,
     pub name: String,
     pub size: u64,
+    /// The custom ID the file was uploaded with, if any.
+    #[serde(rename = "customId", default)]
+    pub custom_id: Option<String>,
+}
+
+/// The outcome of uploading a single file.
+#[derive(Debug)]
+pub struct UploadResult {
+    /// The name of the file, or the URL when a remote file could not be fetched.
+    pub name: String,
+    /// The uploaded file, or the reason why the upload failed.
+    pub result: Result<FileUpload, UtApiError>,
+}
+
+/// The per-file outcome of an upload, in the same order as the files that were passed in.
+#[derive(Debug, Default)]
+pub struct UploadReport {
+    /// One entry per input file.
+    pub results: Vec<UploadResult>,
+}
+
+impl UploadReport {
+    /// Returns `true` if every file was uploaded successfully.
+    pub fn is_success(&self) -> bool {
+        self.results.iter().all(|r| r.result.is_ok())
+    }
+
+    /// Iterates over the files that were uploaded successfully.
+    pub fn successes(&self) -> impl Iterator<Item = &FileUpload> {
+        self.results.iter().filter_map(|r| r.result.as_ref().ok())
+    }
+
+    /// Iterates over the files that failed, with their position in the input and the error.
+    pub fn failures(&self) -> impl Iterator<Item = (usize, &str, &UtApiError)> {
+        self.results
+            .iter()
+            .enumerate()
+            .filter_map(|(i, r)| r.result.as_ref().err().map(|e| (i, r.name.as_str(), e)))
+    }
+
+    /// Consumes the report and returns the per-file results in input order.
+    pub fn into_results(self) -> Vec<Result<FileUpload, UtApiError>> {
+        self.results.into_iter().map(|r| r.result).collect()
+    }
 }
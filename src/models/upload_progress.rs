@@ -0,0 +1,43 @@
+/// A progress update for one file of an `upload_files` call.
+///
+/// Events are sent to the channel set in `UploadFileOpts::progress` from the task
+/// uploading the file, so events of different files may be interleaved.
+#[derive(Debug, Clone)]
+pub struct UploadEvent {
+    /// The position of the file in the list passed to `upload_files`.
+    pub index: usize,
+    /// The name of the file.
+    pub name: String,
+    /// What happened to the file.
+    pub kind: UploadEventKind,
+}
+
+/// The kind of progress reported by an `UploadEvent`.
+#[derive(Debug, Clone)]
+pub enum UploadEventKind {
+    /// UploadThing returned the presigned destination for the file.
+    PresignReceived {
+        /// The key the file will be stored under.
+        key: String,
+    },
+    /// More of the file has been sent.
+    BytesSent {
+        /// The number of bytes sent so far.
+        sent: u64,
+        /// The total size of the file in bytes.
+        total: u64,
+    },
+    /// All bytes of the file have been uploaded.
+    UploadFinished,
+    /// The client started polling UploadThing until the file is processed.
+    PollingStarted,
+    /// The file has been uploaded successfully.
+    Done,
+    /// The upload of the file was cancelled through `UploadFileOpts::cancellation_token`.
+    Cancelled,
+    /// The upload of the file failed.
+    Failed {
+        /// A description of the error.
+        error: String,
+    },
+}
@@ -1,30 +1,44 @@
 use serde::Deserialize;
+use std::collections::HashMap;
 
 #[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
 /// `UploadthingUsageInfo` holds statistics about the usage of the UploadThing service.
 ///
 /// It contains information about the total bytes transferred, a human-readable representation
 /// of the total bytes, application-specific byte counts, the number of files uploaded,
-/// and the limits imposed on the usage.
+/// and the limits imposed on the usage. The human-readable totals are only present
+/// when the API includes them.
 pub struct UploadthingUsageInfo {
     /// The total number of bytes uploaded.
+    #[serde(alias = "total_bytes")]
     pub total_bytes: i64,
 
     /// A human-readable string representing the total number of bytes uploaded.
-    pub total_readable: String,
+    #[serde(alias = "total_readable", default)]
+    pub total_readable: Option<String>,
 
     /// The total number of bytes uploaded attributed to the application level.
+    #[serde(alias = "app_total_bytes")]
     pub app_total_bytes: f32,
 
     /// A human-readable string representing the application-specific total bytes uploaded.
-    pub app_total_readable: String,
+    #[serde(alias = "app_total_readable", default)]
+    pub app_total_readable: Option<String>,
 
     /// The count of uploaded files.
+    #[serde(alias = "files_uploaded")]
     pub files_uploaded: i32,
 
     /// The upper limit of bytes that can be uploaded, as a floating-point number.
+    #[serde(alias = "limit_bytes")]
     pub limit_bytes: f32,
 
     /// A human-readable string representing the upper limit of bytes that can be uploaded.
-    pub limit_readable: String,
+    #[serde(alias = "limit_readable", default)]
+    pub limit_readable: Option<String>,
+
+    /// Fields returned by the API that this version of the crate does not know about.
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
 }
@@ -0,0 +1,258 @@
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Reset values at or above this are treated as Unix timestamps rather than
+/// a number of seconds from now.
+const EPOCH_THRESHOLD_SECS: u64 = 1_000_000_000;
+
+/// The rate-limit state reported by the UploadThing API on its last response.
+///
+/// The values are taken from the `x-ratelimit-*` (or `ratelimit-*`) and `Retry-After`
+/// headers. Any of them may be missing, in which case the field is `None`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use utapi_rs::UtApi;
+///
+/// # async fn run(api: &UtApi) {
+/// if let Some(rate_limit) = api.rate_limit() {
+///     if rate_limit.is_exhausted() {
+///         if let Some(wait) = rate_limit.reset_in() {
+///             tokio::time::sleep(wait).await;
+///         }
+///     }
+/// }
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RateLimit {
+    /// The number of requests allowed in the current window.
+    pub limit: Option<u64>,
+    /// The number of requests left in the current window.
+    pub remaining: Option<u64>,
+    /// When the current window resets.
+    pub reset_at: Option<SystemTime>,
+    /// How long the server asked the client to wait before retrying.
+    pub retry_after: Option<Duration>,
+    /// When the response carrying these headers was received.
+    pub observed_at: SystemTime,
+}
+
+impl RateLimit {
+    /// Reads the rate-limit headers of a response.
+    ///
+    /// Returns `None` if the response carries none of them.
+    pub(crate) fn from_headers(headers: &HeaderMap) -> Option<RateLimit> {
+        let now = SystemTime::now();
+        let limit = header_u64(headers, &["x-ratelimit-limit", "ratelimit-limit"]);
+        let remaining = header_u64(headers, &["x-ratelimit-remaining", "ratelimit-remaining"]);
+        // A reset too far out to be represented is dropped rather than trusted.
+        let reset_at =
+            header_u64(headers, &["x-ratelimit-reset", "ratelimit-reset"]).and_then(|reset| {
+                if reset >= EPOCH_THRESHOLD_SECS {
+                    UNIX_EPOCH.checked_add(Duration::from_secs(reset))
+                } else {
+                    now.checked_add(Duration::from_secs(reset))
+                }
+            });
+        let retry_after = parse_retry_after(headers);
+
+        if limit.is_none() && remaining.is_none() && reset_at.is_none() && retry_after.is_none() {
+            return None;
+        }
+
+        Some(RateLimit {
+            limit,
+            remaining,
+            reset_at,
+            retry_after,
+            observed_at: now,
+        })
+    }
+
+    /// Returns `true` if the server reported that no requests are left in the current window.
+    pub fn is_exhausted(&self) -> bool {
+        self.remaining == Some(0) || self.retry_after.is_some()
+    }
+
+    /// Returns how long until the limit resets, or until the server allows a retry,
+    /// whichever is later. Returns `None` if that moment has already passed or is unknown.
+    ///
+    /// A `Retry-After` too large to be added to the time it was received is treated as
+    /// lying beyond any reset, and returned as is.
+    pub fn reset_in(&self) -> Option<Duration> {
+        let retry_at = match self.retry_after {
+            Some(wait) => match self.observed_at.checked_add(wait) {
+                Some(retry_at) => Some(retry_at),
+                None => return Some(wait),
+            },
+            None => None,
+        };
+        let until = match (self.reset_at, retry_at) {
+            (Some(reset), Some(retry)) => reset.max(retry),
+            (reset, retry) => reset.or(retry)?,
+        };
+        until.duration_since(SystemTime::now()).ok()
+    }
+}
+
+/// Parses a `Retry-After` header, given either as a number of seconds or as an HTTP date.
+pub(crate) fn parse_retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    // A date in the past means the client may retry right away.
+    let date = httpdate::parse_http_date(value).ok()?;
+    Some(
+        date.duration_since(SystemTime::now())
+            .unwrap_or(Duration::ZERO),
+    )
+}
+
+/// Returns the first of the given headers that holds an unsigned integer.
+fn header_u64(headers: &HeaderMap, names: &[&str]) -> Option<u64> {
+    names
+        .iter()
+        .find_map(|name| headers.get(*name)?.to_str().ok()?.trim().parse().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.insert(*name, HeaderValue::from_str(value).unwrap());
+        }
+        headers
+    }
+
+    /// Asserts that `actual` is within a second below `expected`, allowing for the time
+    /// that passes while the test runs.
+    fn assert_about(actual: Option<Duration>, expected: Duration) {
+        let actual = actual.expect("a duration");
+        assert!(
+            actual <= expected && actual + Duration::from_secs(1) >= expected,
+            "{:?} is not about {:?}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn parses_retry_after_in_seconds() {
+        let parsed = parse_retry_after(&headers(&[("retry-after", " 120 ")]));
+        assert_eq!(parsed, Some(Duration::from_secs(120)));
+        let parsed = parse_retry_after(&headers(&[("retry-after", "0")]));
+        assert_eq!(parsed, Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn parses_retry_after_as_an_http_date() {
+        let date = httpdate::fmt_http_date(SystemTime::now() + Duration::from_secs(30));
+        assert_about(
+            parse_retry_after(&headers(&[("retry-after", &date)])),
+            Duration::from_secs(30),
+        );
+
+        // A date in the past allows a retry right away.
+        let parsed = parse_retry_after(&headers(&[(
+            "retry-after",
+            "Wed, 21 Oct 2015 07:28:00 GMT",
+        )]));
+        assert_eq!(parsed, Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn ignores_missing_or_malformed_retry_after() {
+        assert_eq!(parse_retry_after(&HeaderMap::new()), None);
+        for value in ["-1", "1.5", "soon", "Wed, 21 Oct 2015"] {
+            assert_eq!(
+                parse_retry_after(&headers(&[("retry-after", value)])),
+                None,
+                "{}",
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn reads_rate_limit_headers() {
+        let rate_limit = RateLimit::from_headers(&headers(&[
+            ("x-ratelimit-limit", "100"),
+            ("x-ratelimit-remaining", "7"),
+            ("x-ratelimit-reset", "30"),
+        ]))
+        .unwrap();
+        assert_eq!(rate_limit.limit, Some(100));
+        assert_eq!(rate_limit.remaining, Some(7));
+        assert_eq!(rate_limit.retry_after, None);
+        assert!(!rate_limit.is_exhausted());
+        assert_about(rate_limit.reset_in(), Duration::from_secs(30));
+
+        // The unprefixed names of the IETF draft are read too.
+        let rate_limit =
+            RateLimit::from_headers(&headers(&[("ratelimit-remaining", "0")])).unwrap();
+        assert_eq!(rate_limit.remaining, Some(0));
+        assert!(rate_limit.is_exhausted());
+        assert_eq!(rate_limit.reset_in(), None);
+
+        assert!(RateLimit::from_headers(&headers(&[("content-type", "text/plain")])).is_none());
+    }
+
+    #[test]
+    fn reads_large_reset_values_as_unix_timestamps() {
+        let reset_at = SystemTime::now() + Duration::from_secs(90);
+        let timestamp = reset_at.duration_since(UNIX_EPOCH).unwrap().as_secs();
+        let rate_limit =
+            RateLimit::from_headers(&headers(&[("x-ratelimit-reset", &timestamp.to_string())]))
+                .unwrap();
+        assert_eq!(
+            rate_limit.reset_at,
+            Some(UNIX_EPOCH + Duration::from_secs(timestamp))
+        );
+        assert_about(rate_limit.reset_in(), Duration::from_secs(90));
+    }
+
+    #[test]
+    fn resets_at_the_later_of_the_window_and_retry_after() {
+        let rate_limit = RateLimit::from_headers(&headers(&[
+            ("x-ratelimit-reset", "10"),
+            ("retry-after", "20"),
+        ]))
+        .unwrap();
+        assert!(rate_limit.is_exhausted());
+        assert_about(rate_limit.reset_in(), Duration::from_secs(20));
+    }
+
+    #[test]
+    fn drops_a_reset_that_cannot_be_represented() {
+        let rate_limit = RateLimit::from_headers(&headers(&[
+            ("x-ratelimit-limit", "100"),
+            ("x-ratelimit-reset", "18446744073709551615"),
+        ]))
+        .unwrap();
+        assert_eq!(rate_limit.limit, Some(100));
+        assert_eq!(rate_limit.reset_at, None);
+        assert_eq!(rate_limit.reset_in(), None);
+    }
+
+    #[test]
+    fn waits_for_a_retry_after_that_cannot_be_represented() {
+        let rate_limit = RateLimit::from_headers(&headers(&[
+            ("x-ratelimit-reset", "10"),
+            ("retry-after", "10000000000000000000"),
+        ]))
+        .unwrap();
+        assert_eq!(
+            rate_limit.reset_in(),
+            Some(Duration::from_secs(10_000_000_000_000_000_000))
+        );
+    }
+}
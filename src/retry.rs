@@ -0,0 +1,551 @@
+use rand::{thread_rng, Rng};
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+use crate::error::UtApiError;
+
+/// How random jitter is added to the exponential back-off between attempts.
+///
+/// Jitter spreads out retries from many clients that failed at the same time,
+/// so that they do not hit the service again in lockstep.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Jitter {
+    /// Wait exactly the computed back-off.
+    None,
+    /// Wait a random duration between zero and the computed back-off.
+    Full,
+    /// Wait half of the computed back-off plus a random duration up to the other half.
+    Equal,
+    /// Wait the computed back-off plus a random duration up to the given bound.
+    Bounded(Duration),
+}
+
+/// Controls how failed requests to UploadThing and to presigned upload URLs are retried.
+///
+/// The delay before retry `n` is `base_backoff * 2^n`, capped at `max_backoff` and
+/// adjusted by `jitter`. When the API says how long to wait, through a `Retry-After`
+/// header or the `x-ratelimit-reset` header of a `429` response, that delay is used
+/// instead; if it is longer than `max_retry_after`, the error is returned right away.
+/// The default policy makes up to 5 attempts and retries transport failures as well as
+/// `408`, `429`, `500`, `502`, `503` and `504` responses.
+///
+/// Requests that register files with `/api/uploadFiles` or complete a multipart upload
+/// are not idempotent: if one reached UploadThing but its response was lost, repeating it
+/// would register or complete the file twice. Those requests are only retried when the
+/// error shows they were not processed, i.e. on `408` and `429` responses and on failures
+/// to connect, and only if the policy retries those at all. See
+/// `RetryPolicy::is_retryable_non_idempotent`.
+///
+/// The policy also paces the polling that waits for UploadThing to process an uploaded
+/// file: the back-off applies between polls, and `poll_attempts` limits how many polls
+/// are made before the upload fails with `UtApiError::PollTimeout`. A file that is still
+/// being processed has not failed, so `max_attempts` does not limit polling, and
+/// `RetryPolicy::none` keeps the default number of polls. With the default back-off,
+/// 20 polls wait about 16 minutes in total; lower `max_backoff` or `poll_attempts` to
+/// give up sooner.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use utapi_rs::config::UploadthingConfig;
+/// use utapi_rs::retry::{Jitter, RetryPolicy};
+///
+/// let policy = RetryPolicy {
+///     max_attempts: 3,
+///     base_backoff: Duration::from_millis(200),
+///     jitter: Jitter::Full,
+///     ..RetryPolicy::default()
+/// };
+/// let config = UploadthingConfig::builder()
+///     .api_key("your_api_key")
+///     .retry_policy(policy)
+///     .build()
+///     .unwrap();
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetryPolicy {
+    /// The maximum number of attempts, including the first one. A value of 1 disables retries.
+    pub max_attempts: u32,
+    /// The back-off before the first retry, doubled for each following retry.
+    pub base_backoff: Duration,
+    /// The upper bound of the back-off, before jitter is applied.
+    pub max_backoff: Duration,
+    /// The jitter strategy applied to each back-off.
+    pub jitter: Jitter,
+    /// The HTTP status codes that are worth retrying.
+    pub retryable_status_codes: Vec<u16>,
+    /// Whether connection failures, resets and timeouts are retried.
+    pub retry_transport_errors: bool,
+    /// The longest delay requested by the server that the policy waits for. A request
+    /// asking for a longer wait fails instead of blocking the caller.
+    #[serde(default = "default_max_retry_after")]
+    pub max_retry_after: Duration,
+    /// The maximum number of polls, including the first one, made while waiting for
+    /// UploadThing to process an uploaded file. Values below 1 are treated as 1.
+    #[serde(default = "default_poll_attempts")]
+    pub poll_attempts: u32,
+}
+
+/// The default value of `RetryPolicy::max_retry_after`.
+pub const DEFAULT_MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+fn default_max_retry_after() -> Duration {
+    DEFAULT_MAX_RETRY_AFTER
+}
+
+/// The default value of `RetryPolicy::poll_attempts`.
+pub const DEFAULT_POLL_ATTEMPTS: u32 = 20;
+
+fn default_poll_attempts() -> u32 {
+    DEFAULT_POLL_ATTEMPTS
+}
+
+impl Default for RetryPolicy {
+    fn default() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 5,
+            base_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(64),
+            jitter: Jitter::Bounded(Duration::from_millis(500)),
+            retryable_status_codes: vec![408, 429, 500, 502, 503, 504],
+            retry_transport_errors: true,
+            max_retry_after: DEFAULT_MAX_RETRY_AFTER,
+            poll_attempts: DEFAULT_POLL_ATTEMPTS,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A policy that never retries. Polling for processed files still makes up to
+    /// `poll_attempts` polls.
+    pub fn none() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 1,
+            ..RetryPolicy::default()
+        }
+    }
+
+    /// Returns the delay to wait after the given failed attempt, counting from 1.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(31);
+        let backoff = self
+            .base_backoff
+            .saturating_mul(1 << exponent)
+            .min(self.max_backoff);
+
+        let mut rng = thread_rng();
+        match self.jitter {
+            Jitter::None => backoff,
+            Jitter::Full => backoff.mul_f64(rng.gen_range(0.0..=1.0)),
+            Jitter::Equal => backoff / 2 + (backoff / 2).mul_f64(rng.gen_range(0.0..=1.0)),
+            Jitter::Bounded(bound) => backoff + bound.mul_f64(rng.gen_range(0.0..=1.0)),
+        }
+    }
+
+    /// Returns `true` if the given error is worth retrying under this policy.
+    pub fn is_retryable(&self, error: &UtApiError) -> bool {
+        match error {
+            UtApiError::Transport(e) => match e.status() {
+                Some(status) => self.retryable_status_codes.contains(&status.as_u16()),
+                None => {
+                    self.retry_transport_errors
+                        && (e.is_connect() || e.is_timeout() || e.is_request() || e.is_body())
+                }
+            },
+            UtApiError::BatchRejected(e) => self.is_retryable(e),
+            _ => error
+                .status()
+                .is_some_and(|status| self.retryable_status_codes.contains(&status.as_u16())),
+        }
+    }
+
+    /// Returns `true` if the given error is worth retrying for a request that must not be
+    /// processed twice: the error must be retryable under this policy and show that the
+    /// request was not processed, i.e. a `408` or `429` response or a failure to connect.
+    pub fn is_retryable_non_idempotent(&self, error: &UtApiError) -> bool {
+        match error {
+            UtApiError::Transport(e) if e.status().is_none() => {
+                self.retry_transport_errors && e.is_connect()
+            }
+            UtApiError::BatchRejected(e) => self.is_retryable_non_idempotent(e),
+            _ => {
+                let unprocessed = error
+                    .status()
+                    .is_some_and(|status| matches!(status.as_u16(), 408 | 429));
+                unprocessed && self.is_retryable(error)
+            }
+        }
+    }
+
+    /// Returns the delay to wait after the given failed attempt, counting from 1, or
+    /// `None` if the server asked to wait longer than `max_retry_after`.
+    pub(crate) fn retry_delay(&self, error: &UtApiError, attempt: u32) -> Option<Duration> {
+        match error.retry_after() {
+            Some(wait) if wait > self.max_retry_after => None,
+            Some(wait) => Some(wait),
+            None => Some(self.backoff(attempt)),
+        }
+    }
+
+    /// Runs `operation` until it succeeds, fails with an error that is not retryable,
+    /// or `max_attempts` is reached. The operation receives the attempt number, counting from 1.
+    pub(crate) async fn run<F, Fut, T>(&self, operation: F) -> Result<T, UtApiError>
+    where
+        F: FnMut(u32) -> Fut,
+        Fut: Future<Output = Result<T, UtApiError>>,
+    {
+        self.run_until_cancelled(&CancellationToken::new(), operation)
+            .await
+    }
+
+    /// Like `run`, for a request that must not be processed twice. Only the errors
+    /// accepted by `is_retryable_non_idempotent` are retried.
+    pub(crate) async fn run_non_idempotent<F, Fut, T>(&self, operation: F) -> Result<T, UtApiError>
+    where
+        F: FnMut(u32) -> Fut,
+        Fut: Future<Output = Result<T, UtApiError>>,
+    {
+        self.retry(
+            &CancellationToken::new(),
+            RetryPolicy::is_retryable_non_idempotent,
+            operation,
+        )
+        .await
+    }
+
+    /// Like `run`, but stops with `UtApiError::Cancelled` as soon as `cancel` is cancelled,
+    /// whether an attempt is in flight or the policy is waiting to retry.
+    pub(crate) async fn run_until_cancelled<F, Fut, T>(
+        &self,
+        cancel: &CancellationToken,
+        operation: F,
+    ) -> Result<T, UtApiError>
+    where
+        F: FnMut(u32) -> Fut,
+        Fut: Future<Output = Result<T, UtApiError>>,
+    {
+        self.retry(cancel, RetryPolicy::is_retryable, operation)
+            .await
+    }
+
+    /// Runs `operation` until it succeeds, fails with an error that `retryable` rejects,
+    /// `max_attempts` is reached or `cancel` is cancelled.
+    async fn retry<F, Fut, T>(
+        &self,
+        cancel: &CancellationToken,
+        retryable: fn(&RetryPolicy, &UtApiError) -> bool,
+        mut operation: F,
+    ) -> Result<T, UtApiError>
+    where
+        F: FnMut(u32) -> Fut,
+        Fut: Future<Output = Result<T, UtApiError>>,
+    {
+        let mut attempt = 1;
+        loop {
+            let result = tokio::select! {
+                result = operation(attempt) => result,
+                _ = cancel.cancelled() => return Err(UtApiError::Cancelled),
+            };
+            match result {
+                Ok(value) => return Ok(value),
+                Err(e) if attempt < self.max_attempts && retryable(self, &e) => {
+                    let Some(delay) = self.retry_delay(&e, attempt) else {
+                        trace_warn!(
+                            attempt,
+                            error = %e,
+                            "server asked to wait longer than max_retry_after, giving up"
+                        );
+                        return Err(e);
+                    };
+                    trace_warn!(
+                        attempt,
+                        delay_ms = delay.as_millis() as u64,
+                        error = %e,
+                        "request failed, retrying"
+                    );
+                    tokio::select! {
+                        _ = tokio::time::sleep(delay) => {}
+                        _ = cancel.cancelled() => return Err(UtApiError::Cancelled),
+                    }
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::StatusCode;
+    use std::sync::Arc;
+
+    fn api_error(status: u16) -> UtApiError {
+        api_error_after(status, None)
+    }
+
+    fn api_error_after(status: u16, retry_after: Option<Duration>) -> UtApiError {
+        UtApiError::Api {
+            status: StatusCode::from_u16(status).unwrap(),
+            error: None,
+            code: None,
+            data: None,
+            retry_after,
+        }
+    }
+
+    fn fast_policy() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 3,
+            base_backoff: Duration::from_millis(1),
+            jitter: Jitter::None,
+            ..RetryPolicy::default()
+        }
+    }
+
+    /// A transport error from connecting to a port nothing listens on.
+    async fn connect_error() -> UtApiError {
+        let listener = std::net::TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+        let error = reqwest::get(format!("http://{}", addr)).await.unwrap_err();
+        assert!(error.is_connect(), "{:?}", error);
+        UtApiError::Transport(error)
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        let policy = RetryPolicy {
+            base_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_millis(1000),
+            jitter: Jitter::None,
+            ..RetryPolicy::default()
+        };
+        let backoffs: Vec<u64> = (1..=6)
+            .map(|attempt| policy.backoff(attempt).as_millis() as u64)
+            .collect();
+        assert_eq!(backoffs, [100, 200, 400, 800, 1000, 1000]);
+        // Huge attempt numbers neither overflow nor exceed the cap.
+        assert_eq!(policy.backoff(u32::MAX), Duration::from_millis(1000));
+        assert_eq!(policy.backoff(0), Duration::from_millis(100));
+    }
+
+    #[test]
+    fn jitter_stays_within_its_bounds() {
+        let backoff = Duration::from_millis(800);
+        let policy = |jitter| RetryPolicy {
+            base_backoff: backoff,
+            jitter,
+            ..RetryPolicy::default()
+        };
+        let bound = Duration::from_millis(300);
+        for _ in 0..200 {
+            assert!(policy(Jitter::Full).backoff(1) <= backoff);
+            let equal = policy(Jitter::Equal).backoff(1);
+            assert!(equal >= backoff / 2 && equal <= backoff, "{:?}", equal);
+            let bounded = policy(Jitter::Bounded(bound)).backoff(1);
+            assert!(
+                bounded >= backoff && bounded <= backoff + bound,
+                "{:?}",
+                bounded
+            );
+        }
+    }
+
+    #[test]
+    fn retries_the_configured_status_codes() {
+        let policy = RetryPolicy::default();
+        for status in [408, 429, 500, 502, 503, 504] {
+            assert!(policy.is_retryable(&api_error(status)), "{}", status);
+        }
+        for status in [400, 401, 403, 404, 409, 501] {
+            assert!(!policy.is_retryable(&api_error(status)), "{}", status);
+        }
+
+        let upload = UtApiError::Upload {
+            status: StatusCode::SERVICE_UNAVAILABLE,
+            body: String::new(),
+        };
+        assert!(policy.is_retryable(&upload));
+        let rejected = UtApiError::BatchRejected(Arc::new(api_error(502)));
+        assert!(policy.is_retryable(&rejected));
+        assert!(!policy.is_retryable(&UtApiError::Cancelled));
+        assert!(!policy.is_retryable(&UtApiError::InvalidInput(String::new())));
+
+        let custom = RetryPolicy {
+            retryable_status_codes: vec![409],
+            ..RetryPolicy::default()
+        };
+        assert!(custom.is_retryable(&api_error(409)));
+        assert!(!custom.is_retryable(&api_error(503)));
+    }
+
+    #[test]
+    fn retries_non_idempotent_requests_only_if_they_were_not_processed() {
+        let policy = RetryPolicy::default();
+        assert!(policy.is_retryable_non_idempotent(&api_error(408)));
+        assert!(policy.is_retryable_non_idempotent(&api_error(429)));
+        for status in [500, 502, 503, 504] {
+            assert!(
+                !policy.is_retryable_non_idempotent(&api_error(status)),
+                "{}",
+                status
+            );
+        }
+
+        // The policy must still retry the status at all.
+        let no_429 = RetryPolicy {
+            retryable_status_codes: vec![500],
+            ..RetryPolicy::default()
+        };
+        assert!(!no_429.is_retryable_non_idempotent(&api_error(429)));
+    }
+
+    #[tokio::test]
+    async fn retries_connect_errors_when_transport_errors_are_retried() {
+        let error = connect_error().await;
+        assert!(RetryPolicy::default().is_retryable(&error));
+        assert!(RetryPolicy::default().is_retryable_non_idempotent(&error));
+
+        let policy = RetryPolicy {
+            retry_transport_errors: false,
+            ..RetryPolicy::default()
+        };
+        assert!(!policy.is_retryable(&error));
+        assert!(!policy.is_retryable_non_idempotent(&error));
+    }
+
+    #[test]
+    fn waits_as_long_as_the_server_asks_up_to_max_retry_after() {
+        let policy = RetryPolicy {
+            jitter: Jitter::None,
+            max_retry_after: Duration::from_secs(10),
+            ..RetryPolicy::default()
+        };
+        let asked = api_error_after(429, Some(Duration::from_secs(3)));
+        assert_eq!(policy.retry_delay(&asked, 1), Some(Duration::from_secs(3)));
+        let too_long = api_error_after(429, Some(Duration::from_secs(11)));
+        assert_eq!(policy.retry_delay(&too_long, 1), None);
+        assert_eq!(
+            policy.retry_delay(&api_error(503), 2),
+            Some(policy.base_backoff * 2)
+        );
+    }
+
+    #[tokio::test]
+    async fn run_retries_until_success() {
+        let mut attempts = Vec::new();
+        let result = fast_policy()
+            .run(|attempt| {
+                attempts.push(attempt);
+                async move {
+                    match attempt {
+                        1 | 2 => Err(api_error(503)),
+                        _ => Ok("done"),
+                    }
+                }
+            })
+            .await;
+        assert_eq!(result.unwrap(), "done");
+        assert_eq!(attempts, [1, 2, 3]);
+    }
+
+    #[tokio::test]
+    async fn run_gives_up_after_max_attempts() {
+        let mut attempts = 0;
+        let result: Result<(), _> = fast_policy()
+            .run(|_| {
+                attempts += 1;
+                async { Err(api_error(503)) }
+            })
+            .await;
+        assert_eq!(
+            result.unwrap_err().status(),
+            Some(StatusCode::SERVICE_UNAVAILABLE)
+        );
+        assert_eq!(attempts, 3);
+
+        attempts = 0;
+        let _ = RetryPolicy::none()
+            .run(|_| {
+                attempts += 1;
+                async { Err::<(), _>(api_error(503)) }
+            })
+            .await;
+        assert_eq!(attempts, 1);
+    }
+
+    #[tokio::test]
+    async fn run_stops_at_errors_that_are_not_retryable() {
+        for error in [
+            api_error(400),
+            api_error_after(429, Some(Duration::from_secs(3600))),
+        ] {
+            let mut error = Some(error);
+            let mut attempts = 0;
+            let result: Result<(), _> = fast_policy()
+                .run(|_| {
+                    attempts += 1;
+                    let error = error.take().unwrap();
+                    async { Err(error) }
+                })
+                .await;
+            assert!(result.is_err());
+            assert_eq!(attempts, 1);
+        }
+    }
+
+    #[tokio::test]
+    async fn run_non_idempotent_does_not_repeat_processed_requests() {
+        let mut attempts = 0;
+        let result: Result<(), _> = fast_policy()
+            .run_non_idempotent(|_| {
+                attempts += 1;
+                async { Err(api_error(503)) }
+            })
+            .await;
+        assert!(result.is_err());
+        assert_eq!(attempts, 1);
+
+        let mut attempts = 0;
+        let result = fast_policy()
+            .run_non_idempotent(|attempt| {
+                attempts += 1;
+                async move {
+                    match attempt {
+                        1 => Err(api_error(429)),
+                        _ => Ok(()),
+                    }
+                }
+            })
+            .await;
+        assert!(result.is_ok());
+        assert_eq!(attempts, 2);
+    }
+
+    #[tokio::test]
+    async fn run_until_cancelled_stops_while_waiting_to_retry() {
+        let policy = RetryPolicy {
+            base_backoff: Duration::from_secs(3600),
+            max_backoff: Duration::from_secs(3600),
+            ..RetryPolicy::default()
+        };
+        let cancel = CancellationToken::new();
+        let mut attempts = 0;
+        let run = policy.run_until_cancelled(&cancel, |_| {
+            attempts += 1;
+            async { Err::<(), _>(api_error(503)) }
+        });
+        let cancel_soon = async {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            cancel.cancel();
+        };
+        let (result, ()) = tokio::join!(run, cancel_soon);
+        assert!(matches!(result, Err(UtApiError::Cancelled)));
+        assert_eq!(attempts, 1);
+    }
+}
@@ -0,0 +1,797 @@
+//! Request signing and file key generation for the UploadThing ingest protocol,
+//! and signature checks for webhooks.
+//!
+//! Ingest servers accept uploads to URLs that are signed locally with the app's
+//! API key, so no round trip to the UploadThing API is needed before an upload.
+//! UploadThing signs the webhooks it sends with the same key, and accepts file access
+//! URLs signed with it.
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use url::Url;
+
+use crate::utapi::encode_uri_component;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// The scheme prefix of every signature.
+const SIGNATURE_PREFIX: &str = "hmac-sha256=";
+
+/// The alphabet file keys are built from, shuffled per app.
+const KEY_ALPHABET: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+
+/// The minimum length of the app part of a file key.
+const APP_ID_MIN_LENGTH: usize = 12;
+
+/// The minimum length of the file part of a file key.
+const FILE_SEED_MIN_LENGTH: usize = 36;
+
+/// Signs `payload` with `secret` and returns the signature in UploadThing's
+/// `hmac-sha256=<hex>` format.
+pub(crate) fn sign_payload(payload: &str, secret: &str) -> String {
+    let mut mac =
+        HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(payload.as_bytes());
+    format!(
+        "{}{}",
+        SIGNATURE_PREFIX,
+        hex::encode(mac.finalize().into_bytes())
+    )
+}
+
+/// Checks a signature in UploadThing's `hmac-sha256=<hex>` format against `payload`.
+///
+/// The comparison runs in constant time, so the result does not reveal how much of
+/// a forged signature was correct.
+pub(crate) fn verify_signature(payload: &[u8], signature: &str, secret: &str) -> bool {
+    let Some(Ok(expected)) = signature
+        .trim()
+        .strip_prefix(SIGNATURE_PREFIX)
+        .map(hex::decode)
+    else {
+        return false;
+    };
+    let mut mac =
+        HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(payload);
+    mac.verify_slice(&expected).is_ok()
+}
+
+/// Appends an `expires` timestamp and `params` to `url` and signs the result.
+///
+/// The parameter values are percent-encoded before they are added to the query,
+/// like the official SDKs do, and the signature covers the full URL including
+/// the query. The signature is appended as the `signature` parameter.
+pub(crate) fn generate_signed_url(
+    url: &str,
+    secret: &str,
+    expires_at: SystemTime,
+    params: &[(&str, String)],
+) -> Result<String, url::ParseError> {
+    let mut url = Url::parse(url)?;
+    let expires = expires_at
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+
+    {
+        let mut query = url.query_pairs_mut();
+        query.append_pair("expires", &expires.to_string());
+        for (name, value) in params {
+            query.append_pair(name, &encode_uri_component(value));
+        }
+    }
+
+    let signature = sign_payload(url.as_str(), secret);
+    url.query_pairs_mut().append_pair("signature", &signature);
+    Ok(url.into())
+}
+
+/// Checks a URL produced by `generate_signed_url` and returns when it expires.
+///
+/// Returns the reason if the URL cannot be parsed, its signature does not match, or it
+/// has expired at `now`.
+pub(crate) fn verify_signed_url(
+    url: &str,
+    secret: &str,
+    now: SystemTime,
+) -> Result<SystemTime, String> {
+    let mut url = Url::parse(url).map_err(|e| e.to_string())?;
+    let mut signature = None;
+    let mut expires = None;
+    let mut params = Vec::new();
+    for (name, value) in url.query_pairs() {
+        match name.as_ref() {
+            "signature" => signature = Some(value.into_owned()),
+            "expires" => {
+                expires = Some(value.to_string());
+                params.push((name.into_owned(), value.into_owned()));
+            }
+            _ => params.push((name.into_owned(), value.into_owned())),
+        }
+    }
+    let signature = signature.ok_or("the URL is not signed")?;
+
+    // The signature covers the URL as it was before the signature was appended.
+    url.query_pairs_mut().clear().extend_pairs(&params);
+    if !verify_signature(url.as_str().as_bytes(), &signature, secret) {
+        return Err("the signature does not match".to_string());
+    }
+
+    let expires_at = expires
+        .and_then(|expires| expires.parse::<u64>().ok())
+        .and_then(|millis| UNIX_EPOCH.checked_add(Duration::from_millis(millis)))
+        .ok_or("the URL has no valid expiry")?;
+    if expires_at <= now {
+        return Err("the URL has expired".to_string());
+    }
+    Ok(expires_at)
+}
+
+/// Generates a file key for `app_id`.
+///
+/// Keys are built the way UploadThing's SDKs build them: a prefix derived from the
+/// app ID followed by a part derived from `seed`, both encoded with Sqids over an
+/// alphabet shuffled by the app ID. Callers should pass a seed that is unique per
+/// upload.
+pub(crate) fn generate_key(app_id: &str, seed: &str) -> String {
+    let alphabet = shuffle_with_seed(KEY_ALPHABET, app_id);
+    let app_part = sqids_encode(
+        &alphabet,
+        hash_string(app_id).unsigned_abs(),
+        APP_ID_MIN_LENGTH,
+    );
+    let file_part = sqids_encode(
+        &alphabet,
+        hash_string(seed).unsigned_abs(),
+        FILE_SEED_MIN_LENGTH,
+    );
+    format!("{}{}", app_part, file_part)
+}
+
+/// The 31-bit string hash used by the SDKs, computed over UTF-16 code units.
+///
+/// The result keeps its sign, which `shuffle_with_seed` depends on; key parts
+/// encode its absolute value.
+fn hash_string(value: &str) -> i64 {
+    let units: Vec<u16> = value.encode_utf16().collect();
+    let mut hash: i32 = 5381;
+    for unit in units.into_iter().rev() {
+        hash = hash.wrapping_mul(33) ^ i32::from(unit);
+    }
+
+    // Fold the sign bit into bit 30 so that the result stays a small integer.
+    let hash = hash as u32;
+    i64::from(((hash & 0xbfff_ffff) | ((hash >> 1) & 0x4000_0000)) as i32)
+}
+
+/// Shuffles `alphabet` deterministically with the hash of `seed`.
+fn shuffle_with_seed(alphabet: &[u8], seed: &str) -> Vec<u8> {
+    let mut chars = alphabet.to_vec();
+    let seed = hash_string(seed);
+    let len = chars.len() as i64;
+    for i in 0..chars.len() {
+        let position = i as i64;
+        let j = ((seed % (position + 1)) + position) % len;
+        chars.swap(i, j as usize);
+    }
+    chars
+}
+
+/// Encodes a single number with the Sqids algorithm, padded to `min_length`.
+///
+/// Like Sqids, an ID that contains a blocked word is generated again with the next
+/// offset into the alphabet.
+fn sqids_encode(alphabet: &[u8], number: u64, min_length: usize) -> String {
+    let mut base = alphabet.to_vec();
+    sqids_shuffle(&mut base);
+
+    // Sqids gives up after trying every offset; keep the last ID instead of failing.
+    let mut id = sqids_encode_with_offset(&base, number, min_length, 0);
+    for increment in 1..=base.len() {
+        if !is_blocked_id(&id) {
+            break;
+        }
+        id = sqids_encode_with_offset(&base, number, min_length, increment);
+    }
+    id
+}
+
+/// Encodes a single number with the already shuffled Sqids alphabet `base`, starting
+/// `increment` characters further into it than usual.
+fn sqids_encode_with_offset(
+    base: &[u8],
+    number: u64,
+    min_length: usize,
+    increment: usize,
+) -> String {
+    let len = base.len();
+
+    let offset = (base[(number % len as u64) as usize] as usize + 1 + increment) % len;
+    let mut alphabet: Vec<u8> = base[offset..]
+        .iter()
+        .chain(&base[..offset])
+        .copied()
+        .collect();
+    let prefix = alphabet[0];
+    alphabet.reverse();
+
+    let mut id = vec![prefix];
+    id.extend(sqids_to_id(number, &alphabet[1..]));
+
+    if id.len() < min_length {
+        id.push(alphabet[0]);
+        while id.len() < min_length {
+            sqids_shuffle(&mut alphabet);
+            let take = (min_length - id.len()).min(alphabet.len());
+            id.extend_from_slice(&alphabet[..take]);
+        }
+    }
+
+    // The alphabet is ASCII, so every byte is a valid character.
+    id.into_iter().map(char::from).collect()
+}
+
+/// The consistent shuffle of the Sqids algorithm.
+fn sqids_shuffle(chars: &mut [u8]) {
+    let len = chars.len();
+    let (mut i, mut j) = (0, len - 1);
+    while j > 0 {
+        let r = (i * j + chars[i] as usize + chars[j] as usize) % len;
+        chars.swap(i, r);
+        i += 1;
+        j -= 1;
+    }
+}
+
+/// Writes `number` in the base of `alphabet`.
+fn sqids_to_id(mut number: u64, alphabet: &[u8]) -> Vec<u8> {
+    let len = alphabet.len() as u64;
+    let mut id = Vec::new();
+    loop {
+        id.push(alphabet[(number % len) as usize]);
+        number /= len;
+        if number == 0 {
+            break;
+        }
+    }
+    id.reverse();
+    id
+}
+
+/// Returns `true` if `id` contains a word of `SQIDS_BLOCKLIST`, matched the way Sqids
+/// matches it: short IDs and words must match exactly, words with digits only at
+/// either end of the ID, and other words anywhere in it.
+fn is_blocked_id(id: &str) -> bool {
+    let id = id.to_ascii_lowercase();
+    SQIDS_BLOCKLIST.iter().any(|word| {
+        if word.len() > id.len() {
+            false
+        } else if id.len() <= 3 || word.len() <= 3 {
+            id == *word
+        } else if word.bytes().any(|b| b.is_ascii_digit()) {
+            id.starts_with(word) || id.ends_with(word)
+        } else {
+            id.contains(word)
+        }
+    })
+}
+
+/// The default blocklist of Sqids, which the SDKs encode file keys with. Every word is
+/// lowercase and made of characters of `KEY_ALPHABET`, so none of them are filtered out.
+const SQIDS_BLOCKLIST: &[&str] = &[
+    "0rgasm",
+    "1d10t",
+    "1d1ot",
+    "1di0t",
+    "1diot",
+    "1eccacu10",
+    "1eccacu1o",
+    "1eccacul0",
+    "1eccaculo",
+    "1mbec11e",
+    "1mbec1le",
+    "1mbeci1e",
+    "1mbecile",
+    "a11upat0",
+    "a11upato",
+    "a1lupat0",
+    "a1lupato",
+    "aand",
+    "ah01e",
+    "ah0le",
+    "aho1e",
+    "ahole",
+    "al1upat0",
+    "al1upato",
+    "allupat0",
+    "allupato",
+    "ana1",
+    "ana1e",
+    "anal",
+    "anale",
+    "anus",
+    "arrapat0",
+    "arrapato",
+    "arsch",
+    "arse",
+    "ass",
+    "b00b",
+    "b00be",
+    "b01ata",
+    "b0ceta",
+    "b0iata",
+    "b0ob",
+    "b0obe",
+    "b0sta",
+    "b1tch",
+    "b1te",
+    "b1tte",
+    "ba1atkar",
+    "balatkar",
+    "bastard0",
+    "bastardo",
+    "batt0na",
+    "battona",
+    "bitch",
+    "bite",
+    "bitte",
+    "bo0b",
+    "bo0be",
+    "bo1ata",
+    "boceta",
+    "boiata",
+    "boob",
+    "boobe",
+    "bosta",
+    "bran1age",
+    "bran1er",
+    "bran1ette",
+    "bran1eur",
+    "bran1euse",
+    "branlage",
+    "branler",
+    "branlette",
+    "branleur",
+    "branleuse",
+    "c0ck",
+    "c0g110ne",
+    "c0g11one",
+    "c0g1i0ne",
+    "c0g1ione",
+    "c0gl10ne",
+    "c0gl1one",
+    "c0gli0ne",
+    "c0glione",
+    "c0na",
+    "c0nnard",
+    "c0nnasse",
+    "c0nne",
+    "c0u111es",
+    "c0u11les",
+    "c0u1l1es",
+    "c0u1lles",
+    "c0ul11es",
+    "c0ul1les",
+    "c0ull1es",
+    "c0ulles",
+    "c11t",
+    "c1it",
+    "c1t",
+    "cac0",
+    "caca",
+    "cacc0",
+    "cacca",
+    "cazz0",
+    "cazzo",
+    "chiava",
+    "chiavata",
+    "chinga",
+    "chingada",
+    "chink",
+    "cl1t",
+    "clit",
+    "cock",
+    "cog110ne",
+    "cog11one",
+    "cog1i0ne",
+    "cog1ione",
+    "cogl10ne",
+    "cogl1one",
+    "cogli0ne",
+    "coglione",
+    "cona",
+    "connard",
+    "connasse",
+    "conne",
+    "cou111es",
+    "cou11les",
+    "cou1l1es",
+    "cou1lles",
+    "coul11es",
+    "coul1les",
+    "coull1es",
+    "coulles",
+    "cracker",
+    "crap",
+    "cu10",
+    "cu1att0ne",
+    "cu1attone",
+    "cu1er0",
+    "cu1ero",
+    "cu1o",
+    "culatt0ne",
+    "culattone",
+    "culer0",
+    "culero",
+    "culo",
+    "cum",
+    "cunt",
+    "d11d0",
+    "d11do",
+    "d1ck",
+    "d1ld0",
+    "d1ldo",
+    "damn",
+    "de1ch",
+    "deich",
+    "depp",
+    "di1d0",
+    "di1do",
+    "dick",
+    "dild0",
+    "dildo",
+    "dyke",
+    "encu1e",
+    "encule",
+    "enema",
+    "enf01re",
+    "enf0ire",
+    "enfo1re",
+    "enfoire",
+    "estup1d0",
+    "estup1do",
+    "estupid0",
+    "estupido",
+    "etr0n",
+    "etron",
+    "f0da",
+    "f0der",
+    "f0ttere",
+    "f0tters1",
+    "f0ttersi",
+    "f0tze",
+    "f0utre",
+    "f1ca",
+    "f1cker",
+    "f1ga",
+    "fag",
+    "fica",
+    "ficker",
+    "figa",
+    "foda",
+    "foder",
+    "fottere",
+    "fotters1",
+    "fottersi",
+    "fotze",
+    "foutre",
+    "fr0c10",
+    "fr0c1o",
+    "fr0ci0",
+    "fr0cio",
+    "fr0sc10",
+    "fr0sc1o",
+    "fr0sci0",
+    "fr0scio",
+    "froc10",
+    "froc1o",
+    "froci0",
+    "frocio",
+    "frosc10",
+    "frosc1o",
+    "frosci0",
+    "froscio",
+    "fuck",
+    "fux0r",
+    "g0ve",
+    "g0ven",
+    "g1mp",
+    "g1n0",
+    "g1no",
+    "gay",
+    "ge1",
+    "gei",
+    "geil",
+    "gimp",
+    "gin0",
+    "gino",
+    "h0m0",
+    "h0mo",
+    "h0re",
+    "hom0",
+    "homo",
+    "hore",
+    "idi0t",
+    "idiot",
+    "imbec11e",
+    "imbec1le",
+    "imbeci1e",
+    "imbecile",
+    "j1zz",
+    "jizz",
+    "k1ke",
+    "kike",
+    "l3sb1",
+    "l3sbi",
+    "le1ch",
+    "les8o",
+    "les8os",
+    "lesb1",
+    "lesbi",
+    "lesbo",
+    "lesbos",
+    "m1nch1a",
+    "m1nchia",
+    "m1nga",
+    "m1nge",
+    "m1nkia",
+    "m1nkie",
+    "m1ntch1a",
+    "merd0",
+    "merda",
+    "merde",
+    "minch1a",
+    "minchia",
+    "minga",
+    "minge",
+    "mink1a",
+    "minkia",
+    "n1gg3r",
+    "n1gger",
+    "nazi",
+    "negr0",
+    "negro",
+    "nigg3r",
+    "nigger",
+    "orgasm",
+    "p00p",
+    "p0mp1n0",
+    "p0mp1no",
+    "p0mpin0",
+    "p0mpino",
+    "p0op",
+    "p0rca",
+    "p0rn",
+    "p0rnhub",
+    "p1m0",
+    "p1mo",
+    "p1rla",
+    "p1sc10",
+    "p1sc1o",
+    "p1sci0",
+    "p1scio",
+    "p1ss",
+    "p1sser",
+    "paki",
+    "pec0r1na",
+    "pec0rina",
+    "pecor1na",
+    "pecorina",
+    "pen1s",
+    "penis",
+    "pi1la",
+    "pim0",
+    "pimp",
+    "pip1",
+    "pipi",
+    "pirla",
+    "pisc10",
+    "pisc1o",
+    "pisci0",
+    "piscio",
+    "piss",
+    "pisser",
+    "po0p",
+    "po1ata",
+    "pompin0",
+    "pompino",
+    "porca",
+    "porn",
+    "porno",
+    "potta",
+    "pussy",
+    "qu3er",
+    "queer",
+    "r1mj0b",
+    "r1mjob",
+    "rape",
+    "rapist",
+    "retard",
+    "rimj0b",
+    "rimjob",
+    "s0rra",
+    "s1ut",
+    "s1utty",
+    "scheisse",
+    "schlampe",
+    "sex",
+    "sh1t",
+    "shit",
+    "slut",
+    "slutty",
+    "sp1c",
+    "spic",
+    "spunk",
+    "t1tt1e5",
+    "t1tties",
+    "tette",
+    "tits",
+    "titt1e5",
+    "titties",
+    "tr01a",
+    "tr0ia",
+    "tr0mba",
+    "tro1a",
+    "troia",
+    "tromba",
+    "vaffanc0u1o",
+    "vaffanc0ul0",
+    "vaffanc0ulo",
+    "vaffancu1o",
+    "vaffancul0",
+    "vaffanculo",
+    "vag1na",
+    "vagina",
+    "wank",
+    "wh0r3",
+    "wh0re",
+    "whore",
+    "xxx",
+    "z0cc01a",
+    "z0cc0la",
+    "z0cco1a",
+    "z0ccola",
+    "zocc01a",
+    "zocc0la",
+    "zocco1a",
+    "zoccola",
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The expected values follow `generateKey` and `generateSignedURL` of
+    // `@uploadthing/shared`, run with `Date.now()` fixed.
+    const SECRET: &str = "sk_live_123";
+
+    fn at_millis(millis: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn generate_key_matches_sdk() {
+        let seed = r#"["foo.txt",1234,"text/plain",1700000000000,1700000000001]"#;
+        assert_eq!(
+            generate_key("app123", seed),
+            "Lu6wlnqnmWqdwBiiA7TukYqvlsc5tb9V1ZRJfjNGUmMK0Fnd"
+        );
+        assert_eq!(
+            generate_key("yzrlzsx6sp", r#"["a.png",1,"image/png"]"#),
+            "V55vRUs7NsPOPtiak0O3ACXpz1FWeh724QN0yJKtIGVD9Hui"
+        );
+    }
+
+    #[test]
+    fn generate_key_skips_blocked_words() {
+        // Without the blocklist this seed encodes to `...ddFi2lD882kBplYiXT4EWaNK7CPqt3bce0frD`.
+        assert_eq!(
+            generate_key("app123", "seed-36"),
+            "Lu6wlnqnmWqdriWg2AdckaPXY1jutIW3lFinf5qB2g7Er94D"
+        );
+    }
+
+    #[test]
+    fn generate_signed_url_matches_sdk() {
+        let url = generate_signed_url(
+            "https://sea1.ingest.uploadthing.com/abc",
+            SECRET,
+            at_millis(1_700_000_000_000),
+            &[
+                ("x-ut-identifier", "app123".to_string()),
+                ("x-ut-file-name", "my file.txt".to_string()),
+                ("x-ut-file-size", "1234".to_string()),
+                ("x-ut-file-type", "text/plain".to_string()),
+            ],
+        )
+        .unwrap();
+        assert_eq!(
+            url,
+            "https://sea1.ingest.uploadthing.com/abc?expires=1700000000000\
+             &x-ut-identifier=app123&x-ut-file-name=my%2520file.txt&x-ut-file-size=1234\
+             &x-ut-file-type=text%252Fplain\
+             &signature=hmac-sha256%3D80d1c9c7b6afca25df89c223cfcce524f9a8db26866c6263ad1952684ba1834e"
+        );
+
+        let url = generate_signed_url(
+            "https://app123.ufs.sh/f/abc",
+            SECRET,
+            at_millis(1_700_000_000_000),
+            &[],
+        )
+        .unwrap();
+        assert_eq!(
+            url,
+            "https://app123.ufs.sh/f/abc?expires=1700000000000\
+             &signature=hmac-sha256%3D05ff69baf317f26aa9bcae986ab0781dec3fb189e2decb0b8104916376994558"
+        );
+    }
+
+    fn signed_ingest_url() -> String {
+        generate_signed_url(
+            "https://sea1.ingest.uploadthing.com/abc",
+            SECRET,
+            at_millis(1_700_000_000_000),
+            &[
+                ("x-ut-identifier", "app123".to_string()),
+                ("x-ut-file-name", "my file.txt".to_string()),
+            ],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn verify_signed_url_accepts_generated_urls() {
+        let url = signed_ingest_url();
+        assert_eq!(
+            verify_signed_url(&url, SECRET, at_millis(1_699_999_999_999)),
+            Ok(at_millis(1_700_000_000_000))
+        );
+    }
+
+    #[test]
+    fn verify_signed_url_rejects_expired_urls() {
+        let url = signed_ingest_url();
+        assert_eq!(
+            verify_signed_url(&url, SECRET, at_millis(1_700_000_000_000)),
+            Err("the URL has expired".to_string())
+        );
+    }
+
+    #[test]
+    fn verify_signed_url_rejects_modified_urls() {
+        let url = signed_ingest_url();
+        let now = at_millis(1_600_000_000_000);
+        let modified = [
+            url.replace("/abc?", "/abd?"),
+            url.replace("app123", "app124"),
+            url.replace("expires=1700000000000", "expires=1800000000000"),
+            url.replace("&signature=", "&x-ut-acl=public-read&signature="),
+            url.replace("sea1.", "fra1."),
+        ];
+        for url in modified {
+            assert_eq!(
+                verify_signed_url(&url, SECRET, now),
+                Err("the signature does not match".to_string()),
+                "{}",
+                url
+            );
+        }
+        assert_eq!(
+            verify_signed_url(&url, "sk_live_456", now),
+            Err("the signature does not match".to_string())
+        );
+    }
+
+    #[test]
+    fn verify_signed_url_rejects_unsigned_urls() {
+        let url = signed_ingest_url();
+        let (unsigned, _) = url.split_once("&signature=").unwrap();
+        assert_eq!(
+            verify_signed_url(unsigned, SECRET, at_millis(1_600_000_000_000)),
+            Err("the URL is not signed".to_string())
+        );
+    }
+}
@@ -0,0 +1,1162 @@
+use crate::config::UploadthingConfig;
+use crate::signing;
+use crate::utapi::encode_uri_component;
+use crate::UtApi;
+use bytes::Bytes;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{header, Body, Method, Request, Response, Server, StatusCode};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::convert::Infallible;
+use std::net::{SocketAddr, TcpListener};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::task::JoinHandle;
+use url::Url;
+
+/// The API key the fake server accepts.
+pub const FAKE_API_KEY: &str = "sk_test_fake_uploadthing";
+
+/// The app ID of the configuration returned by `FakeUploadThing::config`.
+pub const FAKE_APP_ID: &str = "fakeapp";
+
+/// How long the URLs returned by `/api/requestFileAccess` stay valid when no
+/// `expiresIn` is requested.
+const DEFAULT_FILE_ACCESS_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// The number of requests per window reported by injected `429` failures.
+const FAKE_RATE_LIMIT: u64 = 100;
+
+/// The storage limit reported by `/api/getUsageInfo`.
+const FAKE_LIMIT_BYTES: u64 = 2 * 1024 * 1024 * 1024;
+
+/// An endpoint of the fake server, used to inject failures and latency and to count
+/// requests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Endpoint {
+    /// `POST /api/uploadFiles`, which registers files and returns their presigned POST targets.
+    UploadFiles,
+    /// `POST /upload/:key`, the presigned POST target the file contents are sent to.
+    PresignedPost,
+    /// `GET /api/pollUpload/:key`.
+    PollUpload,
+    /// `POST /api/listFiles`.
+    ListFiles,
+    /// `POST /api/deleteFile`.
+    DeleteFile,
+    /// `POST /api/getFileUrl`.
+    GetFileUrl,
+    /// `POST /api/renameFiles`.
+    RenameFiles,
+    /// `POST /api/getUsageInfo`.
+    GetUsageInfo,
+    /// `POST /api/requestFileAccess`.
+    RequestFileAccess,
+    /// `GET /f/:key`, which serves the contents of an uploaded file.
+    File,
+    /// `PUT /ingest/:key`, the signed ingest URL the file contents are sent to with
+    /// `UploadProtocol::Ingest`.
+    IngestUpload,
+    /// `HEAD /ingest/:key`, which reports how many bytes of an interrupted ingest upload
+    /// the server has in `x-ut-range-start`.
+    IngestRangeStart,
+    /// `PUT /multipart/:key/:part`, the presigned URL a part of a multipart upload is sent
+    /// to. See `FakeUploadThing::enable_multipart`.
+    UploadPart,
+    /// `POST /api/completeMultipart`, which assembles the parts of a multipart upload.
+    CompleteMultipart,
+    /// `POST /api/failureCallback`, which aborts a multipart upload.
+    FailureCallback,
+}
+
+impl Endpoint {
+    /// Every endpoint of the fake server.
+    pub const ALL: [Endpoint; 15] = [
+        Endpoint::UploadFiles,
+        Endpoint::PresignedPost,
+        Endpoint::PollUpload,
+        Endpoint::ListFiles,
+        Endpoint::DeleteFile,
+        Endpoint::GetFileUrl,
+        Endpoint::RenameFiles,
+        Endpoint::GetUsageInfo,
+        Endpoint::RequestFileAccess,
+        Endpoint::File,
+        Endpoint::IngestUpload,
+        Endpoint::IngestRangeStart,
+        Endpoint::UploadPart,
+        Endpoint::CompleteMultipart,
+        Endpoint::FailureCallback,
+    ];
+
+    /// Finds the endpoint a request is sent to, along with the file key in its path.
+    fn route<'a>(method: &Method, path: &'a str) -> Option<(Endpoint, Option<&'a str>)> {
+        if let Some(key) = path.strip_prefix("/upload/") {
+            return (method == Method::POST).then_some((Endpoint::PresignedPost, Some(key)));
+        }
+        if let Some(key) = path.strip_prefix("/api/pollUpload/") {
+            return (method == Method::GET).then_some((Endpoint::PollUpload, Some(key)));
+        }
+        if let Some(key) = path.strip_prefix("/f/") {
+            return (method == Method::GET).then_some((Endpoint::File, Some(key)));
+        }
+        if let Some(key) = path.strip_prefix("/ingest/") {
+            let endpoint = match *method {
+                Method::PUT => Endpoint::IngestUpload,
+                Method::HEAD => Endpoint::IngestRangeStart,
+                _ => return None,
+            };
+            return Some((endpoint, Some(key)));
+        }
+        if let Some(part) = path.strip_prefix("/multipart/") {
+            return (method == Method::PUT).then_some((Endpoint::UploadPart, Some(part)));
+        }
+        if method != Method::POST {
+            return None;
+        }
+        let endpoint = match path {
+            "/api/uploadFiles" => Endpoint::UploadFiles,
+            "/api/listFiles" => Endpoint::ListFiles,
+            "/api/deleteFile" => Endpoint::DeleteFile,
+            "/api/getFileUrl" => Endpoint::GetFileUrl,
+            "/api/renameFiles" => Endpoint::RenameFiles,
+            "/api/getUsageInfo" => Endpoint::GetUsageInfo,
+            "/api/requestFileAccess" => Endpoint::RequestFileAccess,
+            "/api/completeMultipart" => Endpoint::CompleteMultipart,
+            "/api/failureCallback" => Endpoint::FailureCallback,
+            _ => return None,
+        };
+        Some((endpoint, None))
+    }
+}
+
+/// A file stored by the fake server.
+#[derive(Debug, Clone)]
+pub struct FakeFile {
+    /// The file key.
+    pub key: String,
+    /// The file ID.
+    pub id: String,
+    /// The custom ID the file was registered with, if any.
+    pub custom_id: Option<String>,
+    /// The name of the file.
+    pub name: String,
+    /// The MIME type the file was registered with.
+    pub content_type: String,
+    /// The size of the file in bytes; the registered size until the contents arrive.
+    pub size: u64,
+    /// Whether all of the contents have been uploaded.
+    pub uploaded: bool,
+    /// Whether the upload was aborted through `/api/failureCallback`.
+    pub failed: bool,
+    /// The contents of the file, empty until they are uploaded. Holds the bytes received
+    /// so far while an interrupted ingest upload has not been resumed.
+    pub content: Bytes,
+    /// When the file was registered or, once uploaded, when its contents arrived.
+    pub uploaded_at: SystemTime,
+}
+
+impl FakeFile {
+    /// Returns the file as the UploadThing API lists it.
+    fn to_json(&self) -> Value {
+        let uploaded_at = self
+            .uploaded_at
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+        json!({
+            "key": self.key,
+            "id": self.id,
+            "customId": self.custom_id,
+            "name": self.name,
+            "size": self.size,
+            "status": match (self.uploaded, self.failed) {
+                (true, _) => "Uploaded",
+                (false, true) => "Failed",
+                (false, false) => "Uploading",
+            },
+            "uploadedAt": uploaded_at,
+        })
+    }
+}
+
+/// The in-memory state of the fake server.
+#[derive(Default)]
+struct State {
+    /// The stored files, in the order they were registered.
+    files: Vec<FakeFile>,
+    /// The status codes the next requests to an endpoint fail with, in order.
+    failures: HashMap<Endpoint, VecDeque<StatusCode>>,
+    /// How long requests to an endpoint are delayed.
+    latency: HashMap<Endpoint, Duration>,
+    /// How many requests each endpoint has received.
+    requests: HashMap<Endpoint, usize>,
+    /// How many requests to each endpoint are being answered right now.
+    in_flight: HashMap<Endpoint, usize>,
+    /// The most requests to each endpoint that were answered at once.
+    max_in_flight: HashMap<Endpoint, usize>,
+    /// After how many bytes each of the next ingest uploads is cut off, in order.
+    interruptions: VecDeque<u64>,
+    /// The size from which files are uploaded in parts, and the size of each part.
+    multipart: Option<(u64, u64)>,
+    /// The multipart uploads in progress, by file key.
+    multipart_uploads: HashMap<String, MultipartUpload>,
+    /// Whether `/api/pollUpload` reports uploaded files as still being processed.
+    processing_stalled: bool,
+    /// Whether `/api/listFiles` leaves out `hasMore`.
+    has_more_hidden: bool,
+}
+
+/// A multipart upload that has not been completed or aborted yet.
+struct MultipartUpload {
+    upload_id: String,
+    chunk_size: u64,
+    chunk_count: u64,
+    /// The parts received so far, by part number.
+    parts: BTreeMap<u64, Bytes>,
+}
+
+impl State {
+    /// Finds a file by its key or custom ID, depending on `field`.
+    fn position(&self, field: &str, id: &str) -> Option<usize> {
+        self.files.iter().position(|file| match field {
+            "customId" | "customIds" => file.custom_id.as_deref() == Some(id),
+            _ => file.key == id,
+        })
+    }
+}
+
+/// The state shared by the server task and the `FakeUploadThing` handle.
+struct Shared {
+    /// The base URL of the server, e.g. `http://127.0.0.1:40123`.
+    url: String,
+    state: Mutex<State>,
+}
+
+impl Shared {
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// An in-process fake of the UploadThing API for integration tests.
+///
+/// The server listens on a random local port and keeps its files in memory. It serves
+/// `/api/uploadFiles` and the presigned POST targets it hands out, `/api/pollUpload/:key`,
+/// `/api/listFiles`, `/api/deleteFile`, `/api/getFileUrl`, `/api/renameFiles`,
+/// `/api/getUsageInfo` and `/api/requestFileAccess`, and checks the API key of every API
+/// request against `FAKE_API_KEY`. Uploads are answered with a single presigned POST
+/// target unless `FakeUploadThing::enable_multipart` is called, in which case large files
+/// are uploaded in parts through `/api/completeMultipart` and `/api/failureCallback`.
+///
+/// It also acts as the ingest server of `UploadProtocol::Ingest`: it accepts `PUT`s to
+/// signed ingest URLs, checking their signature, and resumes interrupted uploads from the
+/// offset it reports to `HEAD` requests. Use `FakeUploadThing::interrupt_next_ingest` to
+/// cut an upload off part way.
+///
+/// Point a client at it with `FakeUploadThing::config`, or by setting
+/// `UploadthingConfig::host` to `FakeUploadThing::url`. The server stops when the handle
+/// is dropped. This module is only available with the `testing` feature.
+///
+/// # Examples
+///
+/// ```
+/// use reqwest::StatusCode;
+/// use utapi_rs::models::{FileObj, KeyType};
+/// use utapi_rs::testing::{Endpoint, FakeUploadThing};
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let fake = FakeUploadThing::start()?;
+/// let api = fake.client();
+///
+/// let file = FileObj::from_bytes("hello.txt", "hello world").with_custom_id("greeting");
+/// let report = api.upload_files(vec![file], None, true).await?;
+/// let key = &report.results[0].result.as_ref().unwrap().key;
+/// assert_eq!(fake.file(key).unwrap().content, "hello world");
+///
+/// // The next request to list files fails.
+/// fake.fail_next(Endpoint::ListFiles, StatusCode::BAD_REQUEST, 1);
+/// assert!(api.list_files(None).await.is_err());
+/// assert_eq!(api.list_files(None).await?.files.len(), 1);
+///
+/// api.delete_files(vec!["greeting".to_string()], KeyType::CustomId).await?;
+/// assert!(fake.files().is_empty());
+/// # Ok(())
+/// # }
+/// ```
+pub struct FakeUploadThing {
+    shared: Arc<Shared>,
+    addr: SocketAddr,
+    server: JoinHandle<()>,
+}
+
+impl FakeUploadThing {
+    /// Starts a fake server on a random port of `127.0.0.1`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `std::io::Error` if no local port can be bound.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of a Tokio runtime, which the server runs on.
+    pub fn start() -> std::io::Result<FakeUploadThing> {
+        let listener = TcpListener::bind(("127.0.0.1", 0))?;
+        listener.set_nonblocking(true)?;
+        let addr = listener.local_addr()?;
+        let shared = Arc::new(Shared {
+            url: format!("http://{}", addr),
+            state: Mutex::default(),
+        });
+
+        let service_state = shared.clone();
+        let make_service = make_service_fn(move |_conn| {
+            let shared = service_state.clone();
+            async move { Ok::<_, Infallible>(service_fn(move |req| handle(shared.clone(), req))) }
+        });
+        let server = Server::from_tcp(listener)
+            .map_err(std::io::Error::other)?
+            .serve(make_service);
+        let server = tokio::spawn(async move {
+            if let Err(_e) = server.await {
+                trace_warn!(error = %_e, "fake UploadThing server stopped");
+            }
+        });
+
+        Ok(FakeUploadThing {
+            shared,
+            addr,
+            server,
+        })
+    }
+
+    /// Returns the address the server listens on.
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Returns the base URL of the server, to be used as `UploadthingConfig::host`.
+    pub fn url(&self) -> String {
+        self.shared.url.clone()
+    }
+
+    /// Returns a configuration pointed at this server, with `FAKE_API_KEY` as the API key
+    /// and `FAKE_APP_ID` as the app ID. Ingest uploads are sent to this server too, so
+    /// setting `upload_protocol` to `UploadProtocol::Ingest` is enough to use it.
+    pub fn config(&self) -> UploadthingConfig {
+        let mut config = UploadthingConfig::builder()
+            .api_key(FAKE_API_KEY)
+            .host(&self.shared.url)
+            .build()
+            .expect("the fake server configuration is valid");
+        config.app_id = Some(FAKE_APP_ID.to_string());
+        config.ingest_url = Some(format!("{}/ingest", self.shared.url));
+        config
+    }
+
+    /// Returns a client pointed at this server. See `FakeUploadThing::config`.
+    pub fn client(&self) -> UtApi {
+        UtApi::from_config(self.config())
+    }
+
+    /// Stores a file as if it had been uploaded.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the file.
+    /// * `content` - The contents of the file.
+    /// * `custom_id` - An optional custom ID for the file.
+    ///
+    /// # Returns
+    ///
+    /// The stored file, with a newly generated key.
+    pub fn add_file(
+        &self,
+        name: impl Into<String>,
+        content: impl Into<Bytes>,
+        custom_id: Option<&str>,
+    ) -> FakeFile {
+        let name = name.into();
+        let content = content.into();
+        let file = FakeFile {
+            key: new_id(),
+            id: new_id(),
+            custom_id: custom_id.map(str::to_string),
+            content_type: mime_guess::from_path(&name)
+                .first_or_octet_stream()
+                .to_string(),
+            name,
+            size: content.len() as u64,
+            uploaded: true,
+            failed: false,
+            content,
+            uploaded_at: SystemTime::now(),
+        };
+        self.shared.state().files.push(file.clone());
+        file
+    }
+
+    /// Returns the stored file with the given key.
+    pub fn file(&self, key: &str) -> Option<FakeFile> {
+        let state = self.shared.state();
+        state.files.iter().find(|file| file.key == key).cloned()
+    }
+
+    /// Returns every stored file, in the order they were registered.
+    pub fn files(&self) -> Vec<FakeFile> {
+        self.shared.state().files.clone()
+    }
+
+    /// Makes the next `times` requests to `endpoint` fail with `status` and an UploadThing
+    /// error body, before they are authenticated or processed.
+    ///
+    /// A `429 Too Many Requests` failure also carries `Retry-After: 1` and
+    /// `x-ratelimit-limit`/`x-ratelimit-remaining` headers reporting an exhausted limit.
+    ///
+    /// Failures add up: calling this again queues more failures after the pending ones.
+    pub fn fail_next(&self, endpoint: Endpoint, status: StatusCode, times: usize) {
+        let mut state = self.shared.state();
+        let failures = state.failures.entry(endpoint).or_default();
+        failures.extend(std::iter::repeat_n(status, times));
+    }
+
+    /// Delays every request to `endpoint` by `delay`, including requests that fail.
+    /// A zero `delay` removes the latency.
+    pub fn set_latency(&self, endpoint: Endpoint, delay: Duration) {
+        self.shared.state().latency.insert(endpoint, delay);
+    }
+
+    /// Makes the next ingest upload stop after the server has received `bytes` bytes of the
+    /// file, as if the connection dropped, and fail with `503 Service Unavailable`. The
+    /// bytes are kept, so a retry can resume from them.
+    ///
+    /// Interruptions add up: calling this again interrupts the upload after that one too.
+    pub fn interrupt_next_ingest(&self, bytes: u64) {
+        self.shared.state().interruptions.push_back(bytes);
+    }
+
+    /// Makes `/api/pollUpload` report every file as still being processed, even once it is
+    /// uploaded, until this is called again with `false`.
+    pub fn stall_processing(&self, stalled: bool) {
+        self.shared.state().processing_stalled = stalled;
+    }
+
+    /// Makes `/api/listFiles` leave out `hasMore`, so that clients have to tell the last
+    /// page by its size, until this is called again with `false`.
+    pub fn hide_has_more(&self, hidden: bool) {
+        self.shared.state().has_more_hidden = hidden;
+    }
+
+    /// Makes `/api/uploadFiles` answer files of at least `threshold` bytes with a multipart
+    /// upload of `chunk_size` byte parts, as UploadThing does for large files.
+    ///
+    /// Completing the upload fails if a part is missing, empty, or smaller than
+    /// `chunk_size` without being the last one, or if an ETag does not match.
+    pub fn enable_multipart(&self, threshold: u64, chunk_size: u64) {
+        self.shared.state().multipart = Some((threshold, chunk_size.max(1)));
+    }
+
+    /// Returns how many requests `endpoint` has received, including failed ones.
+    pub fn request_count(&self, endpoint: Endpoint) -> usize {
+        let state = self.shared.state();
+        state.requests.get(&endpoint).copied().unwrap_or(0)
+    }
+
+    /// Returns the most requests to `endpoint` that were being answered at once, counting
+    /// a request from when it arrives until its response is sent. Together with
+    /// `set_latency`, this shows how many uploads a client runs concurrently.
+    pub fn max_in_flight(&self, endpoint: Endpoint) -> usize {
+        let state = self.shared.state();
+        state.max_in_flight.get(&endpoint).copied().unwrap_or(0)
+    }
+}
+
+/// Counts a request as in flight until it is dropped, including when the client hangs up.
+struct InFlight {
+    shared: Arc<Shared>,
+    endpoint: Endpoint,
+}
+
+impl InFlight {
+    fn start(shared: &Arc<Shared>, endpoint: Endpoint) -> InFlight {
+        let mut state = shared.state();
+        let in_flight = state.in_flight.entry(endpoint).or_default();
+        *in_flight += 1;
+        let in_flight = *in_flight;
+        let max = state.max_in_flight.entry(endpoint).or_default();
+        *max = (*max).max(in_flight);
+        InFlight {
+            shared: shared.clone(),
+            endpoint,
+        }
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        if let Some(in_flight) = self.shared.state().in_flight.get_mut(&self.endpoint) {
+            *in_flight -= 1;
+        }
+    }
+}
+
+impl std::fmt::Debug for FakeUploadThing {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FakeUploadThing")
+            .field("url", &self.shared.url)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Drop for FakeUploadThing {
+    fn drop(&mut self) {
+        self.server.abort();
+    }
+}
+
+/// Answers a request to the fake server.
+async fn handle(shared: Arc<Shared>, req: Request<Body>) -> Result<Response<Body>, Infallible> {
+    let path = req.uri().path().to_string();
+    let Some((endpoint, key)) = Endpoint::route(req.method(), &path) else {
+        return Ok(error(StatusCode::NOT_FOUND, "Not found"));
+    };
+    let _in_flight = InFlight::start(&shared, endpoint);
+
+    let (delay, failure) = {
+        let mut state = shared.state();
+        *state.requests.entry(endpoint).or_default() += 1;
+        let failure = state
+            .failures
+            .get_mut(&endpoint)
+            .and_then(VecDeque::pop_front);
+        (state.latency.get(&endpoint).copied(), failure)
+    };
+    if let Some(delay) = delay {
+        tokio::time::sleep(delay).await;
+    }
+    if let Some(status) = failure {
+        let mut response = error(status, "Injected failure");
+        if status == StatusCode::TOO_MANY_REQUESTS {
+            let headers = response.headers_mut();
+            headers.insert(header::RETRY_AFTER, header::HeaderValue::from(1));
+            headers.insert(
+                "x-ratelimit-limit",
+                header::HeaderValue::from(FAKE_RATE_LIMIT),
+            );
+            headers.insert("x-ratelimit-remaining", header::HeaderValue::from(0));
+        }
+        return Ok(response);
+    }
+
+    if path.starts_with("/api/") {
+        let api_key = req
+            .headers()
+            .get("x-uploadthing-api-key")
+            .and_then(|value| value.to_str().ok());
+        if api_key != Some(FAKE_API_KEY) {
+            return Ok(error(StatusCode::UNAUTHORIZED, "Invalid API key"));
+        }
+    }
+
+    let key = key.map(decode_component);
+
+    let response = match endpoint {
+        Endpoint::PresignedPost => {
+            let content_type = req
+                .headers()
+                .get(header::CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok())
+                .unwrap_or_default()
+                .to_string();
+            match hyper::body::to_bytes(req.into_body()).await {
+                Ok(body) => presigned_post(&shared, &key.unwrap_or_default(), &content_type, &body),
+                Err(_) => error(StatusCode::BAD_REQUEST, "Failed to read the body"),
+            }
+        }
+        Endpoint::PollUpload => poll_upload(&shared, &key.unwrap_or_default()),
+        Endpoint::File => {
+            let url = format!("{}{}", shared.url, req.uri());
+            serve_file(&shared, &key.unwrap_or_default(), &url)
+        }
+        Endpoint::IngestRangeStart => ingest_range_start(&shared, &key.unwrap_or_default()),
+        Endpoint::UploadPart => match hyper::body::to_bytes(req.into_body()).await {
+            Ok(body) => upload_part(&shared, &key.unwrap_or_default(), body),
+            Err(_) => error(StatusCode::BAD_REQUEST, "Failed to read the body"),
+        },
+        Endpoint::IngestUpload => {
+            let url = format!("{}{}", shared.url, req.uri());
+            let header = |name| {
+                req.headers()
+                    .get(name)
+                    .and_then(|value| value.to_str().ok())
+                    .unwrap_or_default()
+                    .to_string()
+            };
+            let (content_type, range) = (header(header::CONTENT_TYPE), header(header::RANGE));
+            match hyper::body::to_bytes(req.into_body()).await {
+                Ok(body) => ingest_upload(
+                    &shared,
+                    &key.unwrap_or_default(),
+                    &url,
+                    &range,
+                    &content_type,
+                    &body,
+                ),
+                Err(_) => error(StatusCode::BAD_REQUEST, "Failed to read the body"),
+            }
+        }
+        _ => {
+            let body = match hyper::body::to_bytes(req.into_body()).await {
+                Ok(body) if body.is_empty() => Value::Null,
+                Ok(body) => match serde_json::from_slice(&body) {
+                    Ok(body) => body,
+                    Err(_) => return Ok(error(StatusCode::BAD_REQUEST, "Invalid JSON body")),
+                },
+                Err(_) => return Ok(error(StatusCode::BAD_REQUEST, "Failed to read the body")),
+            };
+            api(&shared, endpoint, &body)
+        }
+    };
+    Ok(response)
+}
+
+/// Answers a JSON request to one of the `/api/*` endpoints.
+fn api(shared: &Shared, endpoint: Endpoint, body: &Value) -> Response<Body> {
+    let mut state = shared.state();
+    match endpoint {
+        Endpoint::UploadFiles => {
+            let Some(files) = body["files"].as_array() else {
+                return error(StatusCode::BAD_REQUEST, "Missing files");
+            };
+            let mut registered = Vec::with_capacity(files.len());
+            for file in files {
+                let (Some(name), Some(size)) = (file["name"].as_str(), file["size"].as_u64())
+                else {
+                    return error(
+                        StatusCode::BAD_REQUEST,
+                        "Every file needs a name and a size",
+                    );
+                };
+                let custom_id = file["customId"].as_str();
+                if let Some(custom_id) = custom_id {
+                    let taken = state.position("customId", custom_id).is_some()
+                        || registered
+                            .iter()
+                            .any(|f: &FakeFile| f.custom_id.as_deref() == Some(custom_id));
+                    if taken {
+                        return error(StatusCode::BAD_REQUEST, "Custom ID is already in use");
+                    }
+                }
+                registered.push(FakeFile {
+                    key: new_id(),
+                    id: new_id(),
+                    custom_id: custom_id.map(str::to_string),
+                    name: name.to_string(),
+                    content_type: file["type"]
+                        .as_str()
+                        .unwrap_or("application/octet-stream")
+                        .to_string(),
+                    size,
+                    uploaded: false,
+                    failed: false,
+                    content: Bytes::new(),
+                    uploaded_at: SystemTime::now(),
+                });
+            }
+
+            let mut data = Vec::with_capacity(registered.len());
+            for file in &registered {
+                let mut target = json!({
+                    "key": file.key,
+                    "fileName": file.name,
+                    "fileType": file.content_type,
+                    "customId": file.custom_id,
+                    "fileUrl": file_url(shared, &file.key),
+                });
+                match state.multipart {
+                    Some((threshold, chunk_size)) if file.size >= threshold => {
+                        let upload = MultipartUpload {
+                            upload_id: new_id(),
+                            chunk_size,
+                            chunk_count: file.size.div_ceil(chunk_size).max(1),
+                            parts: BTreeMap::new(),
+                        };
+                        let key = encode_uri_component(&file.key);
+                        let urls: Vec<String> = (1..=upload.chunk_count)
+                            .map(|part| format!("{}/multipart/{}/{}", shared.url, key, part))
+                            .collect();
+                        target["urls"] = json!(urls);
+                        target["uploadId"] = json!(upload.upload_id);
+                        target["chunkSize"] = json!(upload.chunk_size);
+                        target["chunkCount"] = json!(upload.chunk_count);
+                        state.multipart_uploads.insert(file.key.clone(), upload);
+                    }
+                    _ => {
+                        target["presignedUrl"] = json!(format!(
+                            "{}/upload/{}",
+                            shared.url,
+                            encode_uri_component(&file.key)
+                        ));
+                        target["fields"] = json!({
+                            "key": file.key,
+                            "Content-Type": file.content_type,
+                        });
+                    }
+                }
+                data.push(target);
+            }
+            state.files.extend(registered);
+            ok(json!({ "data": data }))
+        }
+        Endpoint::ListFiles => {
+            let offset = body["offset"].as_u64().unwrap_or(0) as usize;
+            let limit = body["limit"]
+                .as_u64()
+                .map_or(usize::MAX, |limit| limit as usize);
+            let files: Vec<Value> = state
+                .files
+                .iter()
+                .skip(offset)
+                .take(limit)
+                .map(FakeFile::to_json)
+                .collect();
+            if state.has_more_hidden {
+                return ok(json!({ "files": files }));
+            }
+            let has_more = offset.saturating_add(files.len()) < state.files.len();
+            ok(json!({ "files": files, "hasMore": has_more }))
+        }
+        Endpoint::DeleteFile => {
+            let Some((field, ids)) = key_list(body) else {
+                return error(StatusCode::BAD_REQUEST, "Missing fileKeys or customIds");
+            };
+            let before = state.files.len();
+            state.files.retain(|file| {
+                let id = match field {
+                    "customIds" => file.custom_id.as_deref(),
+                    _ => Some(file.key.as_str()),
+                };
+                !id.is_some_and(|id| ids.contains(&id))
+            });
+            let deleted = before - state.files.len();
+            ok(json!({ "success": true, "deletedCount": deleted }))
+        }
+        Endpoint::GetFileUrl => {
+            let Some((field, ids)) = key_list(body) else {
+                return error(StatusCode::BAD_REQUEST, "Missing fileKeys or customIds");
+            };
+            let data: Vec<Value> = ids
+                .iter()
+                .filter_map(|id| state.position(field, id))
+                .map(|i| {
+                    let key = &state.files[i].key;
+                    json!({ "key": key, "url": file_url(shared, key) })
+                })
+                .collect();
+            ok(json!({ "data": data }))
+        }
+        Endpoint::RenameFiles => {
+            let Some(updates) = body["updates"].as_array() else {
+                return error(StatusCode::BAD_REQUEST, "Missing updates");
+            };
+            for update in updates {
+                let Some(new_name) = update["newName"].as_str() else {
+                    return error(StatusCode::BAD_REQUEST, "Every update needs a newName");
+                };
+                let found = ["fileKey", "customId"].iter().find_map(|field| {
+                    update[*field]
+                        .as_str()
+                        .and_then(|id| state.position(field, id))
+                });
+                if let Some(i) = found {
+                    state.files[i].name = new_name.to_string();
+                }
+            }
+            ok(json!({ "success": true }))
+        }
+        Endpoint::GetUsageInfo => {
+            let total: u64 = state.files.iter().map(|file| file.size).sum();
+            ok(json!({
+                "totalBytes": total,
+                "totalReadable": format!("{} B", total),
+                "appTotalBytes": total,
+                "appTotalReadable": format!("{} B", total),
+                "filesUploaded": state.files.iter().filter(|file| file.uploaded).count(),
+                "limitBytes": FAKE_LIMIT_BYTES,
+                "limitReadable": "2 GB",
+            }))
+        }
+        Endpoint::RequestFileAccess => {
+            let found = ["fileKey", "customId"].iter().find_map(|field| {
+                body[*field]
+                    .as_str()
+                    .and_then(|id| state.position(field, id))
+            });
+            let Some(i) = found else {
+                return error(StatusCode::NOT_FOUND, "File not found");
+            };
+            let ttl = body["expiresIn"]
+                .as_u64()
+                .map_or(DEFAULT_FILE_ACCESS_TTL, Duration::from_secs);
+            let url = file_url(shared, &state.files[i].key);
+            match signing::generate_signed_url(&url, FAKE_API_KEY, SystemTime::now() + ttl, &[]) {
+                Ok(url) => ok(json!({ "url": url })),
+                Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()),
+            }
+        }
+        Endpoint::CompleteMultipart => complete_multipart(&mut state, body),
+        Endpoint::FailureCallback => {
+            let Some(key) = body["fileKey"].as_str() else {
+                return error(StatusCode::BAD_REQUEST, "Missing fileKey");
+            };
+            let known = state
+                .multipart_uploads
+                .get(key)
+                .map(|upload| &upload.upload_id);
+            if known.is_none_or(|upload_id| body["uploadId"].as_str() != Some(upload_id)) {
+                return error(StatusCode::NOT_FOUND, "No multipart upload with this ID");
+            }
+            state.multipart_uploads.remove(key);
+            if let Some(i) = state.position("fileKey", key) {
+                state.files[i].failed = true;
+            }
+            ok(json!({ "success": true }))
+        }
+        Endpoint::PresignedPost
+        | Endpoint::PollUpload
+        | Endpoint::File
+        | Endpoint::IngestUpload
+        | Endpoint::IngestRangeStart
+        | Endpoint::UploadPart => error(StatusCode::NOT_FOUND, "Not found"),
+    }
+}
+
+/// Stores the contents of a registered file sent to its presigned POST target.
+fn presigned_post(shared: &Shared, key: &str, content_type: &str, body: &[u8]) -> Response<Body> {
+    let Some(content) = multipart_file(content_type, body) else {
+        return error(StatusCode::BAD_REQUEST, "Missing file field");
+    };
+    let mut state = shared.state();
+    let Some(file) = state.files.iter_mut().find(|file| file.key == key) else {
+        return error(
+            StatusCode::NOT_FOUND,
+            "No upload is registered for this key",
+        );
+    };
+    file.size = content.len() as u64;
+    file.content = content;
+    file.uploaded = true;
+    file.uploaded_at = SystemTime::now();
+    Response::builder()
+        .status(StatusCode::NO_CONTENT)
+        .body(Body::empty())
+        .expect("a valid response")
+}
+
+/// Stores a part of a multipart upload sent to `/multipart/:key/:part` and answers with
+/// its ETag.
+fn upload_part(shared: &Shared, path: &str, body: Bytes) -> Response<Body> {
+    let Some((key, part)) = path
+        .rsplit_once('/')
+        .and_then(|(key, part)| Some((decode_component(key), part.parse::<u64>().ok()?)))
+    else {
+        return error(StatusCode::BAD_REQUEST, "Invalid part URL");
+    };
+    let mut state = shared.state();
+    let Some(upload) = state.multipart_uploads.get_mut(&key) else {
+        return error(StatusCode::NOT_FOUND, "No multipart upload for this key");
+    };
+    if part == 0 || part > upload.chunk_count {
+        return error(StatusCode::BAD_REQUEST, "Invalid part number");
+    }
+    let etag = part_etag(&body);
+    upload.parts.insert(part, body);
+    Response::builder()
+        .header(header::ETAG, format!("\"{}\"", etag))
+        .body(Body::empty())
+        .expect("a valid response")
+}
+
+/// Assembles the parts of a multipart upload once the client lists their ETags.
+fn complete_multipart(state: &mut State, body: &Value) -> Response<Body> {
+    let (Some(key), Some(etags)) = (body["fileKey"].as_str(), body["etags"].as_array()) else {
+        return error(StatusCode::BAD_REQUEST, "Missing fileKey or etags");
+    };
+    let Some(upload) = state.multipart_uploads.get(key) else {
+        return error(StatusCode::NOT_FOUND, "No multipart upload for this key");
+    };
+    if body["uploadId"].as_str() != Some(upload.upload_id.as_str()) {
+        return error(StatusCode::NOT_FOUND, "No multipart upload with this ID");
+    }
+
+    let mut etags: Vec<(u64, &str)> = etags
+        .iter()
+        .filter_map(|etag| Some((etag["partNumber"].as_u64()?, etag["tag"].as_str()?)))
+        .collect();
+    etags.sort_unstable();
+    let last = etags.len() as u64;
+    let mut content = Vec::new();
+    for (i, (part_number, tag)) in etags.into_iter().enumerate() {
+        let Some(part) = upload.parts.get(&part_number) else {
+            return error(StatusCode::BAD_REQUEST, "A listed part was not uploaded");
+        };
+        if part_number != i as u64 + 1 || tag != part_etag(part) {
+            return error(
+                StatusCode::BAD_REQUEST,
+                "The parts do not match their ETags",
+            );
+        }
+        // Like S3, every part but the last must be full, and no part may be empty.
+        if part.is_empty() || (part_number < last && (part.len() as u64) < upload.chunk_size) {
+            return error(StatusCode::BAD_REQUEST, "EntityTooSmall");
+        }
+        content.extend_from_slice(part);
+    }
+    if last == 0 {
+        return error(StatusCode::BAD_REQUEST, "No parts were listed");
+    }
+
+    state.multipart_uploads.remove(key);
+    if let Some(i) = state.position("fileKey", key) {
+        let file = &mut state.files[i];
+        file.size = content.len() as u64;
+        file.content = Bytes::from(content);
+        file.uploaded = true;
+        file.uploaded_at = SystemTime::now();
+    }
+    ok(json!({ "success": true }))
+}
+
+/// Returns the ETag the fake server assigns to an uploaded part.
+fn part_etag(part: &[u8]) -> String {
+    hex::encode(Sha256::digest(part))
+}
+
+/// Stores the contents of a file sent to its signed ingest URL, appending them to the
+/// bytes of an interrupted upload when the request resumes it with a `Range` header.
+fn ingest_upload(
+    shared: &Shared,
+    key: &str,
+    url: &str,
+    range: &str,
+    content_type: &str,
+    body: &[u8],
+) -> Response<Body> {
+    if let Err(reason) = signing::verify_signed_url(url, FAKE_API_KEY, SystemTime::now()) {
+        return error(StatusCode::FORBIDDEN, &reason);
+    }
+    let Some(content) = multipart_file(content_type, body) else {
+        return error(StatusCode::BAD_REQUEST, "Missing file field");
+    };
+    let params: HashMap<String, String> = Url::parse(url)
+        .map(|url| {
+            url.query_pairs()
+                .map(|(name, value)| (name.into_owned(), decode_component(&value)))
+                .collect()
+        })
+        .unwrap_or_default();
+    let Some(size) = params
+        .get("x-ut-file-size")
+        .and_then(|size| size.parse::<u64>().ok())
+    else {
+        return error(StatusCode::BAD_REQUEST, "Missing x-ut-file-size");
+    };
+    let start = range
+        .strip_prefix("bytes=")
+        .and_then(|range| range.strip_suffix('-'))
+        .and_then(|start| start.parse::<u64>().ok())
+        .unwrap_or(0);
+
+    let mut state = shared.state();
+    let position = match state.files.iter().position(|file| file.key == key) {
+        Some(i) => i,
+        None => {
+            let name = params.get("x-ut-file-name").cloned().unwrap_or_default();
+            state.files.push(FakeFile {
+                key: key.to_string(),
+                id: new_id(),
+                custom_id: params.get("x-ut-custom-id").cloned(),
+                content_type: params
+                    .get("x-ut-file-type")
+                    .cloned()
+                    .unwrap_or_else(|| "application/octet-stream".to_string()),
+                name,
+                size,
+                uploaded: false,
+                failed: false,
+                content: Bytes::new(),
+                uploaded_at: SystemTime::now(),
+            });
+            state.files.len() - 1
+        }
+    };
+    if state.files[position].uploaded {
+        return error(StatusCode::CONFLICT, "The file has already been uploaded");
+    }
+    if start != state.files[position].content.len() as u64 {
+        return error(
+            StatusCode::RANGE_NOT_SATISFIABLE,
+            "The range does not start where the upload left off",
+        );
+    }
+
+    let interruption = state.interruptions.pop_front();
+    let file = &mut state.files[position];
+    let mut received = file.content.to_vec();
+    match interruption {
+        Some(bytes) => {
+            let kept = (bytes as usize).min(content.len());
+            received.extend_from_slice(&content[..kept]);
+            file.content = Bytes::from(received);
+            return error(StatusCode::SERVICE_UNAVAILABLE, "Injected interruption");
+        }
+        None => received.extend_from_slice(&content),
+    }
+    if received.len() as u64 != size {
+        return error(
+            StatusCode::BAD_REQUEST,
+            "The contents do not match x-ut-file-size",
+        );
+    }
+    file.content = Bytes::from(received);
+    file.uploaded = true;
+    file.uploaded_at = SystemTime::now();
+
+    let file_url = file_url(shared, key);
+    ok(json!({
+        "url": file_url,
+        "appUrl": file_url,
+        "ufsUrl": file_url,
+        "fileHash": hex::encode(Sha256::digest(&file.content)),
+    }))
+}
+
+/// Reports how many bytes of an ingest upload the server has received.
+fn ingest_range_start(shared: &Shared, key: &str) -> Response<Body> {
+    let state = shared.state();
+    let received = state
+        .files
+        .iter()
+        .find(|file| file.key == key)
+        .map_or(0, |file| file.content.len());
+    Response::builder()
+        .header("x-ut-range-start", received)
+        .body(Body::empty())
+        .expect("a valid response")
+}
+
+/// Reports whether the contents of a registered file have been uploaded.
+fn poll_upload(shared: &Shared, key: &str) -> Response<Body> {
+    let state = shared.state();
+    match state.files.iter().find(|file| file.key == key) {
+        Some(file) if file.uploaded && !state.processing_stalled => {
+            ok(json!({ "status": "done", "file": file.to_json() }))
+        }
+        Some(_) => ok(json!({ "status": "still working" })),
+        None => error(StatusCode::NOT_FOUND, "File not found"),
+    }
+}
+
+/// Serves the contents of an uploaded file, checking the signature of signed URLs.
+fn serve_file(shared: &Shared, key: &str, url: &str) -> Response<Body> {
+    if url.contains("signature=") {
+        if let Err(reason) = signing::verify_signed_url(url, FAKE_API_KEY, SystemTime::now()) {
+            return error(StatusCode::FORBIDDEN, &reason);
+        }
+    }
+    let state = shared.state();
+    match state.files.iter().find(|file| file.key == key) {
+        Some(file) if file.uploaded => Response::builder()
+            .header(header::CONTENT_TYPE, &file.content_type)
+            .body(Body::from(file.content.clone()))
+            .expect("a valid response"),
+        _ => error(StatusCode::NOT_FOUND, "File not found"),
+    }
+}
+
+/// Reads the `fileKeys` or `customIds` list of a request body.
+fn key_list(body: &Value) -> Option<(&'static str, Vec<&str>)> {
+    ["fileKeys", "customIds"].into_iter().find_map(|field| {
+        let ids = body[field].as_array()?;
+        Some((field, ids.iter().filter_map(Value::as_str).collect()))
+    })
+}
+
+/// Extracts the `file` part of a `multipart/form-data` body.
+fn multipart_file(content_type: &str, body: &[u8]) -> Option<Bytes> {
+    let boundary = content_type
+        .split(';')
+        .find_map(|param| param.trim().strip_prefix("boundary="))?
+        .trim_matches('"');
+    let delimiter = format!("--{}", boundary);
+    let delimiter = delimiter.as_bytes();
+
+    let mut rest = body;
+    while let Some(start) = find(rest, delimiter) {
+        rest = &rest[start + delimiter.len()..];
+        let end = find(rest, delimiter).unwrap_or(rest.len());
+        let part = &rest[..end];
+        let Some(headers_end) = find(part, b"\r\n\r\n") else {
+            continue;
+        };
+        let headers = String::from_utf8_lossy(&part[..headers_end]);
+        if headers.contains("name=\"file\"") {
+            let content = &part[headers_end + 4..];
+            let content = content.strip_suffix(b"\r\n").unwrap_or(content);
+            return Some(Bytes::copy_from_slice(content));
+        }
+    }
+    None
+}
+
+/// Decodes a percent-encoded path segment or `encodeURIComponent` value.
+fn decode_component(value: &str) -> String {
+    url::form_urlencoded::parse(format!("k={}", value).as_bytes())
+        .map(|(_, value)| value.into_owned())
+        .next()
+        .unwrap_or_default()
+}
+
+/// Returns the position of the first occurrence of `needle` in `haystack`.
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+/// Returns the URL the fake server serves a file at.
+fn file_url(shared: &Shared, key: &str) -> String {
+    format!("{}/f/{}", shared.url, encode_uri_component(key))
+}
+
+/// Generates a random key or ID.
+fn new_id() -> String {
+    uuid::Uuid::new_v4().simple().to_string()
+}
+
+/// Builds a `200 OK` response with a JSON body.
+fn ok(body: Value) -> Response<Body> {
+    json_response(StatusCode::OK, &body)
+}
+
+/// Builds an error response with a body in the format of UploadThing's API errors.
+fn error(status: StatusCode, message: &str) -> Response<Body> {
+    let code = status
+        .canonical_reason()
+        .unwrap_or("Unknown error")
+        .to_uppercase()
+        .replace(' ', "_");
+    json_response(status, &json!({ "error": message, "code": code }))
+}
+
+fn json_response(status: StatusCode, body: &Value) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))
+        .expect("a valid response")
+}
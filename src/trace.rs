@@ -0,0 +1,83 @@
+//! Internal logging shims.
+//!
+//! With the `tracing` feature enabled the macros below forward to the `tracing` crate.
+//! Without it they expand to nothing and spans are zero-sized placeholders, so the
+//! library never writes to stdout or stderr on its own.
+
+#[cfg(feature = "tracing")]
+pub(crate) use tracing::{Instrument, Span};
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) use noop::{Instrument, Span};
+
+#[cfg(not(feature = "tracing"))]
+mod noop {
+    /// Stand-in for `tracing::Span` when the `tracing` feature is disabled.
+    #[derive(Debug, Clone)]
+    pub(crate) struct Span;
+
+    impl Span {
+        pub(crate) fn current() -> Span {
+            Span
+        }
+
+        pub(crate) fn record<V>(&self, _field: &str, _value: V) -> &Span {
+            self
+        }
+    }
+
+    /// Stand-in for `tracing::Instrument` when the `tracing` feature is disabled.
+    pub(crate) trait Instrument: Sized {
+        fn instrument(self, _span: Span) -> Self {
+            self
+        }
+    }
+
+    impl<T: std::future::Future> Instrument for T {}
+}
+
+/// Creates an `INFO` span, or a placeholder when the `tracing` feature is disabled.
+#[cfg(feature = "tracing")]
+macro_rules! trace_span {
+    ($($arg:tt)+) => { ::tracing::info_span!($($arg)+) };
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! trace_span {
+    ($($arg:tt)+) => {
+        $crate::trace::Span
+    };
+}
+
+/// Emits a `DEBUG` event when the `tracing` feature is enabled.
+#[cfg(feature = "tracing")]
+macro_rules! trace_debug {
+    ($($arg:tt)+) => { ::tracing::debug!($($arg)+) };
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! trace_debug {
+    ($($arg:tt)+) => {};
+}
+
+/// Emits a `WARN` event when the `tracing` feature is enabled.
+#[cfg(feature = "tracing")]
+macro_rules! trace_warn {
+    ($($arg:tt)+) => { ::tracing::warn!($($arg)+) };
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! trace_warn {
+    ($($arg:tt)+) => {};
+}
+
+/// Emits an `ERROR` event when the `tracing` feature is enabled.
+#[cfg(feature = "tracing")]
+macro_rules! trace_error {
+    ($($arg:tt)+) => { ::tracing::error!($($arg)+) };
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! trace_error {
+    ($($arg:tt)+) => {};
+}
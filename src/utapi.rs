@@ -1,26 +1,265 @@
-use crate::config::{ApiKey, UploadthingConfig};
+use crate::config::{UploadProtocol, UploadthingConfig, DEFAULT_UFS_HOST};
+use crate::error::{ConfigError, UtApiError};
 use crate::models::{
-    Acl, ContentDisposition, DeleteFileResponse, FileKeysPayload, FileObj, FileUpload,
-    ListFilesOpts, PresignedUrlOpts, PresignedUrlResponse, RenameFilesOpts, UploadFileOpts,
-    UploadFileResponse, UploadFileResponseData, UploadthingFileResponse, UploadthingUrlsResponse,
-    UploadthingUsageInfo,
+    Acl, ContentDisposition, DeleteFileResponse, Expiry, FileKeysPayload, FileObj, FileSource,
+    FileUpload, IngestUploadResponse, KeyType, ListAllFilesOpts, ListFilesOpts, PresignedUrl,
+    PresignedUrlOpts, PresignedUrlResponse, RenameFilesOpts, UploadEvent, UploadEventKind,
+    UploadFileOpts, UploadFileResponse, UploadFileResponseData, UploadReport, UploadResult,
+    UploadthingFile, UploadthingFileResponse, UploadthingUrlsResponse, UploadthingUsageInfo,
+    UrlUpload,
 };
-use anyhow::anyhow;
-use filesize::PathExt;
+use crate::rate_limit::RateLimit;
+use crate::retry::RetryPolicy;
+use crate::signing;
+use crate::trace::{Instrument, Span};
+use bytes::Bytes;
+use futures::{Stream, StreamExt, TryStreamExt};
 use rand::{thread_rng, Rng};
-use reqwest::{header, multipart, Client, Response};
+use reqwest::{header, multipart, Body, Client, RequestBuilder, Response};
+use serde::de::DeserializeOwned;
 use serde::Serialize;
 use serde_json::json;
 use std::collections::HashMap;
-use std::error::Error;
-use std::io::Read;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-use std::time::Duration;
-use tokio::macros::support::Future;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::sync::Semaphore;
 use tokio::task::JoinHandle;
+use tokio_util::io::{ReaderStream, StreamReader};
+use tokio_util::sync::CancellationToken;
 
-const MAX_RETRIES: u32 = 20;
-const MAXIMUM_BACKOFF_MS: u64 = 64 * 1000;
+/// How long a locally signed ingest URL stays valid.
+const INGEST_URL_TTL: Duration = Duration::from_secs(60 * 60);
+/// How long a locally signed file URL stays valid unless told otherwise.
+const DEFAULT_SIGNED_URL_TTL: Duration = Duration::from_secs(60 * 60);
+const DEFAULT_PART_CONCURRENCY: usize = 4;
+/// API endpoints that register or complete uploads. Repeating a request that reached
+/// UploadThing would register or complete a file twice, so they are retried only when
+/// the request was not processed.
+const NON_IDEMPOTENT_ENDPOINTS: [&str; 2] = ["api/uploadFiles", "api/completeMultipart"];
+const STREAM_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Upload options resolved from `UploadFileOpts`, shared by every file of an `upload_files` call.
+#[derive(Clone)]
+struct UploadSettings {
+    metadata: HashMap<String, String>,
+    content_disposition: &'static str,
+    acl: &'static str,
+    wait_until_done: bool,
+    part_concurrency: usize,
+    concurrency: usize,
+    progress: Option<UnboundedSender<UploadEvent>>,
+    cancel: CancellationToken,
+}
+
+impl UploadSettings {
+    /// Resolves the settings for an upload, applying the defaults for missing options.
+    fn from_opts(
+        opts: Option<UploadFileOpts>,
+        wait_until_done: bool,
+        config: &UploadthingConfig,
+    ) -> UploadSettings {
+        let mut metadata = HashMap::new();
+        let mut content_disposition = "inline";
+        let mut acl = "public-read";
+        let mut part_concurrency = DEFAULT_PART_CONCURRENCY;
+        let mut concurrency = config.max_concurrency;
+        let mut progress = None;
+        let mut cancel = None;
+
+        match opts {
+            None => {}
+            Some(o) => {
+                metadata = o.metadata.unwrap_or(HashMap::new());
+                content_disposition = match o.content_disposition {
+                    None => "inline",
+                    Some(cd) => match cd {
+                        ContentDisposition::Attachment => "attachment",
+                        ContentDisposition::Inline => "inline",
+                    },
+                };
+                acl = match o.acl {
+                    None => "public-read",
+                    Some(acl) => match acl {
+                        Acl::Private => "private",
+                        Acl::PublicRead => "public-read",
+                    },
+                };
+                part_concurrency = o
+                    .part_concurrency
+                    .unwrap_or(DEFAULT_PART_CONCURRENCY)
+                    .max(1);
+                if let Some(requested) = o.concurrency {
+                    if requested > config.max_concurrency {
+                        trace_warn!(
+                            concurrency = requested,
+                            max_concurrency = config.max_concurrency,
+                            "concurrency is above the max_concurrency of the client, which limits it"
+                        );
+                    }
+                    concurrency = requested.min(config.max_concurrency);
+                }
+                progress = o.progress;
+                cancel = o.cancellation_token;
+            }
+        }
+
+        UploadSettings {
+            metadata,
+            content_disposition,
+            acl,
+            wait_until_done,
+            part_concurrency,
+            concurrency: concurrency.max(1),
+            progress,
+            // A token that is never cancelled keeps the upload code free of `Option`s.
+            cancel: cancel.unwrap_or_default(),
+        }
+    }
+}
+
+/// Where a single file of a batch is uploaded to.
+enum UploadTarget {
+    /// A presigned POST or multipart upload registered with `/api/uploadFiles`.
+    Presigned(Box<UploadFileResponseData>),
+    /// A locally signed URL on an ingest server.
+    Ingest { key: String, url: String },
+}
+
+impl UploadTarget {
+    /// The key of the file on UploadThing.
+    fn key(&self) -> &str {
+        match self {
+            UploadTarget::Presigned(presigned) => &presigned.key,
+            UploadTarget::Ingest { key, .. } => key,
+        }
+    }
+}
+
+/// Sends `UploadEvent`s for one file of an upload, if the caller asked for them.
+#[derive(Clone)]
+struct ProgressReporter {
+    sender: Option<UnboundedSender<UploadEvent>>,
+    index: usize,
+    name: String,
+    total: u64,
+}
+
+impl ProgressReporter {
+    fn new(
+        sender: Option<UnboundedSender<UploadEvent>>,
+        index: usize,
+        name: &str,
+        total: u64,
+    ) -> Self {
+        ProgressReporter {
+            sender,
+            index,
+            name: name.to_string(),
+            total,
+        }
+    }
+
+    /// Sends an event; a receiver that has been dropped is ignored.
+    fn emit(&self, kind: UploadEventKind) {
+        if let Some(sender) = &self.sender {
+            let _ = sender.send(UploadEvent {
+                index: self.index,
+                name: self.name.clone(),
+                kind,
+            });
+        }
+    }
+
+    /// Wraps a body stream so that every chunk handed to the HTTP client is reported
+    /// as `UploadEventKind::BytesSent`, counting from the `start` offset of the stream.
+    fn count_bytes<S>(&self, stream: S, start: u64) -> impl Stream<Item = S::Item>
+    where
+        S: Stream<Item = std::io::Result<Bytes>>,
+    {
+        let progress = self.clone();
+        let mut sent = start;
+        stream.inspect_ok(move |chunk| {
+            sent += chunk.len() as u64;
+            progress.bytes_sent(sent);
+        })
+    }
+
+    /// Reports that `sent` bytes of the file have been uploaded so far.
+    fn bytes_sent(&self, sent: u64) {
+        self.emit(UploadEventKind::BytesSent {
+            sent,
+            total: self.total,
+        });
+    }
+}
+
+/// The position of `UtApi::list_all_files` in the list of files.
+struct PageCursor {
+    api: UtApi,
+    page_size: i32,
+    prefetch: bool,
+    // The offset of the next page to request.
+    offset: i32,
+    // The next page, if it is already being requested in the background.
+    next: Option<PrefetchedPage>,
+    done: bool,
+}
+
+impl PageCursor {
+    /// Returns the files of the next page, or `None` once all pages have been returned.
+    async fn next_page(&mut self) -> Result<Option<Vec<UploadthingFile>>, UtApiError> {
+        if self.done {
+            return Ok(None);
+        }
+
+        let page = match self.next.take() {
+            Some(mut prefetched) => (&mut prefetched.0).await??,
+            None => self.api.list_files(Some(self.page_opts())).await?,
+        };
+
+        let count = page.files.len();
+        // Without a `hasMore` flag, a full page suggests that more files follow.
+        let has_more = page.has_more.unwrap_or(count >= self.page_size as usize);
+        if count == 0 || !has_more {
+            self.done = true;
+        } else {
+            self.offset = self.offset.saturating_add(count as i32);
+            if self.prefetch {
+                let api = self.api.clone();
+                let opts = self.page_opts();
+                self.next = Some(PrefetchedPage(tokio::spawn(async move {
+                    api.list_files(Some(opts)).await
+                })));
+            }
+        }
+
+        if count == 0 {
+            return Ok(None);
+        }
+        Ok(Some(page.files))
+    }
+
+    fn page_opts(&self) -> ListFilesOpts {
+        ListFilesOpts {
+            limit: Some(self.page_size),
+            offset: Some(self.offset),
+        }
+    }
+}
+
+/// A page requested in the background, cancelled if the stream is dropped before it is used.
+struct PrefetchedPage(JoinHandle<Result<UploadthingFileResponse, UtApiError>>);
+
+impl Drop for PrefetchedPage {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
 
 /// The `UtApi` struct represents the client for interacting with the Uploadthing API.
 ///
@@ -32,6 +271,13 @@ pub struct UtApi {
 
     /// The HTTP client for making requests to the Uploadthing service.
     client: Client,
+
+    /// The rate-limit state reported on the last API response, shared between clones.
+    rate_limit: Arc<Mutex<Option<RateLimit>>>,
+
+    /// Limits the files uploaded at once to `config.max_concurrency` across all calls
+    /// and clones.
+    upload_slots: Arc<Semaphore>,
 }
 
 impl UtApi {
@@ -40,15 +286,18 @@ impl UtApi {
     /// This constructor initializes the `UtApi` struct with the provided API key
     /// or, if none is provided, attempts to retrieve the API key from the environment.
     /// It sets up the `UploadthingConfig` and the internal `Client` for HTTP requests.
+    /// Use `UtApi::try_new` to handle a missing or invalid key without panicking.
     ///
     /// # Arguments
     ///
     /// * `api_key` - An `Option<String>` that holds the API key for authentication.
-    ///               If `None`, the API key is retrieved from the environment.
+    ///   If `None`, the API key is retrieved from the environment.
     ///
     /// # Examples
     ///
-    /// ```
+    /// ```no_run
+    /// use utapi_rs::UtApi;
+    ///
     /// // Create a new API client with a provided API key.
     /// let api_with_key = UtApi::new(Some("your_api_key".to_string()));
     ///
@@ -63,28 +312,51 @@ impl UtApi {
     ///
     /// # Panics
     ///
-    /// Panics if the API key is not provided and is also not set in the environment.
+    /// Panics if the API key is not provided and is also not set in the environment,
+    /// or if the client cannot be created. See `UtApi::try_new`.
     pub fn new(api_key: Option<String>) -> UtApi {
-        // Initialize the configuration for the Uploadthing service using the provided API key.
-        // If no API key is provided, attempt to retrieve the key from the environment variable.
-        let api_key = api_key.unwrap_or_else(|| {
-            ApiKey::from_env()
-                .expect("API key not provided and not found in environment")
-                .to_string()
-        });
-
-        // Build the configuration with the retrieved or provided API key.
-        let config = UploadthingConfig::builder().api_key(&api_key).build();
+        UtApi::try_new(api_key).unwrap_or_else(|e| panic!("failed to create UtApi: {}", e))
+    }
 
-        // Create a new HTTP client for making requests.
-        let client = Client::new();
+    /// Creates a new instance of `UtApi`, returning an error instead of panicking
+    /// if it cannot be configured.
+    ///
+    /// # Arguments
+    ///
+    /// * `api_key` - An `Option<String>` that holds the API key for authentication.
+    ///   If `None`, the credentials are read from the `UPLOADTHING_TOKEN` environment variable,
+    ///   falling back to `UPLOADTHING_SECRET`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use utapi_rs::UtApi;
+    ///
+    /// let api = UtApi::try_new(Some("your_api_key".to_string()))?;
+    /// # Ok::<(), utapi_rs::ConfigError>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConfigError::MissingApiKey` if no key is provided and neither environment
+    /// variable is set, `ConfigError::InvalidToken` if `UPLOADTHING_TOKEN` cannot be decoded,
+    /// any other `ConfigError` if the key is invalid, or
+    /// `ConfigError::Client` if the HTTP client cannot be initialized.
+    pub fn try_new(api_key: Option<String>) -> Result<UtApi, ConfigError> {
+        // Without an explicit key, the default configuration reads `UPLOADTHING_TOKEN`
+        // or `UPLOADTHING_SECRET`.
+        let mut builder = UploadthingConfig::builder();
+        if let Some(api_key) = api_key {
+            builder = builder.api_key(&api_key);
+        }
 
-        // Return a new instance of `UtApi` with the configured settings.
-        UtApi { config, client }
+        UtApi::try_from_config(builder.build()?)
     }
 
     /// Creates a new instance of `UtApi` from a given `UploadthingConfig`.
     ///
+    /// The configuration is used as is; use `UtApi::try_from_config` to validate it first.
+    ///
     /// # Arguments
     ///
     /// * `config` - An `UploadthingConfig` instance containing the configuration for the service.
@@ -92,16 +364,158 @@ impl UtApi {
     /// # Examples
     ///
     /// ```
-    /// let config = UploadthingConfig::builder().api_key("your_api_key").build();
+    /// use utapi_rs::UtApi;
+    /// use utapi_rs::config::UploadthingConfig;
+    ///
+    /// let config = UploadthingConfig::builder().api_key("your_api_key").build().unwrap();
     /// let api = UtApi::from_config(config);
     /// ```
     ///
     /// # Returns
     ///
-    /// Returns a new `UtApi` struct initialized with the provided configuration and a new `Client`.
+    /// Returns a new `UtApi` struct initialized with the provided configuration and a new `Client`
+    /// built from its timeout, proxy and connection pool settings.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the proxy URL is invalid or the client cannot be created.
+    /// Use `UtApi::try_from_config` to handle these errors.
     pub fn from_config(config: UploadthingConfig) -> UtApi {
-        let client = Client::new();
-        UtApi { config, client }
+        let client = config
+            .client_builder()
+            .and_then(|builder| Ok(builder.build()?))
+            .unwrap_or_else(|e| panic!("failed to create HTTP client: {}", e));
+        UtApi::with_client(config, client)
+    }
+
+    /// Creates a new instance of `UtApi` that sends its requests through the given `Client`.
+    ///
+    /// Use this to share a client with the rest of an application, or to configure it
+    /// beyond what `UploadthingConfig` offers, such as extra root certificates. The
+    /// timeout, proxy and connection pool settings of `config` are not applied to
+    /// `client`; `UploadthingConfig::client_builder` returns a builder with them applied.
+    /// The configuration is used as is; call `UploadthingConfig::validate` to check it first.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - An `UploadthingConfig` instance containing the configuration for the service.
+    /// * `client` - The `reqwest::Client` used for all requests.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use utapi_rs::UtApi;
+    /// use utapi_rs::config::UploadthingConfig;
+    ///
+    /// let client = reqwest::Client::builder()
+    ///     .timeout(Duration::from_secs(60))
+    ///     .build()
+    ///     .unwrap();
+    /// let config = UploadthingConfig::builder().api_key("your_api_key").build().unwrap();
+    /// let api = UtApi::with_client(config, client);
+    /// ```
+    ///
+    /// # Returns
+    ///
+    /// Returns a new `UtApi` struct initialized with the provided configuration and client.
+    pub fn with_client(config: UploadthingConfig, client: Client) -> UtApi {
+        UtApi {
+            upload_slots: Arc::new(Semaphore::new(config.max_concurrency.max(1))),
+            config,
+            client,
+            rate_limit: Arc::default(),
+        }
+    }
+
+    /// Creates a new instance of `UtApi` from a given `UploadthingConfig` after
+    /// checking it with `UploadthingConfig::validate`.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - An `UploadthingConfig` instance containing the configuration for the service.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use utapi_rs::UtApi;
+    /// use utapi_rs::config::UploadthingConfig;
+    ///
+    /// let mut config = UploadthingConfig::new();
+    /// config.api_key = None;
+    /// assert!(UtApi::try_from_config(config).is_err());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns a `ConfigError` if the configuration is invalid or the HTTP client
+    /// cannot be initialized.
+    pub fn try_from_config(config: UploadthingConfig) -> Result<UtApi, ConfigError> {
+        config.validate()?;
+        let client = config.client_builder()?.build()?;
+        Ok(UtApi::with_client(config, client))
+    }
+
+    /// Returns the rate-limit state reported by the UploadThing API on its most recent
+    /// response that carried rate-limit headers.
+    ///
+    /// Batch jobs can use this to slow down before the API starts rejecting requests.
+    /// The state is shared by all clones of this client.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use utapi_rs::UtApi;
+    /// use utapi_rs::config::UploadthingConfig;
+    ///
+    /// let config = UploadthingConfig::builder().api_key("your_api_key").build().unwrap();
+    /// let api = UtApi::from_config(config);
+    /// // Nothing is known before the first request.
+    /// assert!(api.rate_limit().is_none());
+    /// ```
+    ///
+    /// # Returns
+    ///
+    /// Returns `None` if no response with rate-limit headers has been received yet.
+    pub fn rate_limit(&self) -> Option<RateLimit> {
+        self.rate_limit
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+
+    /// Joins `pathname` to the configured host with exactly one slash between them.
+    fn endpoint(&self, pathname: &str) -> String {
+        format!(
+            "{}/{}",
+            self.config.host.trim_end_matches('/'),
+            pathname.trim_start_matches('/')
+        )
+    }
+
+    /// Adds the user agent, API key and version headers to a request, skipping
+    /// any that are not configured.
+    fn with_client_headers(&self, mut request: RequestBuilder) -> RequestBuilder {
+        if let Some(user_agent) = &self.config.user_agent {
+            request = request.header(header::USER_AGENT, user_agent);
+        }
+        if let Some(api_key) = &self.config.api_key {
+            request = request.header("x-uploadthing-api-key", api_key.to_string());
+        }
+        if let Some(version) = &self.config.version {
+            request = request.header("x-uploadthing-version", version);
+        }
+        request
+    }
+
+    /// Remembers the rate-limit headers of an API response, if it carries any.
+    fn record_rate_limit(&self, response: &Response) {
+        if let Some(rate_limit) = RateLimit::from_headers(response.headers()) {
+            *self
+                .rate_limit
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(rate_limit);
+        }
     }
 
     /// Sends a `POST` request to the `Uploadthing` service.
@@ -123,75 +537,91 @@ impl UtApi {
     /// # Returns
     ///
     /// A `Result` with the HTTP `Response` if the request was successful,
-    /// or an `Error` boxed in a `Box<dyn Error>` if the request failed.
+    /// or an `UtApiError` if the request failed.
     ///
     /// # Errors
     ///
-    /// If the response status is not a success, this function will return an `Error`
-    /// containing the HTTP error returned by the server.
+    /// Returns `UtApiError::Transport` if the request could not be sent, or
+    /// `UtApiError::Api` containing the status code and the UploadThing error body
+    /// if the response status is not a success, once the configured `RetryPolicy`
+    /// has given up. Requests to `/api/uploadFiles` and `/api/completeMultipart` are only
+    /// retried when they were not processed; see `RetryPolicy::is_retryable_non_idempotent`.
     pub async fn request_uploadthing<T: Serialize>(
         &self,
         pathname: &str,
         payload: &T,
-    ) -> Result<Response, anyhow::Error> {
+    ) -> Result<Response, UtApiError> {
         // Construct the full URL by appending the pathname to the host from the config.
-        let url = format!("{}/{}", self.config.host, pathname);
+        let url = self.endpoint(pathname);
 
-        // Perform a POST request with the serialized payload.
-        let response = self
-            .client
-            .post(&url)
-            .json(payload) // Serialize the payload as JSON and set it as the request body.
-            .header(header::CONTENT_TYPE, "application/json")
-            .header(header::CACHE_CONTROL, "no-store") // Ensure the response is not cached.
-            .header(header::USER_AGENT, self.config.user_agent.as_ref().unwrap()) // Set the User-Agent header.
-            .header(
-                "x-uploadthing-api-key",
-                self.config.api_key.as_ref().unwrap().to_string(), // Set the custom API key header.
-            )
-            .header(
-                "x-uploadthing-version",
-                self.config.version.as_ref().unwrap(), // Set the custom version header.
-            )
-            .send() // Send the request.
-            .await?; // Await the async operation, returning an error if one occurs.
+        let span = trace_span!(
+            "uploadthing.request",
+            endpoint = pathname,
+            attempt = ::tracing::field::Empty,
+        );
 
-        // Check the HTTP response status code to determine success.
-        if response.status().is_success() {
-            Ok(response) // If successful, return the response.
+        // Failed attempts are retried according to the configured `RetryPolicy`.
+        let attempt = |attempt| {
+            Span::current().record("attempt", attempt);
+            async {
+                // Perform a POST request with the serialized payload.
+                let request = self
+                    .client
+                    .post(&url)
+                    .json(payload) // Serialize the payload as JSON and set it as the request body.
+                    .header(header::CONTENT_TYPE, "application/json")
+                    .header(header::CACHE_CONTROL, "no-store"); // Ensure the response is not cached.
+                let response = self
+                    .with_client_headers(request) // Set the user agent, API key and version headers.
+                    .send() // Send the request.
+                    .await?; // Await the async operation, returning an error if one occurs.
+                self.record_rate_limit(&response);
+                trace_debug!(status = response.status().as_u16(), "received response");
+
+                // Check the HTTP response status code to determine success.
+                if response.status().is_success() {
+                    Ok(response) // If successful, return the response.
+                } else {
+                    // If the response indicates failure, extract and return the error.
+                    Err(UtApiError::from_response(response).await)
+                }
+            }
+        };
+        let policy = &self.config.retry_policy;
+        if NON_IDEMPOTENT_ENDPOINTS.contains(&pathname.trim_start_matches('/')) {
+            policy.run_non_idempotent(attempt).instrument(span).await
         } else {
-            // If the response indicates failure, extract and return the error.
-            let response_data =
-                serde_json::to_string_pretty(&response.json::<serde_json::Value>().await?)?.clone();
-            Err(anyhow!(response_data))
+            policy.run(attempt).instrument(span).await
         }
     }
 
     /// Sends a `DELETE` request to the `Uploadthing` service to delete a list of files.
     ///
-    /// This method accepts a list of file keys and constructs a payload to send to the
-    /// `/api/deleteFile` endpoint. It then calls the `request_uploadthing` method to
-    /// perform the actual request.
+    /// This method accepts a list of file keys or custom IDs and constructs a payload to
+    /// send to the `/api/deleteFile` endpoint. It then calls the `request_uploadthing`
+    /// method to perform the actual request.
     ///
     /// # Parameters
     ///
-    /// * `file_keys`: A `Vec<String>` containing the keys of the files to be deleted.
+    /// * `keys`: A `Vec<String>` containing the keys of the files to be deleted.
+    /// * `key_type`: Whether `keys` are file keys or custom IDs.
     ///
     /// # Returns
     ///
     /// A `Result` with a `DeleteFileResponse` if the deletion was successful,
-    /// or an `Error` boxed in a `Box<dyn Error>` if the request failed.
+    /// or an `UtApiError` if the request failed.
     ///
     /// # Errors
     ///
-    /// If the response status is not a success, or if the response cannot be deserialized
-    /// into a `DeleteFileResponse`, this function will return an `Error`.
+    /// Returns `UtApiError::Api` if the response status is not a success, or
+    /// `UtApiError::Deserialize` if the response cannot be deserialized into a `DeleteFileResponse`.
     pub async fn delete_files(
         &self,
-        file_keys: Vec<String>,
-    ) -> Result<DeleteFileResponse, Box<dyn Error>> {
-        // Construct the payload with the file keys to be deleted.
-        let payload = FileKeysPayload { file_keys };
+        keys: Vec<String>,
+        key_type: KeyType,
+    ) -> Result<DeleteFileResponse, UtApiError> {
+        // Construct the payload with the keys of the files to be deleted.
+        let payload = FileKeysPayload { keys, key_type };
 
         // Make a `DELETE` request to the Uploadthing service using the constructed payload.
         let response = self
@@ -200,33 +630,35 @@ impl UtApi {
 
         // Deserialize the JSON response into the `DeleteFileResponse` struct.
         // This holds the result of the delete operation.
-        let delete_response: DeleteFileResponse = response.json().await?;
+        let delete_response: DeleteFileResponse = parse_json(response).await?;
 
         // Return the deserialized delete response.
         Ok(delete_response)
     }
 
-    /// Retrieves the URLs for a list of file keys from the `Uploadthing` service.
+    /// Retrieves the URLs for a list of file keys or custom IDs from the `Uploadthing` service.
     ///
     /// # Parameters
     ///
-    /// * `file_keys`: A `Vec<String>` containing the keys of the files whose URLs are to be retrieved.
+    /// * `keys`: A `Vec<String>` containing the keys of the files whose URLs are to be retrieved.
+    /// * `key_type`: Whether `keys` are file keys or custom IDs.
     ///
     /// # Returns
     ///
     /// A `Result` with a `UploadthingUrlsResponse` if the retrieval was successful,
-    /// or an `Error` boxed in a `Box<dyn Error>` if the request failed.
+    /// or an `UtApiError` if the request failed.
     ///
     /// # Errors
     ///
-    /// If the response status is not a success, or if the response cannot be deserialized
-    /// into a `UploadthingUrlsResponse`, this function will return an `Error`.
+    /// Returns `UtApiError::Api` if the response status is not a success, or
+    /// `UtApiError::Deserialize` if the response cannot be deserialized into a `UploadthingUrlsResponse`.
     pub async fn get_file_urls(
         &self,
-        file_keys: Vec<String>,
-    ) -> Result<UploadthingUrlsResponse, Box<dyn Error>> {
-        // Construct the payload with the file keys for which URLs are to be retrieved.
-        let payload = FileKeysPayload { file_keys };
+        keys: Vec<String>,
+        key_type: KeyType,
+    ) -> Result<UploadthingUrlsResponse, UtApiError> {
+        // Construct the payload with the keys of the files for which URLs are to be retrieved.
+        let payload = FileKeysPayload { keys, key_type };
 
         // Make a `POST` request to the Uploadthing service using the constructed payload.
         // Note: Assuming that the `getFileUrl` API uses a POST method as it was unspecified;
@@ -237,7 +669,7 @@ impl UtApi {
 
         // Deserialize the JSON response into the `UploadthingUrlsResponse` struct.
         // This holds the URLs for the requested file keys.
-        let urls_response: UploadthingUrlsResponse = response.json().await?;
+        let urls_response: UploadthingUrlsResponse = parse_json(response).await?;
 
         // Return the deserialized URLs response.
         Ok(urls_response)
@@ -252,16 +684,16 @@ impl UtApi {
     /// # Returns
     ///
     /// A `Result` with a `UploadthingFileResponse` if the retrieval was successful,
-    /// or an `Error` boxed in a `Box<dyn Error>` if the request failed.
+    /// or an `UtApiError` if the request failed.
     ///
     /// # Errors
     ///
-    /// If the response status is not a success, or if the response cannot be deserialized
-    /// into a `UploadthingFileResponse`, this function will return an `Error`.
+    /// Returns `UtApiError::Api` if the response status is not a success, or
+    /// `UtApiError::Deserialize` if the response cannot be deserialized into a `UploadthingFileResponse`.
     pub async fn list_files(
         &self,
         opts: Option<ListFilesOpts>,
-    ) -> Result<UploadthingFileResponse, Box<dyn Error>> {
+    ) -> Result<UploadthingFileResponse, UtApiError> {
         // You might serialize `None` to send no specific parameters,
         // or provide a default instance of ListFilesOpts with desired default values.
         let payload = opts.unwrap_or_default();
@@ -270,12 +702,74 @@ impl UtApi {
         let response = self.request_uploadthing("/api/listFiles", &payload).await?;
 
         // Deserialize the JSON response into the `UploadthingFileResponse` struct.
-        let file_response: UploadthingFileResponse = response.json().await?;
+        let file_response: UploadthingFileResponse = parse_json(response).await?;
 
         // Return the deserialized file response.
         Ok(file_response)
     }
 
+    /// Lists every file stored in the `Uploadthing` service, one page at a time.
+    ///
+    /// Pages are requested lazily as the stream is consumed, until the API reports that no
+    /// more files follow. With `ListAllFilesOpts::prefetch`, the next page is requested in
+    /// the background as soon as the current one arrives. The stream owns a clone of this
+    /// client, so it can outlive the borrow of `self`.
+    ///
+    /// # Parameters
+    ///
+    /// * `opts`: An optional `ListAllFilesOpts` struct with the page size and prefetching.
+    ///
+    /// # Returns
+    ///
+    /// A `Stream` of the files. It ends after the first error.
+    ///
+    /// # Errors
+    ///
+    /// Yields `UtApiError::Api` if a page cannot be retrieved, or
+    /// `UtApiError::Deserialize` if a page cannot be deserialized.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use futures::TryStreamExt;
+    /// use utapi_rs::models::ListAllFilesOpts;
+    /// use utapi_rs::UtApi;
+    ///
+    /// # async fn run(api: &UtApi) -> Result<(), utapi_rs::UtApiError> {
+    /// let opts = ListAllFilesOpts {
+    ///     prefetch: true,
+    ///     ..ListAllFilesOpts::default()
+    /// };
+    /// let mut files = api.list_all_files(Some(opts));
+    /// while let Some(file) = files.try_next().await? {
+    ///     println!("{} {}", file.key, file.name);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn list_all_files(
+        &self,
+        opts: Option<ListAllFilesOpts>,
+    ) -> impl Stream<Item = Result<UploadthingFile, UtApiError>> + Send + Unpin + 'static {
+        let opts = opts.unwrap_or_default();
+        let pages = PageCursor {
+            api: self.clone(),
+            page_size: opts.page_size.max(1),
+            prefetch: opts.prefetch,
+            offset: 0,
+            next: None,
+            done: false,
+        };
+
+        futures::stream::try_unfold(pages, |mut pages| async move {
+            let files = pages.next_page().await;
+            files.map(|files| files.map(|files| (files, pages)))
+        })
+        .map_ok(|files| futures::stream::iter(files.into_iter().map(Ok)))
+        .try_flatten()
+        .boxed()
+    }
+
     /// Renames files in the `Uploadthing` service according to the given options.
     ///
     /// # Parameters
@@ -285,12 +779,12 @@ impl UtApi {
     /// # Returns
     ///
     /// An `Ok` result if the renaming operation was successful,
-    /// or an `Error` boxed in a `Box<dyn Error>` if the request failed.
+    /// or an `UtApiError` if the request failed.
     ///
     /// # Errors
     ///
-    /// If the response status is not a success, this function will return an `Error`.
-    pub async fn rename_files(&self, files: RenameFilesOpts) -> Result<(), Box<dyn Error>> {
+    /// Returns `UtApiError::Api` if the response status is not a success.
+    pub async fn rename_files(&self, files: RenameFilesOpts) -> Result<(), UtApiError> {
         // Make a `POST` request to the Uploadthing service using the constructed payload.
         // No response content is expected based on the comment in the Go code.
         let _response = self.request_uploadthing("/api/renameFiles", &files).await?;
@@ -304,19 +798,19 @@ impl UtApi {
     /// # Returns
     ///
     /// A `Result` with a `UploadthingUsageInfo` if the retrieval was successful,
-    /// or an `Error` boxed in a `Box<dyn Error>` if the request failed.
+    /// or an `UtApiError` if the request failed.
     ///
     /// # Errors
     ///
-    /// If the response status is not a success, or if the response cannot be deserialized
-    /// into an `UploadthingUsageInfo`, this function will return an `Error`.
-    pub async fn get_usage_info(&self) -> Result<UploadthingUsageInfo, Box<dyn Error>> {
+    /// Returns `UtApiError::Api` if the response status is not a success, or
+    /// `UtApiError::Deserialize` if the response cannot be deserialized into an `UploadthingUsageInfo`.
+    pub async fn get_usage_info(&self) -> Result<UploadthingUsageInfo, UtApiError> {
         // Make a `GET` request to the Uploadthing service to get the usage info.
         // An empty payload is assumed because of the "bytes.NewBuffer([]byte{})" in Go code.
         let response = self.request_uploadthing("/api/getUsageInfo", &()).await?;
 
         // Deserialize the JSON response into the `UploadthingUsageInfo` struct.
-        let usage_info: UploadthingUsageInfo = response.json().await?;
+        let usage_info: UploadthingUsageInfo = parse_json(response).await?;
 
         // Return the deserialized usage information.
         Ok(usage_info)
@@ -324,36 +818,34 @@ impl UtApi {
 
     /// Generates a presigned URL for a file.
     ///
-    /// The maximum value for `expires_in` is 604800 (7 days).
+    /// The maximum value for `expires_in` is `Expiry::MAX` (7 days).
     /// This function assumes that you must accept overrides on the UploadThing dashboard
     /// for `expires_in` to be accepted.
     ///
     /// # Parameters
     ///
     /// * `opts`: A `PresignedUrlOpts` struct containing options for the presigned URL,
-    ///           including the file key and the expiration time in seconds.
+    ///   including the file key or custom ID and the expiration time.
     ///
     /// # Returns
     ///
-    /// A `Result` with a `String` presigned URL if the operation was successful,
-    /// or an `Error` boxed in a `Box<dyn Error>` if the request failed, including
+    /// A `Result` with a `PresignedUrl` if the operation was successful,
+    /// or an `UtApiError` if the request failed, including
     /// scenarios where `expires_in` is greater than the allowed maximum.
+    /// The expiry of the URL is read from the URL itself when possible.
     ///
     /// # Errors
     ///
-    /// If `expires_in` is greater than 604800 or if an error occurs during the request,
-    /// an `Error` is returned.
+    /// Returns `UtApiError::InvalidInput` if `expires_in` is shorter than a second or
+    /// longer than 7 days, or another `UtApiError` if the request fails.
     pub async fn get_presigned_url(
         &self,
         opts: PresignedUrlOpts,
-    ) -> Result<String, Box<dyn Error>> {
-        // Validate expiresIn.
-        if opts.expires_in.unwrap_or(0) > 604800 {
-            return Err(Box::new(std::io::Error::new(
-                std::io::ErrorKind::InvalidInput,
-                "expiresIn must be less than 604800",
-            )));
+    ) -> Result<PresignedUrl, UtApiError> {
+        if let Some(expires_in) = &opts.expires_in {
+            expires_in.validated_secs()?;
         }
+        let requested_at = SystemTime::now();
 
         // Make a `POST` request to the Uploadthing service using the constructed payload.
         let response = self
@@ -361,281 +853,1344 @@ impl UtApi {
             .await?;
 
         // Deserialize the JSON response into the `PresignedUrlResponse` struct.
-        let url_response: PresignedUrlResponse = response.json().await?;
+        let url_response: PresignedUrlResponse = parse_json(response).await?;
 
-        // Return the `url` from the deserialized response.
-        Ok(url_response.url)
+        let expires_at = expiry_from_url(&url_response.url).or_else(|| {
+            opts.expires_in
+                .map(|expires_in| requested_at + expires_in.as_duration())
+        });
+        Ok(PresignedUrl {
+            url: url_response.url,
+            expires_at,
+            key: opts.key,
+        })
+    }
+
+    /// Generates a signed URL to access a file without calling the UploadThing API.
+    ///
+    /// The URL has the form `https://{app_id}.ufs.sh/f/{key}` and is signed with the API
+    /// key, so it grants access to private files until it expires. This needs the app ID
+    /// of an `UPLOADTHING_TOKEN`. Use `UtApi::verify_signed_url` to check such a URL.
+    ///
+    /// # Parameters
+    ///
+    /// * `key`: The file key or custom ID of the file.
+    /// * `expires_in`: How long the URL stays valid, at most `Expiry::MAX` (7 days).
+    ///   Defaults to one hour.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use utapi_rs::config::UploadthingConfig;
+    /// use utapi_rs::UtApi;
+    ///
+    /// let mut config = UploadthingConfig::builder().api_key("sk_live_123").build()?;
+    /// config.app_id = Some("app123".to_string());
+    /// let api = UtApi::try_from_config(config)?;
+    ///
+    /// let signed = api.generate_signed_url("file_key", Some("5m".parse()?))?;
+    /// assert!(signed.url.starts_with("https://app123.ufs.sh/f/file_key?expires="));
+    /// assert!(api.verify_signed_url(&signed.url).is_ok());
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns `UtApiError::InvalidInput` if `expires_in` is shorter than a second or
+    /// longer than 7 days, or if no app ID or API key is configured.
+    pub fn generate_signed_url(
+        &self,
+        key: &str,
+        expires_in: Option<Expiry>,
+    ) -> Result<PresignedUrl, UtApiError> {
+        let expires_in = expires_in.unwrap_or(Expiry::from(DEFAULT_SIGNED_URL_TTL));
+        let ttl = Duration::from_secs(expires_in.validated_secs()?);
+        let (app_id, api_key) = self.signing_credentials()?;
+
+        let url = format!(
+            "https://{}.{}/f/{}",
+            app_id,
+            DEFAULT_UFS_HOST,
+            encode_uri_component(key)
+        );
+        let expires_at = SystemTime::now() + ttl;
+        let url = signing::generate_signed_url(&url, api_key, expires_at, &[])
+            .map_err(|e| UtApiError::InvalidInput(format!("invalid file URL: {}", e)))?;
+        Ok(PresignedUrl {
+            url,
+            expires_at: Some(expires_at),
+            key: key.to_string(),
+        })
+    }
+
+    /// Checks that a URL was signed with this client's API key and has not expired,
+    /// e.g. a URL produced by `UtApi::generate_signed_url`.
+    ///
+    /// # Parameters
+    ///
+    /// * `url`: The signed URL.
+    ///
+    /// # Returns
+    ///
+    /// When the URL expires.
+    ///
+    /// # Errors
+    ///
+    /// Returns `UtApiError::InvalidInput` with the reason if the URL is not signed, its
+    /// signature does not match, or it has expired.
+    pub fn verify_signed_url(&self, url: &str) -> Result<SystemTime, UtApiError> {
+        let api_key = self
+            .config
+            .api_key
+            .as_ref()
+            .ok_or_else(|| UtApiError::InvalidInput("no API key is configured".to_string()))?;
+        signing::verify_signed_url(url, &api_key.key, SystemTime::now())
+            .map_err(|reason| UtApiError::InvalidInput(format!("invalid signed URL: {}", reason)))
+    }
+
+    /// Returns the app ID and API key used to sign URLs locally.
+    fn signing_credentials(&self) -> Result<(&str, &str), UtApiError> {
+        match (self.config.app_id.as_deref(), self.config.api_key.as_ref()) {
+            (Some(app_id), Some(api_key)) if !app_id.is_empty() => Ok((app_id, &api_key.key)),
+            _ => Err(UtApiError::InvalidInput(
+                "signing URLs needs an app ID and an API key; use an UPLOADTHING_TOKEN".to_string(),
+            )),
+        }
     }
 
     /// Uploads files to the `Uploadthing` service.
+    ///
+    /// # Parameters
+    ///
+    /// * `files`: The files to upload.
+    /// * `opts`: Optional `UploadFileOpts` applied to every file.
+    /// * `wait_until_done`: Whether to wait until UploadThing has processed each file. A file
+    ///   that is still not processed after `RetryPolicy::poll_attempts` polls fails with
+    ///   `UtApiError::PollTimeout`.
+    ///
+    /// # Returns
+    ///
+    /// An `UploadReport` with one entry per file, in the same order as `files`, recording
+    /// which files were uploaded and why the others failed. Files are registered with
+    /// UploadThing in batches of `upload_batch_size`; when UploadThing rejects a batch, each
+    /// file of that batch is reported as `UtApiError::BatchRejected`, whichever batch it was.
+    ///
+    /// # Errors
+    ///
+    /// Returns `UtApiError::InvalidInput` if `opts` holds metadata and the client uses
+    /// `UploadProtocol::Ingest`, in which case none of the files were uploaded.
     pub async fn upload_files(
         &self,
         files: Vec<FileObj>,
         opts: Option<UploadFileOpts>,
         wait_until_done: bool,
-    ) -> Result<Vec<FileUpload>, Box<dyn Error>> {
-        let mut metadata = HashMap::new();
-        let mut content_disposition = "inline";
-        let mut acl = "public-read";
+    ) -> Result<UploadReport, UtApiError> {
+        let settings = UploadSettings::from_opts(opts, wait_until_done, &self.config);
+        self.check_upload_settings(&settings)?;
+        self.upload_files_internal(files, settings).await
+    }
 
-        match opts {
-            None => {}
-            Some(o) => {
-                metadata = o.metadata.unwrap_or(HashMap::new());
-                content_disposition = match o.content_disposition {
-                    None => "inline",
-                    Some(cd) => match cd {
-                        ContentDisposition::Attachment => "attachment",
-                        ContentDisposition::Inline => "inline",
-                    },
+    /// Downloads files from remote URLs and uploads them to the `Uploadthing` service.
+    ///
+    /// Each URL is fetched with a `GET` request and its body is streamed straight into the
+    /// upload, without being buffered in memory when the server sends a `Content-Length`.
+    /// The file name is taken from `UrlUpload::name`, the `Content-Disposition` header or the
+    /// last segment of the URL path, in that order, and the MIME type from the `Content-Type`
+    /// header, falling back to a guess based on the file name.
+    ///
+    /// The URLs are fetched in batches of `upload_batch_size`, at most `concurrency` at a
+    /// time, right before each batch is uploaded. Cancelling the upload also stops fetching;
+    /// the URLs that were not reached are reported as `UtApiError::Cancelled`.
+    ///
+    /// # Parameters
+    ///
+    /// * `urls`: The URLs to upload, each with an optional override for the file name.
+    /// * `opts`: Optional `UploadFileOpts` applied to every file.
+    /// * `wait_until_done`: Whether to wait until UploadThing has processed each file.
+    ///
+    /// # Returns
+    ///
+    /// An `UploadReport` with one entry per URL, in the same order as `urls`. Failing to
+    /// fetch a URL only fails that entry; a batch that UploadThing rejects is reported as
+    /// in `upload_files`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `UtApiError::InvalidInput` if `opts` holds metadata and the client uses
+    /// `UploadProtocol::Ingest`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use utapi_rs::models::UrlUpload;
+    /// use utapi_rs::UtApi;
+    ///
+    /// # async fn run() -> Result<(), utapi_rs::UtApiError> {
+    /// let api = UtApi::new(None);
+    /// let results = api
+    ///     .upload_files_from_url(
+    ///         vec![
+    ///             UrlUpload::from("https://example.com/logo.png"),
+    ///             UrlUpload::new("https://example.com/download?id=1").with_name("report.pdf"),
+    ///         ],
+    ///         None,
+    ///         false,
+    ///     )
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn upload_files_from_url(
+        &self,
+        urls: Vec<UrlUpload>,
+        opts: Option<UploadFileOpts>,
+        wait_until_done: bool,
+    ) -> Result<UploadReport, UtApiError> {
+        let settings = UploadSettings::from_opts(opts, wait_until_done, &self.config);
+        self.check_upload_settings(&settings)?;
+        let semaphore = Arc::new(Semaphore::new(settings.concurrency));
+        let batch_size = self.config.upload_batch_size.max(1);
+        let mut results: Vec<UploadResult> = Vec::with_capacity(urls.len());
+        let mut urls = urls.into_iter().peekable();
+
+        // URLs are fetched one batch at a time, just before the batch is uploaded, so that
+        // at most `settings.concurrency` responses are opened at once and only the responses
+        // of the current batch are held open.
+        while urls.peek().is_some() {
+            let batch: Vec<UrlUpload> = urls.by_ref().take(batch_size).collect();
+            let labels: Vec<String> = batch
+                .iter()
+                .map(|u| u.name.clone().unwrap_or_else(|| u.url.clone()))
+                .collect();
+            let offset = results.len();
+
+            let fetches = futures::stream::iter(batch)
+                .map(|u| self.fetch_url(u))
+                .buffered(settings.concurrency)
+                .collect::<Vec<_>>();
+            let fetched = tokio::select! {
+                fetched = fetches => fetched,
+                _ = settings.cancel.cancelled() => {
+                    // Report this batch and every URL that was not reached as cancelled.
+                    let pending = urls.map(|u| u.name.unwrap_or(u.url));
+                    results.extend(labels.into_iter().chain(pending).map(|name| UploadResult {
+                        name,
+                        result: Err(UtApiError::Cancelled),
+                    }));
+                    break;
+                }
+            };
+
+            // Only the URLs that could be fetched are uploaded; remember where each one goes.
+            let mut batch_results: Vec<Option<UploadResult>> = Vec::with_capacity(labels.len());
+            let mut files = Vec::new();
+            let mut indices = Vec::new();
+            for (i, (label, fetch)) in labels.into_iter().zip(fetched).enumerate() {
+                match fetch {
+                    Ok(file) => {
+                        files.push(file);
+                        indices.push(offset + i);
+                        batch_results.push(None);
+                    }
+                    Err(e) => batch_results.push(Some(UploadResult {
+                        name: label,
+                        result: Err(e),
+                    })),
+                }
+            }
+
+            if !files.is_empty() {
+                let names: Vec<String> = files.iter().map(|f| f.name.clone()).collect();
+                let uploads = match self
+                    .upload_batch(files, indices.clone(), &settings, &semaphore)
+                    .await
+                {
+                    Ok(uploads) => uploads,
+                    Err(e) => rejected_batch(names, e),
                 };
-                acl = match o.acl {
-                    None => "public-read",
-                    Some(acl) => match acl {
-                        Acl::Private => "private",
-                        Acl::PublicRead => "public-read",
-                    },
+                for (index, upload) in indices.into_iter().zip(uploads) {
+                    batch_results[index - offset] = Some(upload);
                 }
             }
+            results.extend(batch_results.into_iter().flatten());
         }
 
-        let value = self
-            .upload_files_internal(files, metadata, content_disposition, acl, wait_until_done)
-            .await?;
-        Ok(value)
+        Ok(UploadReport { results })
     }
 
-    /// Ping UploadThing to send a message saying a file is going to be uploaded, then upload it.
+    /// Fetches a remote file and turns the response into a streamed `FileObj`.
+    async fn fetch_url(&self, upload: UrlUpload) -> Result<FileObj, UtApiError> {
+        let response = self.client.get(&upload.url).send().await?;
+        if !response.status().is_success() {
+            return Err(UtApiError::Fetch {
+                url: upload.url,
+                status: response.status(),
+            });
+        }
+
+        let name = upload
+            .name
+            .or_else(|| {
+                response
+                    .headers()
+                    .get(header::CONTENT_DISPOSITION)
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(file_name_from_content_disposition)
+            })
+            .or_else(|| {
+                response
+                    .url()
+                    .path_segments()
+                    .and_then(|mut segments| segments.next_back())
+                    .filter(|segment| !segment.is_empty())
+                    .map(|segment| segment.to_string())
+            })
+            .unwrap_or_else(|| "file".to_string());
+
+        // Drop any parameters such as `charset`; UploadThing expects a bare MIME type.
+        let content_type = response
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.split(';').next())
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty());
+
+        let file = match response.content_length() {
+            Some(size) => {
+                let stream = response.bytes_stream().map_err(std::io::Error::other);
+                FileObj::from_reader(name, StreamReader::new(stream), size)
+            }
+            // Without a declared length the body has to be buffered to know its size.
+            None => FileObj::from_bytes(name, response.bytes().await?),
+        };
+
+        Ok(match content_type {
+            Some(content_type) => file.with_content_type(content_type),
+            None => file,
+        })
+    }
+
+    /// Rejects upload options that the configured `UploadProtocol` cannot honour.
+    fn check_upload_settings(&self, settings: &UploadSettings) -> Result<(), UtApiError> {
+        if self.config.upload_protocol == UploadProtocol::Ingest && !settings.metadata.is_empty() {
+            return Err(UtApiError::InvalidInput(
+                "metadata is not supported by the ingest upload protocol".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Uploads files in batches of at most `upload_batch_size`, with at most
+    /// `settings.concurrency` files being uploaded at once across all batches, and at
+    /// most `config.max_concurrency` across all calls of this client.
+    ///
+    /// Returns one result per file, in the same order as `files`. Files of a batch that
+    /// UploadThing rejected are reported as `UtApiError::BatchRejected`.
     async fn upload_files_internal(
         &self,
         files: Vec<FileObj>,
-        metadata: HashMap<String, String>,
-        content_disposition: &str,
-        acl: &str,
-        wait_until_done: bool,
-    ) -> Result<Vec<FileUpload>, anyhow::Error> {
-        let file_data = files
-            .iter()
-            .map(|f| {
-                let mime_type = mime_guess::from_path(&f.path);
-                let mime = mime_type.first_or_octet_stream().to_string();
-                serde_json::json!({
-                    "name": f.name,
-                    "type": mime,
-                    "size": f.path.size_on_disk().expect("Should be able to get file size"),
-                })
-            })
-            .collect::<Vec<_>>();
+        settings: UploadSettings,
+    ) -> Result<UploadReport, UtApiError> {
+        let semaphore = Arc::new(Semaphore::new(settings.concurrency));
+        let batch_size = self.config.upload_batch_size.max(1);
+        let mut results = Vec::with_capacity(files.len());
+        let mut files = files.into_iter().peekable();
 
-        let json_data = &json!({
-            "files": file_data,
-            "metadata": metadata,
-            "contentDisposition": content_disposition,
-            "acl": acl
-        });
-        let response = self
-            .request_uploadthing("/api/uploadFiles", json_data)
-            .await;
+        while files.peek().is_some() {
+            let batch: Vec<FileObj> = files.by_ref().take(batch_size).collect();
+            let names: Vec<String> = batch.iter().map(|f| f.name.clone()).collect();
+            let offset = results.len();
+            let indices = (offset..offset + batch.len()).collect();
 
-        let response = match response {
-            Err(e) => {
-                eprintln!("[UT] Error uploading files: {}", e);
-                eprintln!(
-                    "[UT] Data sent in request:\n{}",
-                    serde_json::to_string(&json_data).unwrap()
-                );
-                return Err(e);
+            match self
+                .upload_batch(batch, indices, &settings, &semaphore)
+                .await
+            {
+                Ok(batch_results) => results.extend(batch_results),
+                Err(e) => results.extend(rejected_batch(names, e)),
+            }
+        }
+
+        Ok(UploadReport { results })
+    }
+
+    /// Ping UploadThing to send a message saying a batch of files is going to be uploaded,
+    /// then upload them. With `UploadProtocol::Ingest`, the upload URLs are signed locally
+    /// instead.
+    ///
+    /// `indices` holds the position of each file of the batch in the whole upload, used to
+    /// index progress events.
+    async fn upload_batch(
+        &self,
+        files: Vec<FileObj>,
+        indices: Vec<usize>,
+        settings: &UploadSettings,
+        semaphore: &Arc<Semaphore>,
+    ) -> Result<Vec<UploadResult>, UtApiError> {
+        // A file whose size cannot be read, e.g. a missing path, fails on its own; only the
+        // other files are registered. `positions` maps them back to their place in the batch.
+        let mut batch_results: Vec<Option<UploadResult>> = Vec::with_capacity(files.len());
+        let mut positions = Vec::with_capacity(files.len());
+        let mut registered = Vec::with_capacity(files.len());
+        let mut file_data = Vec::with_capacity(files.len());
+        for (i, f) in files.into_iter().enumerate() {
+            match f.source.size().await {
+                Ok(size) => {
+                    file_data.push(serde_json::json!({
+                        "name": f.name,
+                        "type": f.mime_type(),
+                        "size": size,
+                        "customId": f.custom_id,
+                    }));
+                    positions.push(i);
+                    registered.push(f);
+                    batch_results.push(None);
+                }
+                Err(e) => {
+                    let e = UtApiError::Io(e);
+                    trace_warn!(file.name = %f.name, error = %e, "failed to read file size");
+                    ProgressReporter::new(settings.progress.clone(), indices[i], &f.name, 0).emit(
+                        UploadEventKind::Failed {
+                            error: e.to_string(),
+                        },
+                    );
+                    batch_results.push(Some(UploadResult {
+                        name: f.name,
+                        result: Err(e),
+                    }));
+                }
+            }
+        }
+        if registered.is_empty() {
+            return Ok(batch_results.into_iter().flatten().collect());
+        }
+        let files = registered;
+        let indices: Vec<usize> = positions.iter().map(|&i| indices[i]).collect();
+
+        let targets: Vec<UploadTarget> = match self.config.upload_protocol {
+            UploadProtocol::Presigned => {
+                let json_data = &json!({
+                    "files": file_data,
+                    "metadata": settings.metadata,
+                    "contentDisposition": settings.content_disposition,
+                    "acl": settings.acl
+                });
+                let response = tokio::select! {
+                    response = self.request_uploadthing("/api/uploadFiles", json_data) => response,
+                    _ = settings.cancel.cancelled() => return Err(UtApiError::Cancelled),
+                };
+
+                let response = match response {
+                    Err(e) => {
+                        trace_error!(error = %e, files = files.len(), "failed to register files for upload");
+                        return Err(e);
+                    }
+                    Ok(r) => r,
+                };
+
+                let uf_response: UploadFileResponse = parse_json(response).await?;
+                uf_response
+                    .data
+                    .into_iter()
+                    .map(|presigned| UploadTarget::Presigned(Box::new(presigned)))
+                    .collect()
             }
-            Ok(r) => r,
+            UploadProtocol::Ingest => file_data
+                .iter()
+                .map(|data| self.sign_ingest_upload(data, settings))
+                .collect::<Result<_, _>>()?,
         };
 
-        let uf_response: UploadFileResponse = response.json().await?;
+        if targets.len() != files.len() {
+            return Err(UtApiError::InvalidInput(format!(
+                "received {} upload destinations for {} files",
+                targets.len(),
+                files.len()
+            )));
+        }
+
+        let names: Vec<String> = files.iter().map(|f| f.name.clone()).collect();
         let mut handles = vec![];
-        for (i, file) in files.iter().enumerate() {
-            let presigned = uf_response.data[i].clone();
+        for (i, (file, target)) in files.into_iter().zip(targets).enumerate() {
             let data = file_data[i].clone();
-            let path = file.path.clone();
             let client = self.clone();
-            let task: JoinHandle<Result<FileUpload, anyhow::Error>> = tokio::task::spawn(
+            let settings = settings.clone();
+            let semaphore = semaphore.clone();
+            let size = data["size"].as_u64().unwrap();
+            let progress =
+                ProgressReporter::new(settings.progress.clone(), indices[i], &file.name, size);
+            let span = trace_span!(
+                "uploadthing.upload_file",
+                file.index = indices[i],
+                file.name = %file.name,
+                file.key = %target.key(),
+                file.bytes = size,
+            );
+            let task: JoinHandle<Result<FileUpload, UtApiError>> = tokio::task::spawn(
                 async move {
-                    // TODO: handle multi files vs. single url
-                    //
-                    // Actual JS implementation:
-                    //
-                    // if ("urls" in presigned) {
-                    // 	await uploadMultipart(file, presigned, {
-                    // 		...opts
-                    // 	});
-                    // } else {
-                    // 	await uploadPresignedPost(file, presigned, {
-                    // 		...opts
-                    // 	});
-                    // }
-                    let mut f = std::fs::File::open(&path)?;
-                    let file_name = data["name"].as_str().unwrap().to_string();
-
-                    tokio::select! {
-                        result = client.upload_presigned_post(file_name.clone(), &mut f, &presigned) => {
-                            match result {
-                                Ok(_) => {}
-                                Err(e) => {
-                                    eprintln!("[UT] Error uploading file {:?}: {}", path, e);
-                                    return Err(e);
-                                }
-                            }
+                    progress.emit(UploadEventKind::PresignReceived {
+                        key: target.key().to_string(),
+                    });
+
+                    // Wait for a free slot of this call and then of the client; the permits
+                    // are held until the file is done.
+                    let permits = tokio::select! {
+                        permits = async {
+                            let call = semaphore.acquire_owned().await.ok()?;
+                            let shared = client.upload_slots.clone().acquire_owned().await.ok()?;
+                            Some((call, shared))
+                        } => permits,
+                        _ = settings.cancel.cancelled() => None,
+                    };
+                    let result = match (permits, &target) {
+                        (None, _) => Err(UtApiError::Cancelled),
+                        (Some(_permits), UploadTarget::Presigned(presigned)) => {
+                            client
+                                .upload_file(file, &data, presigned, &settings, &progress)
+                                .await
                         }
-                        _ = tokio::signal::ctrl_c() => {
-                            eprintln!("[UT] Upload cancelled for file {:?}", path);
-                            return Err(anyhow::anyhow!("Upload cancelled"));
+                        (Some(_permits), UploadTarget::Ingest { key, url }) => {
+                            client
+                                .upload_ingest(file, &data, key, url, &settings, &progress)
+                                .await
                         }
-                    }
-                    if wait_until_done {
-                        // Poll for file data
-                        let url =
-                            format!("{}/api/pollUpload/{}", client.config.host, presigned.key);
-
-                        tokio::select! {
-                            result = retry_with_time_delays(|| client.poll_for_file_data(&url)) => {
-                                result?;
-                            }
-                            _ = tokio::signal::ctrl_c() => {
-                                eprintln!("[UT] Polling cancelled for file {:?}", path);
-                                return Err(anyhow::anyhow!("Polling cancelled"));
-                            }
+                    };
+
+                    match &result {
+                        Ok(_) => {
+                            trace_debug!("file uploaded");
+                            progress.emit(UploadEventKind::Done)
+                        }
+                        Err(UtApiError::Cancelled) => {
+                            trace_debug!("file upload cancelled");
+                            progress.emit(UploadEventKind::Cancelled)
+                        }
+                        Err(e) => {
+                            trace_warn!(error = %e, "file upload failed");
+                            progress.emit(UploadEventKind::Failed {
+                                error: e.to_string(),
+                            })
                         }
                     }
-
-                    Ok(FileUpload {
-                        key: presigned.key.clone(),
-                        url: presigned.file_url.clone(),
-                        name: file_name,
-                        size: data["size"].as_u64().unwrap(),
-                    })
-                },
+                    result
+                }
+                .instrument(span),
             );
 
             handles.push(task);
         }
 
-        let uploads = futures::future::try_join_all(handles).await?;
-        let uploads: Vec<FileUpload> = uploads.into_iter().filter_map(Result::ok).collect();
+        // A task that panicked only fails its own file.
+        let results = futures::future::join_all(handles)
+            .await
+            .into_iter()
+            .zip(names)
+            .map(|(joined, name)| UploadResult {
+                name,
+                result: joined.unwrap_or_else(|e| Err(UtApiError::Task(e))),
+            });
+        for (position, result) in positions.into_iter().zip(results) {
+            batch_results[position] = Some(result);
+        }
 
-        Ok(uploads)
+        Ok(batch_results.into_iter().flatten().collect())
+    }
+
+    /// Uploads a single file to its presigned destination and optionally waits for
+    /// UploadThing to finish processing it.
+    async fn upload_file(
+        &self,
+        file: FileObj,
+        data: &serde_json::Value,
+        presigned: &UploadFileResponseData,
+        settings: &UploadSettings,
+        progress: &ProgressReporter,
+    ) -> Result<FileUpload, UtApiError> {
+        let file_name = data["name"].as_str().unwrap().to_string();
+        let size = data["size"].as_u64().unwrap();
+
+        let mut source = Some(file.source);
+
+        // Large files get a list of part URLs instead of a single presigned POST.
+        let upload = async {
+            if presigned.urls.is_some() {
+                // Parts are retried individually.
+                let reader = source.take().unwrap().into_reader().await?;
+                self.upload_multipart(data, reader, presigned, settings, progress)
+                    .await
+            } else {
+                // A reader cannot be rewound, so only replayable sources are retried.
+                let policy = match source.as_ref().and_then(FileSource::try_clone) {
+                    Some(_) => self.config.retry_policy.clone(),
+                    None => RetryPolicy::none(),
+                };
+                policy
+                    .run_until_cancelled(&settings.cancel, |_attempt| {
+                        let attempt_source = next_attempt_source(&mut source);
+                        let file_name = file_name.clone();
+                        async move {
+                            let reader = attempt_source?.into_reader().await?;
+                            self.upload_presigned_post(
+                                file_name,
+                                reader,
+                                size,
+                                presigned,
+                                progress,
+                                &settings.cancel,
+                            )
+                            .await
+                        }
+                    })
+                    .await
+            }
+        };
+
+        // Each upload method watches the cancellation token itself so that it can
+        // clean up, e.g. abort a multipart upload on the server.
+        upload.await?;
+        progress.emit(UploadEventKind::UploadFinished);
+
+        if settings.wait_until_done {
+            progress.emit(UploadEventKind::PollingStarted);
+
+            // Poll for file data
+            let url = self.endpoint(&format!("/api/pollUpload/{}", presigned.key));
+
+            tokio::select! {
+                result = retry_with_time_delays(&self.config.retry_policy, || self.poll_for_file_data(&url)) => {
+                    if result?.is_none() {
+                        return Err(UtApiError::PollTimeout {
+                            key: presigned.key.clone(),
+                        });
+                    }
+                }
+                _ = settings.cancel.cancelled() => return Err(UtApiError::Cancelled),
+            }
+        }
+
+        Ok(FileUpload {
+            key: presigned.key.clone(),
+            url: presigned.file_url.clone(),
+            name: file_name,
+            size,
+            custom_id: data["customId"].as_str().map(str::to_string),
+        })
+    }
+
+    /// Signs an ingest upload URL for a file, generating its key locally.
+    fn sign_ingest_upload(
+        &self,
+        data: &serde_json::Value,
+        settings: &UploadSettings,
+    ) -> Result<UploadTarget, UtApiError> {
+        let (Some(app_id), Some(base_url), Some(api_key)) = (
+            self.config.app_id.as_deref(),
+            self.config.ingest_base_url(),
+            self.config.api_key.as_ref(),
+        ) else {
+            return Err(UtApiError::InvalidInput(
+                "the ingest protocol needs an app ID, a region and an API key".to_string(),
+            ));
+        };
+
+        let name = data["name"].as_str().unwrap_or_default();
+        let file_type = data["type"].as_str().unwrap_or_default();
+        let size = data["size"].as_u64().unwrap_or_default();
+        let custom_id = data["customId"].as_str();
+
+        // The seed only has to be unique; the SDKs hash the file details and the time.
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let seed = json!([
+            name,
+            size,
+            file_type,
+            thread_rng().gen::<u64>(),
+            now.to_string()
+        ]);
+        let key = signing::generate_key(app_id, &seed.to_string());
+
+        let mut params = vec![
+            ("x-ut-identifier", app_id.to_string()),
+            ("x-ut-file-name", name.to_string()),
+            ("x-ut-file-size", size.to_string()),
+            ("x-ut-file-type", file_type.to_string()),
+            (
+                "x-ut-content-disposition",
+                settings.content_disposition.to_string(),
+            ),
+            ("x-ut-acl", settings.acl.to_string()),
+        ];
+        if let Some(custom_id) = custom_id {
+            params.push(("x-ut-custom-id", custom_id.to_string()));
+        }
+
+        let url = signing::generate_signed_url(
+            &format!("{}/{}", base_url, key),
+            &api_key.key,
+            SystemTime::now() + INGEST_URL_TTL,
+            &params,
+        )
+        .map_err(|e| UtApiError::InvalidInput(format!("invalid ingest URL: {}", e)))?;
+
+        Ok(UploadTarget::Ingest { key, url })
+    }
+
+    /// Uploads a file to an ingest server with a `PUT` to its signed URL.
+    ///
+    /// When a replayable upload is retried, the server is asked how many bytes it already
+    /// has and the upload resumes from there with a `Range` header.
+    async fn upload_ingest(
+        &self,
+        file: FileObj,
+        data: &serde_json::Value,
+        key: &str,
+        url: &str,
+        settings: &UploadSettings,
+        progress: &ProgressReporter,
+    ) -> Result<FileUpload, UtApiError> {
+        let file_name = data["name"].as_str().unwrap_or_default().to_string();
+        let content_type = data["type"].as_str().unwrap_or_default().to_string();
+        let size = data["size"].as_u64().unwrap_or_default();
+
+        let mut source = Some(file.source);
+
+        // A reader cannot be rewound, so only replayable sources are retried.
+        let policy = match source.as_ref().and_then(FileSource::try_clone) {
+            Some(_) => self.config.retry_policy.clone(),
+            None => RetryPolicy::none(),
+        };
+        // The HEAD probe and the skipped bytes of a resumed attempt are cancellable too.
+        let response: IngestUploadResponse = policy
+            .run_until_cancelled(&settings.cancel, |attempt| {
+                let attempt_source = next_attempt_source(&mut source);
+                let file_name = file_name.clone();
+                let content_type = content_type.clone();
+                async move {
+                    let source = attempt_source?;
+                    let start = if attempt > 1 {
+                        self.ingest_range_start(url).await?
+                    } else {
+                        0
+                    };
+
+                    let mut reader = source.into_reader().await?;
+                    if start > 0 {
+                        // Skip the bytes the server already has.
+                        tokio::io::copy(&mut (&mut reader).take(start), &mut tokio::io::sink())
+                            .await?;
+                    }
+
+                    let body = Body::wrap_stream(
+                        progress
+                            .count_bytes(stream_exact(reader, size.saturating_sub(start)), start),
+                    );
+                    let file_part =
+                        multipart::Part::stream_with_length(body, size.saturating_sub(start))
+                            .file_name(file_name)
+                            .mime_str(&content_type)?;
+                    let form = multipart::Form::new().part("file", file_part);
+
+                    let mut request = self
+                        .client
+                        .put(url)
+                        .header(header::RANGE, format!("bytes={}-", start))
+                        .multipart(form);
+                    if let Some(version) = &self.config.version {
+                        request = request.header("x-uploadthing-version", version);
+                    }
+
+                    let res = tokio::select! {
+                        res = request.send() => res.map_err(body_error)?,
+                        _ = settings.cancel.cancelled() => return Err(UtApiError::Cancelled),
+                    };
+                    if !res.status().is_success() {
+                        return Err(UtApiError::from_upload_response(res).await);
+                    }
+                    parse_json(res).await
+                }
+            })
+            .await?;
+
+        // The ingest server answers once the file is stored, so there is nothing to poll.
+        progress.emit(UploadEventKind::UploadFinished);
+
+        Ok(FileUpload {
+            key: key.to_string(),
+            url: response.ufs_url.unwrap_or(response.url),
+            name: file_name,
+            size,
+            custom_id: data["customId"].as_str().map(str::to_string),
+        })
+    }
+
+    /// Asks an ingest server how many bytes of an interrupted upload it already has.
+    async fn ingest_range_start(&self, url: &str) -> Result<u64, UtApiError> {
+        let res = self.client.head(url).send().await?;
+        Ok(res
+            .headers()
+            .get("x-ut-range-start")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(0))
     }
 
     /// Uploads a file using a POST request to the Uploadthing service.
+    ///
+    /// The file body is streamed from `reader`, so memory use does not depend on the file size.
     async fn upload_presigned_post(
         &self,
         file_name: String,
-        file: &mut std::fs::File,
+        reader: Box<dyn AsyncRead + Send + Unpin>,
+        size: u64,
         presigned: &UploadFileResponseData,
-    ) -> Result<(), anyhow::Error> {
+        progress: &ProgressReporter,
+        cancel: &CancellationToken,
+    ) -> Result<(), UtApiError> {
         let mut form = multipart::Form::new();
 
-        for (k, v) in presigned.fields.as_object().unwrap().iter() {
-            let value = v.clone().to_owned().as_str().unwrap().to_owned();
-            form = form.text(k.clone(), value);
+        if let Some(fields) = presigned.fields.as_object() {
+            for (k, v) in fields.iter() {
+                // Fields are strings; anything else is sent in its JSON form.
+                let value = match v.as_str() {
+                    Some(value) => value.to_owned(),
+                    None => v.to_string(),
+                };
+                form = form.text(k.clone(), value);
+            }
         }
 
-        let mut file_bytes = Vec::new();
-        file.read_to_end(&mut file_bytes)?;
-        let file_part = multipart::Part::bytes(file_bytes).file_name(file_name.clone());
+        let body = Body::wrap_stream(progress.count_bytes(stream_exact(reader, size), 0));
+        let file_part =
+            multipart::Part::stream_with_length(body, size).file_name(file_name.clone());
         form = form.part("file", file_part);
 
-        let res = self
-            .client
-            .post(&presigned.presigned_url)
-            .header(
-                "x-uploadthing-api-key",
-                self.config.api_key.as_ref().unwrap().to_string(),
-            )
+        let request = self
+            .with_client_headers(self.client.post(&presigned.presigned_url))
             .multipart(form)
-            .send()
-            .await?;
+            .send();
+
+        let res = tokio::select! {
+            res = request => res.map_err(body_error)?,
+            _ = cancel.cancelled() => return Err(UtApiError::Cancelled),
+        };
 
         if !res.status().is_success() {
-            let text = res.text().await?;
-            eprintln!("Failed to upload file: {}", text);
+            return Err(UtApiError::from_upload_response(res).await);
         }
 
         Ok(())
     }
 
+    /// Uploads a large file in parts to the presigned multipart URLs returned by UploadThing.
+    ///
+    /// The contents are read sequentially in `chunk_size` parts, and at most
+    /// `settings.part_concurrency` parts are held in memory and uploaded at once.
+    /// Reading stops at the end of the source, so no empty parts are sent, and fails if
+    /// the source holds more or fewer bytes than the size the file was registered with.
+    /// Once every part has been uploaded the multipart upload is completed with the
+    /// collected ETags; if any part fails, the source does not match its size or the
+    /// upload is cancelled, the upload is aborted on the server.
+    async fn upload_multipart(
+        &self,
+        data: &serde_json::Value,
+        reader: Box<dyn AsyncRead + Send + Unpin>,
+        presigned: &UploadFileResponseData,
+        settings: &UploadSettings,
+        progress: &ProgressReporter,
+    ) -> Result<(), UtApiError> {
+        let file_name = data["name"].as_str().unwrap_or_default();
+        let content_type = data["type"].as_str().unwrap_or_default();
+        let size = data["size"].as_u64().unwrap_or_default();
+
+        let (urls, chunk_size, upload_id) = match (
+            presigned.urls.as_ref(),
+            presigned.chunk_size,
+            presigned.upload_id.as_ref(),
+        ) {
+            (Some(urls), Some(chunk_size), Some(upload_id)) if chunk_size > 0 => {
+                (urls, chunk_size, upload_id)
+            }
+            _ => {
+                return Err(UtApiError::InvalidInput(format!(
+                    "incomplete multipart upload response for file {}",
+                    presigned.key
+                )))
+            }
+        };
+
+        let disposition = content_disposition_header(settings.content_disposition, file_name);
+        let part_count = urls.len() as u64;
+        let key = presigned.key.clone();
+        let urls = urls.clone().into_iter().enumerate();
+
+        // Read the file one part at a time so that only the parts currently being
+        // uploaded are held in memory.
+        let parts = futures::stream::try_unfold(
+            (reader, urls, 0u64),
+            move |(mut reader, mut urls, read)| {
+                let key = key.clone();
+                async move {
+                    let next = urls.next();
+                    let mut chunk = Vec::with_capacity(chunk_size as usize);
+                    if next.is_some() {
+                        (&mut reader)
+                            .take(chunk_size)
+                            .read_to_end(&mut chunk)
+                            .await?;
+                    }
+                    let read = read + chunk.len() as u64;
+                    let source_ended = chunk.is_empty() && read > 0;
+                    match next {
+                        Some((index, url)) if !source_ended => {
+                            let part = (index + 1, url, Bytes::from(chunk));
+                            return Ok(Some((part, (reader, urls, read))));
+                        }
+                        // Every URL has been used; anything left in the source would be lost.
+                        None if reader.read(&mut [0u8]).await? > 0 => {
+                            return Err(UtApiError::InvalidInput(format!(
+                                "file {} holds more than the {} bytes that fit in its {} parts",
+                                key,
+                                chunk_size.saturating_mul(part_count),
+                                part_count
+                            )));
+                        }
+                        _ => {}
+                    }
+                    if read != size {
+                        return Err(UtApiError::InvalidInput(format!(
+                            "file {} holds {} bytes, but was registered with {}",
+                            key, read, size
+                        )));
+                    }
+                    Ok(None)
+                }
+            },
+        );
+
+        let client = self.clone();
+        let content_type = content_type.to_string();
+        let progress = progress.clone();
+        let sent = Arc::new(AtomicU64::new(0));
+        let all_parts = parts
+            .map_ok(move |(part_number, url, chunk)| {
+                let client = client.clone();
+                let content_type = content_type.clone();
+                let disposition = disposition.clone();
+                let progress = progress.clone();
+                let sent = sent.clone();
+                async move {
+                    let len = chunk.len() as u64;
+                    let tag = client
+                        .upload_part(&url, chunk, &content_type, &disposition)
+                        .await?;
+                    trace_debug!(part = part_number, bytes = len, "uploaded part");
+                    let sent = sent.fetch_add(len, Ordering::Relaxed) + len;
+                    progress.bytes_sent(sent);
+                    Ok(json!({ "tag": tag, "partNumber": part_number }))
+                }
+            })
+            .try_buffered(settings.part_concurrency)
+            // Boxing erases the closure types, which keeps the spawned upload task `Send`.
+            .boxed()
+            .try_collect::<Vec<_>>();
+
+        // The registered size must fit in the parts UploadThing handed out.
+        let etags = if size.div_ceil(chunk_size) > part_count {
+            Err(UtApiError::InvalidInput(format!(
+                "file {} has {} bytes, more than fit in {} parts of {} bytes",
+                presigned.key, size, part_count, chunk_size
+            )))
+        } else {
+            tokio::select! {
+                etags = all_parts => etags,
+                _ = settings.cancel.cancelled() => Err(UtApiError::Cancelled),
+            }
+        };
+
+        match etags {
+            Ok(etags) => {
+                self.request_uploadthing(
+                    "/api/completeMultipart",
+                    &json!({
+                        "fileKey": presigned.key,
+                        "uploadId": upload_id,
+                        "etags": etags,
+                    }),
+                )
+                .await?;
+                Ok(())
+            }
+            Err(e) => {
+                // Let UploadThing abort the multipart upload; the original error is
+                // more useful to the caller than a failure to abort.
+                let abort = self
+                    .request_uploadthing(
+                        "/api/failureCallback",
+                        &json!({ "fileKey": presigned.key, "uploadId": upload_id }),
+                    )
+                    .await;
+                if let Err(_abort_error) = abort {
+                    trace_warn!(error = %_abort_error, "failed to abort multipart upload");
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Uploads a single part of a multipart upload and returns its ETag.
+    ///
+    /// The part is retried according to the configured `RetryPolicy`.
+    async fn upload_part(
+        &self,
+        url: &str,
+        chunk: Bytes,
+        content_type: &str,
+        content_disposition: &str,
+    ) -> Result<String, UtApiError> {
+        self.config
+            .retry_policy
+            .run(|_attempt| async {
+                let res = self
+                    .client
+                    .put(url)
+                    .header(header::CONTENT_TYPE, content_type)
+                    .header(header::CONTENT_DISPOSITION, content_disposition)
+                    .body(chunk.clone())
+                    .send()
+                    .await?;
+
+                if !res.status().is_success() {
+                    return Err(UtApiError::from_upload_response(res).await);
+                }
+
+                match res.headers().get(header::ETAG) {
+                    Some(etag) => Ok(etag.to_str().unwrap_or_default().replace('"', "")),
+                    None => Err(UtApiError::InvalidInput(
+                        "multipart part response is missing an ETag header".to_string(),
+                    )),
+                }
+            })
+            .await
+    }
+
     /// Make a request to UploadThing to check if the file has finished uploading.
-    async fn poll_for_file_data(&self, url: &str) -> Result<Option<()>, anyhow::Error> {
-        let res = self
-            .client
-            .get(url)
-            .header(
-                "x-uploadthing-api-key",
-                self.config.api_key.as_ref().unwrap().to_string(),
-            )
-            .send()
-            .await;
+    async fn poll_for_file_data(&self, url: &str) -> Result<Option<()>, UtApiError> {
+        let res = self.with_client_headers(self.client.get(url)).send().await;
 
         let res = match res {
             Ok(res) => res,
             Err(err) => {
-                println!("[UT] Error polling for file data for {}: {}", url, err);
-                return Err(anyhow!(err));
+                trace_warn!(url, error = %err, "error polling for file data");
+                return Err(UtApiError::Transport(err));
             }
         };
 
-        let maybe_json: Result<serde_json::Value, _> =
-            res.json().await.map_err(|err| err.to_string());
+        if !res.status().is_success() {
+            return Err(UtApiError::from_response(res).await);
+        }
 
-        match maybe_json {
-            Ok(json) => {
-                if json["status"] == "done" {
-                    return Ok(Some(()));
-                }
+        match res.json::<serde_json::Value>().await {
+            Ok(json) if json["status"] == "done" => Ok(Some(())),
+            Ok(_) => Ok(None),
+            // A malformed body is treated like a file that is not ready yet.
+            Err(_err) => {
+                trace_warn!(url, error = %_err, "error polling for file data");
+                Ok(None)
             }
-            Err(err) => {
-                println!("[UT] Error polling for file data for {}: {}", url, err);
+        }
+    }
+}
+
+/// Reads when a presigned or signed file URL expires from its query: the `expires`
+/// parameter of UploadThing's signed URLs, or the `X-Amz-Date` and `X-Amz-Expires`
+/// parameters of S3 presigned URLs.
+fn expiry_from_url(url: &str) -> Option<SystemTime> {
+    let url = url::Url::parse(url).ok()?;
+    let param = |name: &str| {
+        url.query_pairs()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.into_owned())
+    };
+
+    if let Some(millis) = param("expires").and_then(|value| value.parse().ok()) {
+        return UNIX_EPOCH.checked_add(Duration::from_millis(millis));
+    }
+    let signed_at = parse_amz_date(&param("X-Amz-Date")?)?;
+    let expires_in = param("X-Amz-Expires")?.parse().ok()?;
+    signed_at.checked_add(Duration::from_secs(expires_in))
+}
+
+/// Parses an AWS signature timestamp such as `20240131T235959Z`.
+fn parse_amz_date(value: &str) -> Option<SystemTime> {
+    if value.len() != 16 || !value.is_ascii() || &value[8..9] != "T" || &value[15..] != "Z" {
+        return None;
+    }
+    let field = |range: std::ops::Range<usize>| {
+        let digits = &value[range];
+        if digits.bytes().all(|b| b.is_ascii_digit()) {
+            digits.parse::<u64>().ok()
+        } else {
+            None
+        }
+    };
+    let (year, month, day) = (field(0..4)?, field(4..6)?, field(6..8)?);
+    let (hour, minute, second) = (field(9..11)?, field(11..13)?, field(13..15)?);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || year < 1970 {
+        return None;
+    }
+    if hour > 23 || minute > 59 || second > 59 {
+        return None;
+    }
+
+    // Days since the Unix epoch of a date in the proleptic Gregorian calendar.
+    let (y, m) = if month <= 2 {
+        (year - 1, month + 9)
+    } else {
+        (year, month - 3)
+    };
+    let era = y / 400;
+    let year_of_era = y % 400;
+    let day_of_year = (153 * m + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = (era * 146_097 + day_of_era).checked_sub(719_468)?;
+
+    let seconds = days * 86_400 + hour * 3600 + minute * 60 + second;
+    UNIX_EPOCH.checked_add(Duration::from_secs(seconds))
+}
+
+/// Reports every file of a batch that UploadThing rejected with the shared error of the batch.
+fn rejected_batch(names: Vec<String>, e: UtApiError) -> Vec<UploadResult> {
+    let e = Arc::new(e);
+    names
+        .into_iter()
+        .map(|name| UploadResult {
+            name,
+            result: Err(match *e {
+                UtApiError::Cancelled => UtApiError::Cancelled,
+                _ => UtApiError::BatchRejected(e.clone()),
+            }),
+        })
+        .collect()
+}
+
+/// A source that yielded more or fewer bytes than the size it was registered with.
+#[derive(Debug)]
+struct SizeMismatch {
+    expected: u64,
+    read: u64,
+}
+
+impl std::fmt::Display for SizeMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.read > self.expected {
+            write!(f, "the file holds more than its {} bytes", self.expected)
+        } else {
+            write!(
+                f,
+                "the file ended after {} of its {} bytes",
+                self.read, self.expected
+            )
+        }
+    }
+}
+
+impl std::error::Error for SizeMismatch {}
+
+/// Streams the contents of `reader`, failing with `SizeMismatch` if it does not hold
+/// exactly `size` bytes. The body would otherwise not match its declared length, which
+/// either corrupts the upload or fails it with an opaque transport error.
+fn stream_exact(
+    reader: Box<dyn AsyncRead + Send + Unpin>,
+    size: u64,
+) -> impl Stream<Item = std::io::Result<Bytes>> {
+    let chunks = ReaderStream::with_capacity(reader, STREAM_CHUNK_SIZE);
+    futures::stream::try_unfold((chunks, 0u64), move |(mut chunks, read)| async move {
+        let mismatch = |read| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                SizeMismatch {
+                    expected: size,
+                    read,
+                },
+            )
+        };
+        match chunks.try_next().await? {
+            Some(chunk) => {
+                let read = read + chunk.len() as u64;
+                if read > size {
+                    return Err(mismatch(read));
+                }
+                Ok(Some((chunk, (chunks, read))))
             }
+            None if read < size => Err(mismatch(read)),
+            None => Ok(None),
         }
+    })
+}
 
-        Ok(None)
+/// Turns an error sending an upload into `UtApiError::InvalidInput` when the body stream
+/// failed with a `SizeMismatch`, which no retry can fix.
+fn body_error(e: reqwest::Error) -> UtApiError {
+    let mut source = std::error::Error::source(&e);
+    while let Some(error) = source {
+        let mismatch = error
+            .downcast_ref::<std::io::Error>()
+            .and_then(|e| e.get_ref())
+            .and_then(|e| e.downcast_ref::<SizeMismatch>());
+        if let Some(mismatch) = mismatch {
+            return UtApiError::InvalidInput(mismatch.to_string());
+        }
+        source = error.source();
     }
+    UtApiError::Transport(e)
+}
+
+/// Returns the source to read for the next attempt of an upload.
+///
+/// Replayable sources are copied so that they can be read again; a reader is handed
+/// out once, and later attempts fail.
+fn next_attempt_source(source: &mut Option<FileSource>) -> Result<FileSource, UtApiError> {
+    source
+        .as_ref()
+        .and_then(FileSource::try_clone)
+        .or_else(|| source.take())
+        .ok_or_else(|| {
+            UtApiError::InvalidInput("the file source can only be read once".to_string())
+        })
 }
 
-/// Retry a function with exponential timed back-off.
-async fn retry_with_time_delays<F, T, Fut>(do_the_thing: F) -> Result<Option<T>, anyhow::Error>
+/// Call a polling function until it returns `Some`, with exponential timed back-off.
+///
+/// The back-off follows `policy`, and errors that the policy does not consider retryable
+/// end the polling. Gives up with `Ok(None)` after `policy.poll_attempts` calls, or with
+/// the error if the last call failed, without waiting after the last call.
+async fn retry_with_time_delays<F, T, Fut>(
+    policy: &RetryPolicy,
+    do_the_thing: F,
+) -> Result<Option<T>, UtApiError>
 where
     F: Fn() -> Fut,
-    Fut: Future<Output = Result<Option<T>, anyhow::Error>>,
+    Fut: Future<Output = Result<Option<T>, UtApiError>>,
 {
+    let max_tries = policy.poll_attempts.max(1);
     let mut tries = 0;
-    let mut backoff_ms = 500;
-    let mut backoff_fuzz_ms: i32;
 
     loop {
-        if tries > MAX_RETRIES {
-            return Ok(None);
-        }
+        tries += 1;
+        let last = tries >= max_tries;
+        let backoff = match do_the_thing().await {
+            Ok(Some(value)) => return Ok(Some(value)),
+            Ok(None) if last => return Ok(None),
+            Ok(None) => policy.backoff(tries),
+            Err(e) if last => return Err(e),
+            Err(e) if policy.is_retryable(&e) => match policy.retry_delay(&e, tries) {
+                Some(delay) => delay,
+                None => return Err(e),
+            },
+            Err(e) => return Err(e),
+        };
 
-        let result = do_the_thing().await;
-        if result.is_ok() {
-            return result;
-        }
+        trace_debug!(
+            attempt = tries,
+            delay_ms = backoff.as_millis() as u64,
+            "file not ready yet, polling again"
+        );
 
-        tries += 1;
-        backoff_ms = std::cmp::min(MAXIMUM_BACKOFF_MS, backoff_ms * 2);
-        backoff_fuzz_ms = thread_rng().gen_range(0..500);
-
-        if tries > 3 {
-            println!(
-                "[UT] Call unsuccessful after {} tries. Retrying in {} seconds...",
-                tries,
-                backoff_ms / 1000
-            );
+        tokio::time::sleep(backoff).await;
+    }
+}
+
+/// Extracts the file name from a `Content-Disposition` header value.
+fn file_name_from_content_disposition(value: &str) -> Option<String> {
+    value
+        .split(';')
+        .map(str::trim)
+        .find_map(|param| param.strip_prefix("filename="))
+        .map(|name| name.trim_matches('"').to_string())
+        .filter(|name| !name.is_empty())
+}
+
+/// Builds a `Content-Disposition` header value for a file, mirroring the JS SDK.
+fn content_disposition_header(disposition: &str, file_name: &str) -> String {
+    let encoded = encode_uri_component(file_name);
+    format!(
+        "{}; filename=\"{}\"; filename*=UTF-8''{}",
+        disposition, encoded, encoded
+    )
+}
+
+/// Percent-encodes a string the same way as JavaScript's `encodeURIComponent`.
+pub(crate) fn encode_uri_component(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z'
+            | b'a'..=b'z'
+            | b'0'..=b'9'
+            | b'-'
+            | b'_'
+            | b'.'
+            | b'!'
+            | b'~'
+            | b'*'
+            | b'\''
+            | b'('
+            | b')' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
         }
+    }
+    encoded
+}
 
-        tokio::select! {
-            _ = tokio::signal::ctrl_c() => {
-                return Err(anyhow::anyhow!("Operation cancelled"));
-            }
-            _ = tokio::time::sleep(Duration::from_millis(backoff_ms + backoff_fuzz_ms as u64)) => {}
+/// Reads the body of a successful response and deserializes it as JSON.
+///
+/// Transport failures while reading the body are reported as `UtApiError::Transport`,
+/// and a body that does not match `T` is reported as `UtApiError::Deserialize`.
+async fn parse_json<T: DeserializeOwned>(response: Response) -> Result<T, UtApiError> {
+    let bytes = response.bytes().await?;
+    Ok(serde_json::from_slice(&bytes)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unix_secs(secs: u64) -> Option<SystemTime> {
+        Some(UNIX_EPOCH + Duration::from_secs(secs))
+    }
+
+    #[test]
+    fn parse_amz_date_converts_civil_dates() {
+        assert_eq!(parse_amz_date("19700101T000000Z"), unix_secs(0));
+        assert_eq!(parse_amz_date("19991231T235959Z"), unix_secs(946_684_799));
+        assert_eq!(parse_amz_date("20000301T000000Z"), unix_secs(951_868_800));
+        assert_eq!(parse_amz_date("20240131T235959Z"), unix_secs(1_706_745_599));
+        assert_eq!(parse_amz_date("20240229T120000Z"), unix_secs(1_709_208_000));
+        assert_eq!(parse_amz_date("21000301T000000Z"), unix_secs(4_107_542_400));
+    }
+
+    #[test]
+    fn parse_amz_date_rejects_malformed_timestamps() {
+        for value in [
+            "",
+            "20240131T235959",
+            "20240131 235959Z",
+            "2024-01-31T23:59Z",
+            "20241301T000000Z",
+            "20240100T000000Z",
+            "20240132T000000Z",
+            "20240131T240000Z",
+            "20240131T236000Z",
+            "20240131T235960Z",
+            "19691231T235959Z",
+            "+0240131T235959Z",
+            "2024013éT23595Z",
+        ] {
+            assert_eq!(parse_amz_date(value), None, "{}", value);
         }
     }
 }
@@ -0,0 +1,623 @@
+use reqwest::header::HeaderMap;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::config::{ApiKey, UploadthingConfig};
+use crate::error::{ConfigError, WebhookError};
+use crate::signing;
+
+/// The header that carries the `hmac-sha256=<hex>` signature of the request body.
+pub const SIGNATURE_HEADER: &str = "x-uploadthing-signature";
+
+/// The header that names the kind of webhook, such as `callback` or `error`.
+pub const HOOK_HEADER: &str = "uploadthing-hook";
+
+/// The default value of `WebhookVerifier::with_tolerance`.
+pub const DEFAULT_TOLERANCE: Duration = Duration::from_secs(5 * 60);
+
+/// The default value of `WebhookVerifier::with_replay_window`.
+pub const DEFAULT_REPLAY_WINDOW: Duration = Duration::from_secs(60 * 60);
+
+/// The maximum number of signatures remembered to detect replayed requests.
+const MAX_REMEMBERED_SIGNATURES: usize = 10_000;
+
+/// Timestamps at or above this are taken to be in milliseconds rather than seconds.
+const MILLIS_THRESHOLD: u64 = 1_000_000_000_000;
+
+/// Verifies the webhook requests UploadThing sends to an app's server and parses them
+/// into `WebhookEvent`s.
+///
+/// A request is accepted if its body is signed with the app's API key and no request with
+/// the same signature was marked as processed within the replay window.
+///
+/// Verifying a request does not record it: call `WebhookVerifier::mark_processed` once the
+/// event has been handled. If the handler fails, UploadThing can redeliver the callback
+/// and the redelivery is accepted. Two deliveries of the same request that are verified
+/// before either is marked are both accepted; `mark_processed` reports the second one.
+///
+/// UploadThing signs only the body and sends no signed time, so a captured request stays
+/// validly signed. The replay check is best effort: processed signatures are remembered in
+/// memory, by this verifier and its clones only, so a request replayed to another process
+/// or after a restart is accepted again. Deduplicate on the file key where that matters.
+/// If the body carries the time it was sent, `with_timestamp_field` rejects requests
+/// that arrive outside the tolerance.
+///
+/// Clones share the memory of processed signatures, so one verifier can be cloned into
+/// every request handler of a process.
+///
+/// # Examples
+///
+/// ```
+/// use utapi_rs::config::ApiKey;
+/// use utapi_rs::webhook::{WebhookEvent, WebhookHeaders, WebhookVerifier};
+///
+/// # fn handle(headers: &reqwest::header::HeaderMap, body: &[u8]) {
+/// let verifier = WebhookVerifier::new(&"sk_live_123".parse::<ApiKey>().unwrap());
+/// match verifier.verify(headers, body) {
+///     Ok(WebhookEvent::UploadComplete(event)) => {
+///         println!("{} uploaded to {}", event.file.name, event.file.url);
+///         // Only now is a redelivery of the same request rejected.
+///         let _ = verifier.mark_processed(WebhookHeaders::from_header_map(headers));
+///     }
+///     Ok(_) => {}
+///     Err(e) => eprintln!("rejected webhook: {}", e),
+/// }
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct WebhookVerifier {
+    secret: String,
+    tolerance: Duration,
+    // The top-level body field holding the time the request was sent, if any.
+    timestamp_field: Option<String>,
+    require_timestamp: bool,
+    replay_window: Duration,
+    // The signatures of processed requests, with the time they were marked as processed.
+    seen: Arc<Mutex<HashMap<String, SystemTime>>>,
+}
+
+impl WebhookVerifier {
+    /// Creates a verifier for webhooks signed with the given API key.
+    ///
+    /// # Arguments
+    ///
+    /// * `api_key` - The API key of the app, as used by `UtApi`.
+    pub fn new(api_key: &ApiKey) -> WebhookVerifier {
+        WebhookVerifier {
+            secret: api_key.key.clone(),
+            tolerance: DEFAULT_TOLERANCE,
+            timestamp_field: None,
+            require_timestamp: false,
+            replay_window: DEFAULT_REPLAY_WINDOW,
+            seen: Arc::default(),
+        }
+    }
+
+    /// Creates a verifier for webhooks signed with the API key of `config`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use utapi_rs::config::UploadthingConfig;
+    /// use utapi_rs::webhook::WebhookVerifier;
+    ///
+    /// let config = UploadthingConfig::builder().api_key("sk_live_123").build()?;
+    /// let verifier = WebhookVerifier::from_config(&config)?;
+    /// # Ok::<(), utapi_rs::ConfigError>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConfigError::MissingApiKey` if the configuration has no API key.
+    pub fn from_config(config: &UploadthingConfig) -> Result<WebhookVerifier, ConfigError> {
+        let api_key = config.api_key.as_ref().ok_or(ConfigError::MissingApiKey)?;
+        Ok(WebhookVerifier::new(api_key))
+    }
+
+    /// Checks the time a request was sent against the tolerance, reading it from the
+    /// top-level `field` of the body, in seconds or milliseconds since the Unix epoch.
+    ///
+    /// The body is covered by the signature, so the time cannot be changed without
+    /// invalidating the request. Requests without the field are accepted unless
+    /// `require_timestamp` is set.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use utapi_rs::config::ApiKey;
+    /// use utapi_rs::webhook::WebhookVerifier;
+    ///
+    /// let verifier = WebhookVerifier::new(&"sk_live_123".parse::<ApiKey>().unwrap())
+    ///     .with_timestamp_field("sentAt")
+    ///     .with_tolerance(Duration::from_secs(60))
+    ///     .require_timestamp(true);
+    /// ```
+    pub fn with_timestamp_field(mut self, field: &str) -> WebhookVerifier {
+        self.timestamp_field = Some(field.to_string());
+        self
+    }
+
+    /// Sets how far the timestamp of a request may be from the current time.
+    pub fn with_tolerance(mut self, tolerance: Duration) -> WebhookVerifier {
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// Sets whether requests without a timestamp are rejected. Off by default; when it is
+    /// on, every request is rejected unless a field is set with `with_timestamp_field`.
+    pub fn require_timestamp(mut self, require: bool) -> WebhookVerifier {
+        self.require_timestamp = require;
+        self
+    }
+
+    /// Sets how long the signatures of processed requests are remembered to reject replays.
+    /// `Duration::ZERO` disables replay protection.
+    pub fn with_replay_window(mut self, replay_window: Duration) -> WebhookVerifier {
+        self.replay_window = replay_window;
+        self
+    }
+
+    /// Verifies a webhook request and parses its body.
+    ///
+    /// # Arguments
+    ///
+    /// * `headers` - The headers of the request.
+    /// * `body` - The raw body of the request, exactly as received.
+    ///
+    /// # Errors
+    ///
+    /// * `WebhookError::MissingSignature` or `WebhookError::InvalidSignature` if the body
+    ///   is not signed with the API key.
+    /// * `WebhookError::Deserialize` if the body is not a webhook payload.
+    /// * `WebhookError::InvalidTimestamp` or `WebhookError::Stale` if the timestamp
+    ///   cannot be parsed or is outside the tolerance.
+    /// * `WebhookError::MissingTimestamp` if the body has no timestamp and one is required.
+    /// * `WebhookError::Replayed` if the same request was already marked as processed.
+    pub fn verify(&self, headers: &HeaderMap, body: &[u8]) -> Result<WebhookEvent, WebhookError> {
+        self.verify_parts(WebhookHeaders::from_header_map(headers), body)
+    }
+
+    /// Verifies a webhook request whose headers were read by the caller, for HTTP
+    /// frameworks that do not use `reqwest::header::HeaderMap`.
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as `WebhookVerifier::verify`.
+    pub fn verify_parts(
+        &self,
+        headers: WebhookHeaders<'_>,
+        body: &[u8],
+    ) -> Result<WebhookEvent, WebhookError> {
+        let signature = headers.signature.ok_or(WebhookError::MissingSignature)?;
+        if !signing::verify_signature(body, signature, &self.secret) {
+            return Err(WebhookError::InvalidSignature);
+        }
+
+        let payload: serde_json::Value = serde_json::from_slice(body)?;
+        let now = SystemTime::now();
+        let timestamp = self
+            .timestamp_field
+            .as_deref()
+            .and_then(|field| payload.get(field))
+            .filter(|timestamp| !timestamp.is_null());
+        match timestamp {
+            Some(timestamp) => self.check_timestamp(timestamp, now)?,
+            None if self.require_timestamp => return Err(WebhookError::MissingTimestamp),
+            None => {}
+        }
+
+        let event = WebhookEvent::parse(headers.hook, payload)?;
+        if self.was_processed(&normalize_signature(signature), now) {
+            return Err(WebhookError::Replayed);
+        }
+        Ok(event)
+    }
+
+    /// Records that the request with the given headers has been handled, so that it is
+    /// rejected as `WebhookError::Replayed` if it is delivered again within the replay
+    /// window. Call it once the event returned by `verify` has been processed
+    /// successfully; a request that is not marked can be redelivered and verified again.
+    ///
+    /// # Errors
+    ///
+    /// * `WebhookError::MissingSignature` if the headers hold no signature.
+    /// * `WebhookError::Replayed` if the request was already marked as processed, e.g. by
+    ///   another handler that received the same delivery at the same time.
+    pub fn mark_processed(&self, headers: WebhookHeaders<'_>) -> Result<(), WebhookError> {
+        let signature = headers.signature.ok_or(WebhookError::MissingSignature)?;
+        self.remember(&normalize_signature(signature), SystemTime::now())
+    }
+
+    /// Rejects timestamps that cannot be parsed or are too far from `now`.
+    ///
+    /// The timestamp may be a number or a string holding one.
+    fn check_timestamp(
+        &self,
+        timestamp: &serde_json::Value,
+        now: SystemTime,
+    ) -> Result<(), WebhookError> {
+        let value = match timestamp {
+            serde_json::Value::String(value) => value.trim().parse().ok(),
+            value => value.as_u64(),
+        }
+        .ok_or_else(|| WebhookError::InvalidTimestamp(timestamp.to_string()))?;
+        let offset = if value >= MILLIS_THRESHOLD {
+            Duration::from_millis(value)
+        } else {
+            Duration::from_secs(value)
+        };
+        let sent_at = UNIX_EPOCH
+            .checked_add(offset)
+            .ok_or_else(|| WebhookError::InvalidTimestamp(timestamp.to_string()))?;
+
+        let age = now.duration_since(sent_at).unwrap_or_else(|e| e.duration());
+        if age > self.tolerance {
+            return Err(WebhookError::Stale {
+                age,
+                tolerance: self.tolerance,
+            });
+        }
+        Ok(())
+    }
+
+    /// Returns `true` if a signature was marked as processed within the replay window.
+    fn was_processed(&self, signature: &str, now: SystemTime) -> bool {
+        if self.replay_window.is_zero() {
+            return false;
+        }
+        let seen = self.seen.lock().unwrap_or_else(|e| e.into_inner());
+        seen.get(signature)
+            .is_some_and(|processed_at| self.within_window(*processed_at, now))
+    }
+
+    /// Records a processed signature, failing if it was already recorded within the
+    /// replay window.
+    fn remember(&self, signature: &str, now: SystemTime) -> Result<(), WebhookError> {
+        if self.replay_window.is_zero() {
+            return Ok(());
+        }
+
+        let mut seen = self.seen.lock().unwrap_or_else(|e| e.into_inner());
+        seen.retain(|_, processed_at| self.within_window(*processed_at, now));
+        if seen.contains_key(signature) {
+            return Err(WebhookError::Replayed);
+        }
+
+        // Forget the oldest signatures rather than growing without bound under a flood.
+        if seen.len() >= MAX_REMEMBERED_SIGNATURES {
+            let mut processed: Vec<_> = seen.iter().map(|(k, t)| (*t, k.clone())).collect();
+            processed.sort();
+            for (_, signature) in processed.into_iter().take(seen.len() / 10 + 1) {
+                seen.remove(&signature);
+            }
+        }
+        seen.insert(signature.to_string(), now);
+        Ok(())
+    }
+
+    /// Returns `true` if a signature processed at `processed_at` is still remembered.
+    fn within_window(&self, processed_at: SystemTime, now: SystemTime) -> bool {
+        now.duration_since(processed_at)
+            .map_or(true, |age| age < self.replay_window)
+    }
+}
+
+impl std::fmt::Debug for WebhookVerifier {
+    // The API key is left out so that it does not end up in logs.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WebhookVerifier")
+            .field("tolerance", &self.tolerance)
+            .field("timestamp_field", &self.timestamp_field)
+            .field("require_timestamp", &self.require_timestamp)
+            .field("replay_window", &self.replay_window)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Returns a signature in the form it is remembered in. Hex digits may come in either
+/// case; both spell the same signature.
+fn normalize_signature(signature: &str) -> String {
+    signature.trim().to_ascii_lowercase()
+}
+
+/// The headers of a webhook request that `WebhookVerifier` looks at.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WebhookHeaders<'a> {
+    /// The value of the `x-uploadthing-signature` header.
+    pub signature: Option<&'a str>,
+    /// The value of the `uploadthing-hook` header.
+    pub hook: Option<&'a str>,
+}
+
+impl<'a> WebhookHeaders<'a> {
+    /// Reads the webhook headers from a header map.
+    pub fn from_header_map(headers: &'a HeaderMap) -> WebhookHeaders<'a> {
+        let get = |name| headers.get(name).and_then(|value| value.to_str().ok());
+        WebhookHeaders {
+            signature: get(SIGNATURE_HEADER),
+            hook: get(HOOK_HEADER),
+        }
+    }
+}
+
+/// A webhook sent by UploadThing.
+#[derive(Debug, Clone)]
+pub enum WebhookEvent {
+    /// A file has been uploaded.
+    UploadComplete(Box<UploadCompleteEvent>),
+    /// A file failed to upload.
+    UploadFailed(UploadFailedEvent),
+    /// A webhook this version of the crate does not know about.
+    Unknown {
+        /// The value of the `uploadthing-hook` header, if any.
+        hook: Option<String>,
+        /// The body of the request.
+        payload: serde_json::Value,
+    },
+}
+
+impl WebhookEvent {
+    /// Parses a body according to the kind of hook, or its shape if the kind is not given.
+    fn parse(hook: Option<&str>, payload: serde_json::Value) -> Result<WebhookEvent, WebhookError> {
+        let event = match hook {
+            Some("callback") => WebhookEvent::UploadComplete(serde_json::from_value(payload)?),
+            Some("error") => WebhookEvent::UploadFailed(serde_json::from_value(payload)?),
+            None if payload.get("file").is_some() => {
+                WebhookEvent::UploadComplete(serde_json::from_value(payload)?)
+            }
+            _ => WebhookEvent::Unknown {
+                hook: hook.map(str::to_string),
+                payload,
+            },
+        };
+        Ok(event)
+    }
+}
+
+/// The payload of a webhook sent after a file has been uploaded.
+#[derive(Debug, Clone, Deserialize)]
+pub struct UploadCompleteEvent {
+    /// The status of the upload, such as `uploaded`.
+    #[serde(default)]
+    pub status: Option<String>,
+    /// The metadata attached to the upload.
+    #[serde(default)]
+    pub metadata: serde_json::Value,
+    /// The uploaded file.
+    pub file: UploadedFile,
+    /// Fields sent by UploadThing that this version of the crate does not know about.
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
+}
+
+/// A file described by an `UploadCompleteEvent`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct UploadedFile {
+    /// The key of the file.
+    pub key: String,
+    /// The URL the file is served from.
+    pub url: String,
+    /// The name of the file.
+    pub name: String,
+    /// The size of the file in bytes.
+    pub size: u64,
+    /// The custom ID the file was uploaded with, if any.
+    #[serde(rename = "customId", default)]
+    pub custom_id: Option<String>,
+    /// The MIME type of the file.
+    #[serde(rename = "type", default)]
+    pub file_type: Option<String>,
+    /// The app-specific URL of the file.
+    #[serde(rename = "appUrl", default)]
+    pub app_url: Option<String>,
+    /// The URL of the file on the UploadThing file server.
+    #[serde(rename = "ufsUrl", default)]
+    pub ufs_url: Option<String>,
+    /// The hash of the file contents.
+    #[serde(rename = "fileHash", default)]
+    pub file_hash: Option<String>,
+    /// Fields sent by UploadThing that this version of the crate does not know about.
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
+}
+
+/// The payload of a webhook sent after a file failed to upload.
+#[derive(Debug, Clone, Deserialize)]
+pub struct UploadFailedEvent {
+    /// The key of the file.
+    #[serde(rename = "fileKey")]
+    pub file_key: String,
+    /// A description of the failure.
+    #[serde(default)]
+    pub error: Option<String>,
+    /// Fields sent by UploadThing that this version of the crate does not know about.
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BODY: &str = r#"{"status":"uploaded","metadata":{"userId":"u1"},"file":{"key":"abc123","url":"https://app123.ufs.sh/f/abc123","name":"a.png","size":42,"customId":null,"type":"image/png"}}"#;
+
+    // The HMAC-SHA256 of `BODY` with the key `sk_live_123`.
+    const SIGNATURE: &str =
+        "hmac-sha256=1283d4b752ee5e526052f367f0b7e70a863c05d4e9d2b1b3b6aa7e171246e8ea";
+
+    fn verifier() -> WebhookVerifier {
+        WebhookVerifier::new(&"sk_live_123".parse::<ApiKey>().unwrap())
+    }
+
+    fn headers(signature: &str) -> WebhookHeaders<'_> {
+        WebhookHeaders {
+            signature: Some(signature),
+            hook: Some("callback"),
+        }
+    }
+
+    fn now_millis() -> u128 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis()
+    }
+
+    #[test]
+    fn accepts_a_signed_request() {
+        let event = verifier()
+            .verify_parts(headers(SIGNATURE), BODY.as_bytes())
+            .unwrap();
+        let WebhookEvent::UploadComplete(event) = event else {
+            panic!("expected an upload completion, got {:?}", event);
+        };
+        assert_eq!(event.file.key, "abc123");
+        assert_eq!(event.file.size, 42);
+        assert_eq!(event.metadata["userId"], "u1");
+    }
+
+    #[test]
+    fn reads_the_headers_of_a_header_map() {
+        let mut map = HeaderMap::new();
+        map.insert(SIGNATURE_HEADER, SIGNATURE.parse().unwrap());
+        map.insert(HOOK_HEADER, "callback".parse().unwrap());
+        assert!(verifier().verify(&map, BODY.as_bytes()).is_ok());
+    }
+
+    #[test]
+    fn accepts_uppercase_hex() {
+        let signature = format!("hmac-sha256={}", SIGNATURE[12..].to_ascii_uppercase());
+        assert!(verifier()
+            .verify_parts(headers(&signature), BODY.as_bytes())
+            .is_ok());
+    }
+
+    #[test]
+    fn rejects_a_missing_signature() {
+        let headers = WebhookHeaders {
+            signature: None,
+            hook: Some("callback"),
+        };
+        let result = verifier().verify_parts(headers, BODY.as_bytes());
+        assert!(matches!(result, Err(WebhookError::MissingSignature)));
+    }
+
+    #[test]
+    fn rejects_a_signature_made_with_another_key() {
+        let other = WebhookVerifier::new(&"sk_live_456".parse::<ApiKey>().unwrap());
+        let result = other.verify_parts(headers(SIGNATURE), BODY.as_bytes());
+        assert!(matches!(result, Err(WebhookError::InvalidSignature)));
+    }
+
+    #[test]
+    fn rejects_malformed_signatures() {
+        for signature in ["", "hmac-sha256=", "hmac-sha256=zz", &SIGNATURE[12..]] {
+            let result = verifier().verify_parts(headers(signature), BODY.as_bytes());
+            assert!(
+                matches!(result, Err(WebhookError::InvalidSignature)),
+                "{:?} was not rejected",
+                signature
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_a_tampered_body() {
+        let tampered = BODY.replace(r#""size":42"#, r#""size":43"#);
+        let result = verifier().verify_parts(headers(SIGNATURE), tampered.as_bytes());
+        assert!(matches!(result, Err(WebhookError::InvalidSignature)));
+    }
+
+    #[test]
+    fn rejects_a_replay_of_a_processed_request() {
+        let verifier = verifier();
+        assert!(verifier
+            .verify_parts(headers(SIGNATURE), BODY.as_bytes())
+            .is_ok());
+        verifier.mark_processed(headers(SIGNATURE)).unwrap();
+
+        // Clones share the processed signatures, in either case.
+        let signature = format!("hmac-sha256={}", SIGNATURE[12..].to_ascii_uppercase());
+        let result = verifier
+            .clone()
+            .verify_parts(headers(&signature), BODY.as_bytes());
+        assert!(matches!(result, Err(WebhookError::Replayed)));
+    }
+
+    #[test]
+    fn accepts_a_redelivery_of_a_request_that_was_not_processed() {
+        // A handler that failed does not mark the request, so UploadThing can retry it.
+        let verifier = verifier();
+        for _ in 0..2 {
+            assert!(verifier
+                .verify_parts(headers(SIGNATURE), BODY.as_bytes())
+                .is_ok());
+        }
+    }
+
+    #[test]
+    fn reports_a_request_marked_as_processed_twice() {
+        let verifier = verifier();
+        verifier.mark_processed(headers(SIGNATURE)).unwrap();
+        let result = verifier.mark_processed(headers(SIGNATURE));
+        assert!(matches!(result, Err(WebhookError::Replayed)));
+
+        let result = verifier.mark_processed(WebhookHeaders::default());
+        assert!(matches!(result, Err(WebhookError::MissingSignature)));
+    }
+
+    #[test]
+    fn accepts_replays_when_the_window_is_zero() {
+        let verifier = verifier().with_replay_window(Duration::ZERO);
+        for _ in 0..2 {
+            assert!(verifier
+                .verify_parts(headers(SIGNATURE), BODY.as_bytes())
+                .is_ok());
+            verifier.mark_processed(headers(SIGNATURE)).unwrap();
+        }
+    }
+
+    #[test]
+    fn checks_a_signed_timestamp_field() {
+        let verifier = verifier()
+            .with_timestamp_field("sentAt")
+            .with_tolerance(Duration::from_secs(60));
+        let verify = |sent_at: &str| {
+            let body = format!(
+                r#"{{"sentAt":{},"file":{{"key":"k","url":"u","name":"n","size":1}}}}"#,
+                sent_at
+            );
+            let signature = signing::sign_payload(&body, "sk_live_123");
+            verifier.verify_parts(headers(&signature), body.as_bytes())
+        };
+
+        assert!(verify(&now_millis().to_string()).is_ok());
+        // Seconds given as a string are accepted too.
+        assert!(verify(&format!(r#""{}""#, now_millis() / 1000 - 30)).is_ok());
+        assert!(matches!(
+            verify(&(now_millis() / 1000 - 600).to_string()),
+            Err(WebhookError::Stale { .. })
+        ));
+        assert!(matches!(
+            verify(r#""yesterday""#),
+            Err(WebhookError::InvalidTimestamp(_))
+        ));
+    }
+
+    #[test]
+    fn requires_a_timestamp_only_when_asked_to() {
+        let verifier = verifier().with_timestamp_field("sentAt");
+        assert!(verifier
+            .verify_parts(headers(SIGNATURE), BODY.as_bytes())
+            .is_ok());
+
+        let result = verifier
+            .require_timestamp(true)
+            .with_replay_window(Duration::ZERO)
+            .verify_parts(headers(SIGNATURE), BODY.as_bytes());
+        assert!(matches!(result, Err(WebhookError::MissingTimestamp)));
+    }
+}
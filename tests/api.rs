@@ -0,0 +1,289 @@
+//! Every endpoint of `FakeUploadThing`, and the hooks that shape its answers, driven
+//! through `UtApi`.
+
+mod common;
+
+use common::{fast_client, pattern};
+use reqwest::StatusCode;
+use std::time::{Duration, Instant};
+use utapi_rs::models::{
+    Expiry, FileObj, KeyType, ListFilesOpts, PresignedUrlOpts, RenameFilesOpts, SingleFileRename,
+    UploadthingFileStatus,
+};
+use utapi_rs::testing::{Endpoint, FakeUploadThing};
+
+#[tokio::test]
+async fn uploads_to_the_presigned_post_target_and_polls_until_done() {
+    let fake = FakeUploadThing::start().unwrap();
+    let api = fast_client(&fake);
+
+    let file = FileObj::from_bytes("photo.png", pattern(10_000)).with_custom_id("photo");
+    let report = api.upload_files(vec![file], None, true).await.unwrap();
+    let upload = report.results[0].result.as_ref().unwrap();
+
+    let stored = fake.file(&upload.key).unwrap();
+    assert!(stored.uploaded);
+    assert_eq!(stored.content, pattern(10_000));
+    assert_eq!(stored.content_type, "image/png");
+    assert_eq!(stored.custom_id.as_deref(), Some("photo"));
+    assert_eq!(upload.size, 10_000);
+    assert_eq!(upload.custom_id.as_deref(), Some("photo"));
+    assert_eq!(upload.url, format!("{}/f/{}", fake.url(), upload.key));
+
+    assert_eq!(fake.request_count(Endpoint::UploadFiles), 1);
+    assert_eq!(fake.request_count(Endpoint::PresignedPost), 1);
+    assert_eq!(fake.request_count(Endpoint::PollUpload), 1);
+}
+
+#[tokio::test]
+async fn skips_polling_unless_asked_to_wait() {
+    let fake = FakeUploadThing::start().unwrap();
+    let api = fast_client(&fake);
+
+    let file = FileObj::from_bytes("a.txt", "a");
+    let report = api.upload_files(vec![file], None, false).await.unwrap();
+
+    assert!(report.is_success());
+    assert_eq!(fake.request_count(Endpoint::PollUpload), 0);
+}
+
+#[tokio::test]
+async fn lists_files_a_page_at_a_time() {
+    let fake = FakeUploadThing::start().unwrap();
+    let api = fast_client(&fake);
+    let keys: Vec<String> = (0..5)
+        .map(|i| fake.add_file(format!("{}.txt", i), "x", None).key)
+        .collect();
+
+    let page = api
+        .list_files(Some(ListFilesOpts {
+            limit: Some(2),
+            offset: Some(2),
+        }))
+        .await
+        .unwrap();
+    let listed: Vec<&str> = page.files.iter().map(|file| file.key.as_str()).collect();
+    assert_eq!(listed, [keys[2].as_str(), keys[3].as_str()]);
+    assert_eq!(page.has_more, Some(true));
+    assert_eq!(page.files[0].name, "2.txt");
+    assert_eq!(page.files[0].size, Some(1));
+    assert_eq!(page.files[0].status, UploadthingFileStatus::Uploaded);
+    assert!(page.files[0].uploaded_at.is_some());
+
+    let last = api
+        .list_files(Some(ListFilesOpts {
+            limit: Some(2),
+            offset: Some(4),
+        }))
+        .await
+        .unwrap();
+    assert_eq!(last.files.len(), 1);
+    assert_eq!(last.has_more, Some(false));
+}
+
+#[tokio::test]
+async fn deletes_files_by_key_and_by_custom_id() {
+    let fake = FakeUploadThing::start().unwrap();
+    let api = fast_client(&fake);
+    let by_key = fake.add_file("a.txt", "a", None);
+    fake.add_file("b.txt", "b", Some("b"));
+    let kept = fake.add_file("c.txt", "c", None);
+
+    let response = api
+        .delete_files(vec![by_key.key.clone()], KeyType::FileKey)
+        .await
+        .unwrap();
+    assert!(response.success);
+    assert_eq!(response.deleted_count, Some(1));
+
+    let response = api
+        .delete_files(
+            vec!["b".to_string(), "missing".to_string()],
+            KeyType::CustomId,
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.deleted_count, Some(1));
+
+    let remaining: Vec<String> = fake.files().into_iter().map(|file| file.key).collect();
+    assert_eq!(remaining, [kept.key]);
+}
+
+#[tokio::test]
+async fn gets_file_urls_by_key_and_by_custom_id() {
+    let fake = FakeUploadThing::start().unwrap();
+    let api = fast_client(&fake);
+    let a = fake.add_file("a.txt", "a", None);
+    let b = fake.add_file("b.txt", "b", Some("b"));
+
+    let urls = api
+        .get_file_urls(vec![a.key.clone()], KeyType::FileKey)
+        .await
+        .unwrap();
+    assert_eq!(urls.data.len(), 1);
+    assert_eq!(urls.data[0].key, a.key);
+    assert_eq!(urls.data[0].url, format!("{}/f/{}", fake.url(), a.key));
+
+    let urls = api
+        .get_file_urls(vec!["b".to_string()], KeyType::CustomId)
+        .await
+        .unwrap();
+    assert_eq!(urls.data[0].key, b.key);
+
+    // The URL serves the contents of the file.
+    let body = reqwest::get(&urls.data[0].url).await.unwrap();
+    assert_eq!(body.text().await.unwrap(), "b");
+    assert_eq!(fake.request_count(Endpoint::File), 1);
+}
+
+#[tokio::test]
+async fn renames_files_by_key_and_by_custom_id() {
+    let fake = FakeUploadThing::start().unwrap();
+    let api = fast_client(&fake);
+    let a = fake.add_file("a.txt", "a", None);
+    let b = fake.add_file("b.txt", "b", Some("b"));
+
+    api.rename_files(RenameFilesOpts {
+        updates: vec![
+            SingleFileRename::new(a.key.clone(), "renamed-a.txt"),
+            SingleFileRename::by_custom_id("b", "renamed-b.txt"),
+        ],
+    })
+    .await
+    .unwrap();
+
+    assert_eq!(fake.file(&a.key).unwrap().name, "renamed-a.txt");
+    assert_eq!(fake.file(&b.key).unwrap().name, "renamed-b.txt");
+}
+
+#[tokio::test]
+async fn reports_usage() {
+    let fake = FakeUploadThing::start().unwrap();
+    let api = fast_client(&fake);
+    fake.add_file("a.txt", "abc", None);
+    fake.add_file("b.txt", "de", None);
+
+    let usage = api.get_usage_info().await.unwrap();
+    assert_eq!(usage.total_bytes, 5);
+    assert_eq!(usage.files_uploaded, 2);
+    assert!(usage.limit_bytes > 0.0);
+}
+
+#[tokio::test]
+async fn requests_signed_file_urls() {
+    let fake = FakeUploadThing::start().unwrap();
+    let api = fast_client(&fake);
+    let file = fake.add_file("secret.txt", "top secret", Some("secret"));
+
+    let signed = api
+        .get_presigned_url(
+            PresignedUrlOpts::by_custom_id("secret").with_expires_in(Expiry::from_secs(300)),
+        )
+        .await
+        .unwrap();
+    assert_eq!(signed.key, "secret");
+    assert!(signed.url.contains(&file.key));
+    assert!(signed.expires_within(Duration::from_secs(301)));
+    assert!(!signed.expires_within(Duration::from_secs(200)));
+    assert!(signed.expires_within(Duration::MAX));
+
+    let response = reqwest::get(&signed.url).await.unwrap();
+    assert_eq!(response.text().await.unwrap(), "top secret");
+
+    // The fake server checks the signature of the URL.
+    let tampered = signed
+        .url
+        .replace("signature=hmac-sha256%3D", "signature=hmac-sha256%3D0");
+    let response = reqwest::get(&tampered).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let missing = api
+        .get_presigned_url(PresignedUrlOpts::new("missing"))
+        .await
+        .unwrap_err();
+    assert_eq!(missing.status(), Some(StatusCode::NOT_FOUND));
+}
+
+#[tokio::test]
+async fn rejects_requests_with_another_api_key() {
+    let fake = FakeUploadThing::start().unwrap();
+    let mut config = fake.config();
+    config.api_key = Some("sk_not_the_fake_key".parse().unwrap());
+    let api = utapi_rs::UtApi::from_config(config);
+
+    let error = api.list_files(None).await.unwrap_err();
+    assert_eq!(error.status(), Some(StatusCode::UNAUTHORIZED));
+}
+
+#[tokio::test]
+async fn injected_failures_are_retried_and_counted() {
+    let fake = FakeUploadThing::start().unwrap();
+    let api = fast_client(&fake);
+    fake.add_file("a.txt", "a", None);
+
+    fake.fail_next(Endpoint::ListFiles, StatusCode::SERVICE_UNAVAILABLE, 2);
+    let page = api.list_files(None).await.unwrap();
+    assert_eq!(page.files.len(), 1);
+    assert_eq!(fake.request_count(Endpoint::ListFiles), 3);
+
+    // A status the retry policy does not cover fails right away.
+    fake.fail_next(Endpoint::ListFiles, StatusCode::BAD_REQUEST, 1);
+    let error = api.list_files(None).await.unwrap_err();
+    assert_eq!(error.status(), Some(StatusCode::BAD_REQUEST));
+    assert_eq!(fake.request_count(Endpoint::ListFiles), 4);
+}
+
+#[tokio::test]
+async fn injected_failures_of_presigned_posts_fail_the_file() {
+    let fake = FakeUploadThing::start().unwrap();
+    let api = fast_client(&fake);
+
+    fake.fail_next(Endpoint::PresignedPost, StatusCode::FORBIDDEN, 1);
+    let file = FileObj::from_bytes("a.txt", "a");
+    let report = api.upload_files(vec![file], None, true).await.unwrap();
+
+    let error = report.results[0].result.as_ref().unwrap_err();
+    assert_eq!(error.status(), Some(StatusCode::FORBIDDEN));
+    assert!(!fake.files()[0].uploaded);
+    assert_eq!(fake.request_count(Endpoint::PollUpload), 0);
+}
+
+#[tokio::test]
+async fn latency_delays_requests() {
+    let fake = FakeUploadThing::start().unwrap();
+    let api = fast_client(&fake);
+
+    fake.set_latency(Endpoint::GetUsageInfo, Duration::from_millis(200));
+    let started = Instant::now();
+    api.get_usage_info().await.unwrap();
+    assert!(started.elapsed() >= Duration::from_millis(200));
+
+    // Other endpoints are not delayed, and a zero delay removes the latency.
+    fake.set_latency(Endpoint::GetUsageInfo, Duration::ZERO);
+    let started = Instant::now();
+    api.get_usage_info().await.unwrap();
+    api.list_files(None).await.unwrap();
+    assert!(started.elapsed() < Duration::from_millis(200));
+}
+
+#[tokio::test]
+async fn registers_files_again_only_if_the_request_was_not_processed() {
+    let fake = FakeUploadThing::start().unwrap();
+    let api = fast_client(&fake);
+
+    // A 503 may have been sent after the files were registered, so it is not retried.
+    fake.fail_next(Endpoint::UploadFiles, StatusCode::SERVICE_UNAVAILABLE, 1);
+    let file = FileObj::from_bytes("a.txt", "a");
+    let report = api.upload_files(vec![file], None, false).await.unwrap();
+    let error = report.results[0].result.as_ref().unwrap_err();
+    assert_eq!(error.status(), Some(StatusCode::SERVICE_UNAVAILABLE));
+    assert_eq!(fake.request_count(Endpoint::UploadFiles), 1);
+
+    // A 429 means the request was turned away, so it is.
+    fake.fail_next(Endpoint::UploadFiles, StatusCode::TOO_MANY_REQUESTS, 1);
+    let file = FileObj::from_bytes("a.txt", "a");
+    let report = api.upload_files(vec![file], None, false).await.unwrap();
+    assert!(report.is_success());
+    assert_eq!(fake.request_count(Endpoint::UploadFiles), 3);
+    assert_eq!(fake.files().len(), 1);
+}
@@ -0,0 +1,105 @@
+//! The blocking client, called from plain `#[test]` functions against a
+//! `FakeUploadThing` that runs on a runtime of its own.
+
+mod common;
+
+use common::{fast_config, pattern};
+use std::collections::HashSet;
+use tokio::runtime::Runtime;
+use utapi_rs::blocking::UtApi;
+use utapi_rs::models::{FileObj, ListAllFilesOpts, ListFilesOpts};
+use utapi_rs::testing::{Endpoint, FakeUploadThing};
+
+/// A fake server and the runtime it is served from.
+///
+/// The blocking client cannot be called from within a runtime, so the server runs on
+/// the worker threads of a separate one while the test thread stays outside of it.
+struct BlockingFake {
+    fake: FakeUploadThing,
+    _runtime: Runtime,
+}
+
+impl BlockingFake {
+    fn start() -> BlockingFake {
+        let runtime = Runtime::new().unwrap();
+        let fake = {
+            let _guard = runtime.enter();
+            FakeUploadThing::start().unwrap()
+        };
+        BlockingFake {
+            fake,
+            _runtime: runtime,
+        }
+    }
+
+    fn client(&self) -> UtApi {
+        UtApi::from_config(fast_config(&self.fake))
+    }
+}
+
+#[test]
+fn lists_files() {
+    let server = BlockingFake::start();
+    let api = server.client();
+    for i in 0..3 {
+        server.fake.add_file(format!("{}.txt", i), "x", None);
+    }
+
+    let page = api
+        .list_files(Some(ListFilesOpts {
+            limit: Some(2),
+            offset: Some(1),
+        }))
+        .unwrap();
+
+    let names: Vec<&str> = page.files.iter().map(|file| file.name.as_str()).collect();
+    assert_eq!(names, ["1.txt", "2.txt"]);
+    assert_eq!(page.has_more, Some(false));
+}
+
+#[test]
+fn iterates_over_all_files() {
+    let server = BlockingFake::start();
+    let api = server.client();
+    let keys: Vec<String> = (0..7)
+        .map(|i| server.fake.add_file(format!("{}.txt", i), "x", None).key)
+        .collect();
+
+    for prefetch in [false, true] {
+        let opts = ListAllFilesOpts {
+            page_size: 3,
+            prefetch,
+        };
+        let listed: Vec<String> = api
+            .list_all_files(Some(opts))
+            .map(|file| file.unwrap().key)
+            .collect();
+        assert_eq!(listed, keys);
+    }
+    assert_eq!(server.fake.request_count(Endpoint::ListFiles), 6);
+}
+
+#[test]
+fn uploads_files() {
+    let server = BlockingFake::start();
+    let api = server.client();
+
+    let files = vec![
+        FileObj::from_bytes("a.txt", "a"),
+        FileObj::from_bytes("large.bin", pattern(200_000)),
+    ];
+    let report = api.upload_files(files, None, true).unwrap();
+
+    assert!(report.is_success());
+    let keys: HashSet<String> = report
+        .successes()
+        .map(|upload| upload.key.clone())
+        .collect();
+    let stored = server.fake.files();
+    assert_eq!(stored.len(), 2);
+    assert!(stored
+        .iter()
+        .all(|file| file.uploaded && keys.contains(&file.key)));
+    assert_eq!(stored[1].content, pattern(200_000));
+    assert_eq!(server.fake.request_count(Endpoint::PollUpload), 2);
+}
@@ -0,0 +1,143 @@
+//! Cancelling uploads through `UploadFileOpts::cancellation_token`, with latency injected
+//! into `FakeUploadThing` so that the token is cancelled while requests are in flight.
+
+mod common;
+
+use common::{fast_client, fast_config, pattern};
+use reqwest::StatusCode;
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+use utapi_rs::models::{FileObj, UploadFileOpts};
+use utapi_rs::testing::{Endpoint, FakeUploadThing};
+use utapi_rs::{UtApi, UtApiError};
+
+/// Longer than any test waits, so that a request with this latency only ends if cancelled.
+const STALL: Duration = Duration::from_secs(30);
+
+/// Upload options that cancel the upload after `delay`.
+fn cancel_after(delay: Duration) -> UploadFileOpts {
+    let token = CancellationToken::new();
+    let cancel = token.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(delay).await;
+        cancel.cancel();
+    });
+    UploadFileOpts {
+        cancellation_token: Some(token),
+        ..UploadFileOpts::default()
+    }
+}
+
+fn assert_cancelled(result: &Result<utapi_rs::models::FileUpload, UtApiError>) {
+    assert!(
+        matches!(result, Err(UtApiError::Cancelled)),
+        "expected a cancelled upload, got {:?}",
+        result
+    );
+}
+
+#[tokio::test]
+async fn stops_an_upload_in_flight() {
+    let fake = FakeUploadThing::start().unwrap();
+    let api = fast_client(&fake);
+    fake.set_latency(Endpoint::PresignedPost, STALL);
+
+    let started = Instant::now();
+    let files = vec![
+        FileObj::from_bytes("a.txt", "a"),
+        FileObj::from_bytes("b.txt", "b"),
+    ];
+    let opts = cancel_after(Duration::from_millis(100));
+    let report = api.upload_files(files, Some(opts), true).await.unwrap();
+
+    assert!(started.elapsed() < Duration::from_secs(5));
+    assert_eq!(report.results.len(), 2);
+    for result in &report.results {
+        assert_cancelled(&result.result);
+    }
+    assert!(fake.files().iter().all(|file| !file.uploaded));
+    assert_eq!(fake.request_count(Endpoint::PollUpload), 0);
+}
+
+#[tokio::test]
+async fn stops_waiting_to_retry() {
+    let fake = FakeUploadThing::start().unwrap();
+    let mut config = fast_config(&fake);
+    config.retry_policy.base_backoff = STALL;
+    config.retry_policy.max_backoff = STALL;
+    let api = UtApi::from_config(config);
+    fake.fail_next(Endpoint::PresignedPost, StatusCode::SERVICE_UNAVAILABLE, 5);
+
+    let started = Instant::now();
+    let file = FileObj::from_bytes("a.txt", "a");
+    let opts = cancel_after(Duration::from_millis(100));
+    let report = api
+        .upload_files(vec![file], Some(opts), false)
+        .await
+        .unwrap();
+
+    assert!(started.elapsed() < Duration::from_secs(5));
+    assert_cancelled(&report.results[0].result);
+    assert_eq!(fake.request_count(Endpoint::PresignedPost), 1);
+}
+
+#[tokio::test]
+async fn stops_polling() {
+    let fake = FakeUploadThing::start().unwrap();
+    let api = fast_client(&fake);
+    fake.set_latency(Endpoint::PollUpload, STALL);
+
+    let started = Instant::now();
+    let file = FileObj::from_bytes("a.txt", "a");
+    let opts = cancel_after(Duration::from_millis(100));
+    let report = api
+        .upload_files(vec![file], Some(opts), true)
+        .await
+        .unwrap();
+
+    assert!(started.elapsed() < Duration::from_secs(5));
+    assert_cancelled(&report.results[0].result);
+    // The contents were uploaded before polling was cancelled.
+    assert!(fake.files()[0].uploaded);
+    assert_eq!(fake.request_count(Endpoint::PollUpload), 1);
+}
+
+#[tokio::test]
+async fn aborts_a_multipart_upload() {
+    let fake = FakeUploadThing::start().unwrap();
+    fake.enable_multipart(1000, 400);
+    let api = fast_client(&fake);
+    fake.set_latency(Endpoint::UploadPart, STALL);
+
+    let started = Instant::now();
+    let file = FileObj::from_bytes("large.bin", pattern(1000));
+    let opts = cancel_after(Duration::from_millis(100));
+    let report = api
+        .upload_files(vec![file], Some(opts), true)
+        .await
+        .unwrap();
+
+    assert!(started.elapsed() < Duration::from_secs(5));
+    assert_cancelled(&report.results[0].result);
+    assert!(fake.files()[0].failed);
+    assert_eq!(fake.request_count(Endpoint::FailureCallback), 1);
+    assert_eq!(fake.request_count(Endpoint::CompleteMultipart), 0);
+}
+
+#[tokio::test]
+async fn starts_nothing_once_cancelled() {
+    let fake = FakeUploadThing::start().unwrap();
+    let api = fast_client(&fake);
+    fake.set_latency(Endpoint::UploadFiles, Duration::from_millis(200));
+
+    // The token is cancelled while the files are being registered.
+    let file = FileObj::from_bytes("a.txt", "a");
+    let opts = cancel_after(Duration::from_millis(50));
+    let report = api
+        .upload_files(vec![file], Some(opts), true)
+        .await
+        .unwrap();
+
+    assert_cancelled(&report.results[0].result);
+    assert_eq!(fake.request_count(Endpoint::PresignedPost), 0);
+}
@@ -0,0 +1,187 @@
+//! The `utapi` binary, run against a `FakeUploadThing`.
+
+mod common;
+
+use common::{fast_client, temp_file};
+use reqwest::StatusCode;
+use serde_json::Value;
+use std::process::{Command, Output};
+use tokio::runtime::Runtime;
+use utapi_rs::models::FileObj;
+use utapi_rs::testing::{Endpoint, FakeUploadThing, FAKE_API_KEY};
+
+/// A fake server on a runtime of its own, so that the test thread can wait for the binary.
+struct CliFake {
+    fake: FakeUploadThing,
+    runtime: Runtime,
+}
+
+impl CliFake {
+    fn start() -> CliFake {
+        let runtime = Runtime::new().unwrap();
+        let fake = {
+            let _guard = runtime.enter();
+            FakeUploadThing::start().unwrap()
+        };
+        CliFake { fake, runtime }
+    }
+
+    /// Runs `utapi` with `args` against the fake, without reading credentials from the
+    /// environment.
+    fn run(&self, args: &[&str]) -> Output {
+        Command::new(env!("CARGO_BIN_EXE_utapi"))
+            .args(["--api-key", FAKE_API_KEY, "--host", &self.fake.url()])
+            .args(args)
+            .env_remove("UPLOADTHING_TOKEN")
+            .env_remove("UPLOADTHING_SECRET")
+            .output()
+            .unwrap()
+    }
+
+    /// Runs `utapi` with `args` and `--json`, and returns the JSON it printed.
+    fn run_json(&self, args: &[&str]) -> Value {
+        let output = self.run(&[args, &["--json"]].concat());
+        assert!(output.status.success(), "{:?}", output);
+        serde_json::from_slice(&output.stdout).unwrap()
+    }
+
+    /// Registers a file whose upload never arrives, so that it stays `Uploading`.
+    fn add_pending_file(&self, name: &str) {
+        let api = fast_client(&self.fake);
+        self.fake
+            .fail_next(Endpoint::PresignedPost, StatusCode::FORBIDDEN, 1);
+        let file = FileObj::from_bytes(name, "x");
+        self.runtime
+            .block_on(api.upload_files(vec![file], None, false))
+            .unwrap();
+    }
+}
+
+/// The names of the files in the JSON output of `ls`.
+fn names(listing: &Value) -> Vec<&str> {
+    listing["files"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|file| file["name"].as_str().unwrap())
+        .collect()
+}
+
+#[test]
+fn lists_files_as_json() {
+    let server = CliFake::start();
+    let file = server.fake.add_file("a.txt", "a", Some("first"));
+    server.fake.add_file("b.txt", "bb", None);
+
+    let listing = server.run_json(&["ls"]);
+
+    assert_eq!(names(&listing), ["a.txt", "b.txt"]);
+    assert_eq!(listing["hasMore"], false);
+    let first = &listing["files"][0];
+    assert_eq!(first["key"], file.key.as_str());
+    assert_eq!(first["customId"], "first");
+    assert_eq!(first["size"], 1);
+    assert_eq!(first["status"], "Uploaded");
+}
+
+#[test]
+fn pages_until_limit_files_match_the_status() {
+    let server = CliFake::start();
+    server.fake.add_file("u0", "x", None);
+    server.add_pending_file("p1");
+    server.fake.add_file("u2", "x", None);
+    server.add_pending_file("p3");
+    server.add_pending_file("p4");
+    server.fake.add_file("u5", "x", None);
+    server.fake.add_file("u6", "x", None);
+
+    let args = ["ls", "--limit", "2", "--status", "uploaded"];
+    let listing = server.run_json(&args);
+    assert_eq!(names(&listing), ["u0", "u2"]);
+    assert_eq!(listing["hasMore"], true);
+    assert_eq!(listing["nextOffset"], 3);
+
+    let listing = server.run_json(&[&args[..], &["--offset", "3"]].concat());
+    assert_eq!(names(&listing), ["u5", "u6"]);
+    assert_eq!(listing["hasMore"], false);
+    assert_eq!(listing["nextOffset"], Value::Null);
+
+    let listing = server.run_json(&["ls", "--all", "--status", "uploading"]);
+    assert_eq!(names(&listing), ["p1", "p3", "p4"]);
+}
+
+#[test]
+fn prints_where_the_next_page_starts() {
+    let server = CliFake::start();
+    for name in ["a.txt", "b.txt", "c.txt"] {
+        server.fake.add_file(name, "x", None);
+    }
+
+    let output = server.run(&["ls", "--limit", "2"]);
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout.lines().count(), 2);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("use --offset 2 or --all"), "{}", stderr);
+}
+
+#[test]
+fn uploads_files_as_json() {
+    let server = CliFake::start();
+    let path = temp_file("cli-upload.txt", b"from the command line");
+
+    let results = server.run_json(&["upload", path.to_str().unwrap(), "--metadata", "owner=ops"]);
+
+    let key = results[0]["key"].as_str().unwrap();
+    assert_eq!(results[0]["name"], "cli-upload.txt");
+    let stored = server.fake.file(key).unwrap();
+    assert!(stored.uploaded);
+    assert_eq!(stored.content, "from the command line");
+}
+
+#[test]
+fn fails_with_a_rejected_api_key() {
+    let server = CliFake::start();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_utapi"))
+        .args([
+            "--api-key",
+            "sk_wrong",
+            "--host",
+            &server.fake.url(),
+            "usage",
+        ])
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.starts_with("error:"), "{}", stderr);
+}
+
+#[test]
+fn refuses_to_sign_a_custom_id_locally() {
+    let server = CliFake::start();
+
+    let output = server.run(&["sign", "my-id", "--local", "--custom-id"]);
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.starts_with("error: --local"), "{}", stderr);
+}
+
+#[test]
+fn rejects_out_of_range_paging_arguments() {
+    let server = CliFake::start();
+
+    for args in [
+        &["ls", "--limit", "0"][..],
+        &["ls", "--offset", "-1"],
+        &["ls", "--all", "--page-size", "0"],
+    ] {
+        let output = server.run(args);
+        assert!(!output.status.success(), "{:?}", args);
+        assert_eq!(output.status.code(), Some(2), "{:?}", args);
+    }
+}
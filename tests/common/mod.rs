@@ -0,0 +1,47 @@
+//! Helpers shared by the integration tests that run against `FakeUploadThing`.
+
+#![allow(dead_code)]
+
+use std::path::PathBuf;
+use std::time::Duration;
+use utapi_rs::config::UploadthingConfig;
+use utapi_rs::retry::{Jitter, RetryPolicy};
+use utapi_rs::testing::FakeUploadThing;
+use utapi_rs::UtApi;
+
+/// A retry policy with the default attempts and status codes that retries right away.
+pub fn fast_retry_policy() -> RetryPolicy {
+    RetryPolicy {
+        base_backoff: Duration::from_millis(1),
+        max_backoff: Duration::from_millis(10),
+        jitter: Jitter::None,
+        ..RetryPolicy::default()
+    }
+}
+
+/// A configuration for `fake` that retries quickly.
+pub fn fast_config(fake: &FakeUploadThing) -> UploadthingConfig {
+    let mut config = fake.config();
+    config.retry_policy = fast_retry_policy();
+    config
+}
+
+/// A client for `fake` that retries quickly.
+pub fn fast_client(fake: &FakeUploadThing) -> UtApi {
+    UtApi::from_config(fast_config(fake))
+}
+
+/// Contents that differ from one byte to the next, so that misplaced bytes are noticed.
+pub fn pattern(len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i % 251) as u8).collect()
+}
+
+/// Writes `contents` to a file named `name` in a temporary directory of this test run and
+/// returns its path.
+pub fn temp_file(name: &str, contents: &[u8]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("utapi-rs-tests-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(name);
+    std::fs::write(&path, contents).unwrap();
+    path
+}
@@ -0,0 +1,95 @@
+//! Uploading remote files with `UtApi::upload_files_from_url`, fetched from the file
+//! URLs that `FakeUploadThing` serves.
+
+mod common;
+
+use common::{fast_client, fast_config, pattern};
+use reqwest::StatusCode;
+use utapi_rs::models::UrlUpload;
+use utapi_rs::testing::{Endpoint, FakeUploadThing};
+use utapi_rs::{UtApi, UtApiError};
+
+#[tokio::test]
+async fn uploads_the_body_of_each_url() {
+    let fake = FakeUploadThing::start().unwrap();
+    let api = fast_client(&fake);
+    let source = fake.add_file("logo.png", pattern(100_000), None);
+    let url = format!("{}/f/{}", fake.url(), source.key);
+
+    let urls = vec![
+        UrlUpload::new(url.as_str()).with_name("renamed.png"),
+        UrlUpload::from(url.as_str()),
+    ];
+    let report = api.upload_files_from_url(urls, None, true).await.unwrap();
+    let uploads: Vec<_> = report.successes().collect();
+    assert_eq!(uploads.len(), 2);
+
+    let renamed = fake.file(&uploads[0].key).unwrap();
+    assert_eq!(renamed.name, "renamed.png");
+    assert_eq!(renamed.content, pattern(100_000));
+    assert_eq!(renamed.content_type, "image/png");
+    assert_eq!(uploads[0].size, 100_000);
+
+    // Without a name, the last segment of the URL path is used.
+    let unnamed = fake.file(&uploads[1].key).unwrap();
+    assert_eq!(unnamed.name, source.key);
+    assert_eq!(unnamed.content_type, "image/png");
+    assert_eq!(fake.request_count(Endpoint::File), 2);
+}
+
+#[tokio::test]
+async fn fails_only_the_urls_that_cannot_be_fetched() {
+    let fake = FakeUploadThing::start().unwrap();
+    let mut config = fast_config(&fake);
+    config.upload_batch_size = 2;
+    let api = UtApi::from_config(config);
+    let source = fake.add_file("a.txt", "a", None);
+    let missing = format!("{}/f/missing", fake.url());
+    // Nothing listens on port 9 of the loopback interface.
+    let unreachable = "http://127.0.0.1:9/file.txt";
+
+    let urls = vec![
+        UrlUpload::from(missing.as_str()),
+        UrlUpload::new(format!("{}/f/{}", fake.url(), source.key)).with_name("copy.txt"),
+        UrlUpload::from(unreachable),
+    ];
+    let report = api.upload_files_from_url(urls, None, false).await.unwrap();
+
+    match &report.results[0].result {
+        Err(UtApiError::Fetch { url, status }) => {
+            assert_eq!(url, &missing);
+            assert_eq!(*status, StatusCode::NOT_FOUND);
+        }
+        other => panic!("expected a failed fetch, got {:?}", other),
+    }
+    assert_eq!(report.results[0].name, missing);
+    let copy = report.results[1].result.as_ref().unwrap();
+    assert_eq!(fake.file(&copy.key).unwrap().content, "a");
+    assert!(matches!(
+        report.results[2].result,
+        Err(UtApiError::Transport(_))
+    ));
+    assert_eq!(report.results[2].name, unreachable);
+
+    // Only the URL that could be fetched was registered.
+    assert_eq!(fake.request_count(Endpoint::UploadFiles), 1);
+    assert_eq!(fake.files().len(), 2);
+}
+
+#[tokio::test]
+async fn reports_a_rejected_batch_of_urls() {
+    let fake = FakeUploadThing::start().unwrap();
+    let api = fast_client(&fake);
+    let source = fake.add_file("a.txt", "a", None);
+    fake.fail_next(Endpoint::UploadFiles, StatusCode::BAD_REQUEST, 1);
+
+    let url = UrlUpload::new(format!("{}/f/{}", fake.url(), source.key)).with_name("copy.txt");
+    let report = api
+        .upload_files_from_url(vec![url], None, false)
+        .await
+        .unwrap();
+
+    let error = report.results[0].result.as_ref().unwrap_err();
+    assert!(matches!(error, UtApiError::BatchRejected(_)), "{:?}", error);
+    assert_eq!(report.results[0].name, "copy.txt");
+}
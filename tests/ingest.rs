@@ -0,0 +1,78 @@
+//! Uploads through `UploadProtocol::Ingest` against the fake ingest server of
+//! `FakeUploadThing`.
+
+use std::time::Duration;
+use utapi_rs::config::{UploadProtocol, UploadthingConfig};
+use utapi_rs::models::FileObj;
+use utapi_rs::retry::{Jitter, RetryPolicy};
+use utapi_rs::testing::{Endpoint, FakeUploadThing};
+use utapi_rs::UtApi;
+
+/// A configuration for `fake` that uploads with the ingest protocol and retries quickly.
+fn ingest_config(fake: &FakeUploadThing) -> UploadthingConfig {
+    let mut config = fake.config();
+    config.upload_protocol = UploadProtocol::Ingest;
+    config.retry_policy = RetryPolicy {
+        base_backoff: Duration::from_millis(1),
+        jitter: Jitter::None,
+        ..RetryPolicy::default()
+    };
+    config
+}
+
+#[tokio::test]
+async fn uploads_through_ingest() {
+    let fake = FakeUploadThing::start().unwrap();
+    let api = UtApi::from_config(ingest_config(&fake));
+
+    let file = FileObj::from_bytes("hello world.txt", "hello world").with_custom_id("greeting");
+    let report = api.upload_files(vec![file], None, true).await.unwrap();
+    let upload = report.results[0].result.as_ref().unwrap();
+
+    let stored = fake.file(&upload.key).unwrap();
+    assert!(stored.uploaded);
+    assert_eq!(stored.content, "hello world");
+    assert_eq!(stored.name, "hello world.txt");
+    assert_eq!(stored.content_type, "text/plain");
+    assert_eq!(stored.custom_id.as_deref(), Some("greeting"));
+    assert_eq!(upload.url, format!("{}/f/{}", fake.url(), upload.key));
+
+    // The URL is signed locally, so the API is never asked to register the file.
+    assert_eq!(fake.request_count(Endpoint::UploadFiles), 0);
+    assert_eq!(fake.request_count(Endpoint::IngestUpload), 1);
+    assert_eq!(fake.request_count(Endpoint::IngestRangeStart), 0);
+}
+
+#[tokio::test]
+async fn resumes_an_interrupted_ingest_upload() {
+    let fake = FakeUploadThing::start().unwrap();
+    let api = UtApi::from_config(ingest_config(&fake));
+    let content: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+
+    fake.interrupt_next_ingest(40_000);
+    let file = FileObj::from_bytes("large.bin", content.clone());
+    let report = api.upload_files(vec![file], None, false).await.unwrap();
+    let upload = report.results[0].result.as_ref().unwrap();
+
+    let stored = fake.file(&upload.key).unwrap();
+    assert!(stored.uploaded);
+    assert_eq!(stored.content, content);
+    // The retry asked where the upload left off and only sent the rest.
+    assert_eq!(fake.request_count(Endpoint::IngestUpload), 2);
+    assert_eq!(fake.request_count(Endpoint::IngestRangeStart), 1);
+}
+
+#[tokio::test]
+async fn rejects_ingest_urls_signed_with_another_key() {
+    let fake = FakeUploadThing::start().unwrap();
+    let mut config = ingest_config(&fake);
+    config.api_key = Some("sk_not_the_fake_key".parse().unwrap());
+    let api = UtApi::from_config(config);
+
+    let file = FileObj::from_bytes("hello.txt", "hello");
+    let report = api.upload_files(vec![file], None, false).await.unwrap();
+
+    let error = report.results[0].result.as_ref().unwrap_err();
+    assert_eq!(error.status().map(|status| status.as_u16()), Some(403));
+    assert!(fake.files().is_empty());
+}
@@ -0,0 +1,126 @@
+//! Listing every file with `UtApi::list_all_files` against `FakeUploadThing`.
+
+mod common;
+
+use common::fast_client;
+use futures::{StreamExt, TryStreamExt};
+use reqwest::StatusCode;
+use std::time::Duration;
+use utapi_rs::models::ListAllFilesOpts;
+use utapi_rs::testing::{Endpoint, FakeUploadThing};
+use utapi_rs::UtApiError;
+
+/// Adds `count` files to `fake` and returns their keys, in the order they are listed.
+fn add_files(fake: &FakeUploadThing, count: usize) -> Vec<String> {
+    (0..count)
+        .map(|i| fake.add_file(format!("{}.txt", i), "x", None).key)
+        .collect()
+}
+
+fn pages_of(page_size: i32) -> Option<ListAllFilesOpts> {
+    Some(ListAllFilesOpts {
+        page_size,
+        ..ListAllFilesOpts::default()
+    })
+}
+
+#[tokio::test]
+async fn lists_every_file_a_page_at_a_time() {
+    let fake = FakeUploadThing::start().unwrap();
+    let api = fast_client(&fake);
+    let keys = add_files(&fake, 7);
+
+    let files: Vec<_> = api.list_all_files(pages_of(3)).try_collect().await.unwrap();
+
+    let listed: Vec<String> = files.into_iter().map(|file| file.key).collect();
+    assert_eq!(listed, keys);
+    assert_eq!(fake.request_count(Endpoint::ListFiles), 3);
+}
+
+#[tokio::test]
+async fn stops_when_has_more_is_false() {
+    let fake = FakeUploadThing::start().unwrap();
+    let api = fast_client(&fake);
+    add_files(&fake, 6);
+
+    let files: Vec<_> = api.list_all_files(pages_of(3)).try_collect().await.unwrap();
+
+    // The second page is full, but says that it is the last one.
+    assert_eq!(files.len(), 6);
+    assert_eq!(fake.request_count(Endpoint::ListFiles), 2);
+}
+
+#[tokio::test]
+async fn stops_after_a_short_page_without_has_more() {
+    let fake = FakeUploadThing::start().unwrap();
+    let api = fast_client(&fake);
+    fake.hide_has_more(true);
+    add_files(&fake, 7);
+
+    let files: Vec<_> = api.list_all_files(pages_of(3)).try_collect().await.unwrap();
+    assert_eq!(files.len(), 7);
+    assert_eq!(fake.request_count(Endpoint::ListFiles), 3);
+
+    // A full last page can only be told apart by the empty page that follows it.
+    fake.add_file("7.txt", "x", None);
+    fake.add_file("8.txt", "x", None);
+    let files: Vec<_> = api.list_all_files(pages_of(3)).try_collect().await.unwrap();
+    assert_eq!(files.len(), 9);
+    assert_eq!(fake.request_count(Endpoint::ListFiles), 3 + 4);
+}
+
+#[tokio::test]
+async fn requests_the_next_page_while_the_current_one_is_consumed() {
+    let fake = FakeUploadThing::start().unwrap();
+    let api = fast_client(&fake);
+    let keys = add_files(&fake, 7);
+    fake.set_latency(Endpoint::ListFiles, Duration::from_millis(200));
+
+    let opts = ListAllFilesOpts {
+        page_size: 3,
+        prefetch: true,
+    };
+    let mut files = api.list_all_files(Some(opts));
+    let first = files.try_next().await.unwrap().unwrap();
+    assert_eq!(first.key, keys[0]);
+
+    // The second page was requested as soon as the first one arrived.
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(fake.request_count(Endpoint::ListFiles), 2);
+
+    let rest: Vec<_> = files.try_collect().await.unwrap();
+    assert_eq!(rest.len(), 6);
+    // Nothing is prefetched after the last page.
+    assert_eq!(fake.request_count(Endpoint::ListFiles), 3);
+}
+
+#[tokio::test]
+async fn does_not_request_the_next_page_without_prefetch() {
+    let fake = FakeUploadThing::start().unwrap();
+    let api = fast_client(&fake);
+    add_files(&fake, 7);
+
+    let mut files = api.list_all_files(pages_of(3));
+    files.try_next().await.unwrap().unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    assert_eq!(fake.request_count(Endpoint::ListFiles), 1);
+}
+
+#[tokio::test]
+async fn ends_after_the_first_error() {
+    let fake = FakeUploadThing::start().unwrap();
+    let api = fast_client(&fake);
+    add_files(&fake, 7);
+
+    let mut files = api.list_all_files(pages_of(3));
+    for _ in 0..3 {
+        files.try_next().await.unwrap().unwrap();
+    }
+
+    fake.fail_next(Endpoint::ListFiles, StatusCode::BAD_REQUEST, 1);
+    let error = files.next().await.unwrap().unwrap_err();
+    assert!(matches!(error, UtApiError::Api { .. }), "{:?}", error);
+    assert!(files.next().await.is_none());
+    assert_eq!(fake.request_count(Endpoint::ListFiles), 2);
+}
@@ -0,0 +1,106 @@
+//! Multipart uploads of large files against the multipart mode of `FakeUploadThing`.
+
+mod common;
+
+use common::{fast_client, pattern};
+use reqwest::StatusCode;
+use std::io::Cursor;
+use utapi_rs::models::FileObj;
+use utapi_rs::testing::{Endpoint, FakeUploadThing};
+use utapi_rs::UtApiError;
+
+#[tokio::test]
+async fn uploads_large_files_in_parts() {
+    let fake = FakeUploadThing::start().unwrap();
+    fake.enable_multipart(1000, 400);
+    let api = fast_client(&fake);
+
+    let files = vec![
+        FileObj::from_bytes("large.bin", pattern(1000)),
+        FileObj::from_bytes("small.bin", pattern(999)),
+    ];
+    let report = api.upload_files(files, None, true).await.unwrap();
+    assert!(report.is_success());
+
+    let large = report.results[0].result.as_ref().unwrap();
+    let stored = fake.file(&large.key).unwrap();
+    assert!(stored.uploaded);
+    assert_eq!(stored.content, pattern(1000));
+
+    // Three parts of the large file, and a single presigned POST for the small one.
+    assert_eq!(fake.request_count(Endpoint::UploadPart), 3);
+    assert_eq!(fake.request_count(Endpoint::CompleteMultipart), 1);
+    assert_eq!(fake.request_count(Endpoint::PresignedPost), 1);
+    assert_eq!(fake.request_count(Endpoint::FailureCallback), 0);
+}
+
+#[tokio::test]
+async fn retries_failed_parts_individually() {
+    let fake = FakeUploadThing::start().unwrap();
+    fake.enable_multipart(1000, 400);
+    let api = fast_client(&fake);
+
+    fake.fail_next(Endpoint::UploadPart, StatusCode::SERVICE_UNAVAILABLE, 2);
+    let file = FileObj::from_bytes("large.bin", pattern(1000));
+    let report = api.upload_files(vec![file], None, false).await.unwrap();
+
+    let upload = report.results[0].result.as_ref().unwrap();
+    assert_eq!(fake.file(&upload.key).unwrap().content, pattern(1000));
+    assert_eq!(fake.request_count(Endpoint::UploadPart), 5);
+    assert_eq!(fake.request_count(Endpoint::CompleteMultipart), 1);
+    assert_eq!(fake.request_count(Endpoint::FailureCallback), 0);
+}
+
+#[tokio::test]
+async fn aborts_the_upload_when_a_part_fails() {
+    let fake = FakeUploadThing::start().unwrap();
+    fake.enable_multipart(1000, 400);
+    let api = fast_client(&fake);
+
+    fake.fail_next(Endpoint::UploadPart, StatusCode::FORBIDDEN, 1);
+    let file = FileObj::from_bytes("large.bin", pattern(1000));
+    let report = api.upload_files(vec![file], None, true).await.unwrap();
+
+    let error = report.results[0].result.as_ref().unwrap_err();
+    assert_eq!(error.status(), Some(StatusCode::FORBIDDEN));
+    let stored = &fake.files()[0];
+    assert!(stored.failed);
+    assert!(!stored.uploaded);
+    assert_eq!(fake.request_count(Endpoint::FailureCallback), 1);
+    assert_eq!(fake.request_count(Endpoint::CompleteMultipart), 0);
+    assert_eq!(fake.request_count(Endpoint::PollUpload), 0);
+}
+
+#[tokio::test]
+async fn aborts_the_upload_when_the_source_is_longer_than_its_size() {
+    let fake = FakeUploadThing::start().unwrap();
+    fake.enable_multipart(1000, 400);
+    let api = fast_client(&fake);
+
+    // Three parts hold 1200 bytes, so the last 100 bytes would have been dropped.
+    let file = FileObj::from_reader("large.bin", Cursor::new(pattern(1300)), 1000);
+    let report = api.upload_files(vec![file], None, false).await.unwrap();
+
+    let error = report.results[0].result.as_ref().unwrap_err();
+    assert!(matches!(error, UtApiError::InvalidInput(_)), "{:?}", error);
+    assert!(fake.files()[0].failed);
+    assert_eq!(fake.request_count(Endpoint::CompleteMultipart), 0);
+    assert_eq!(fake.request_count(Endpoint::FailureCallback), 1);
+}
+
+#[tokio::test]
+async fn aborts_the_upload_when_the_source_is_shorter_than_its_size() {
+    let fake = FakeUploadThing::start().unwrap();
+    fake.enable_multipart(1000, 400);
+    let api = fast_client(&fake);
+
+    // The source ends during the third part; no empty part is sent after it.
+    let file = FileObj::from_reader("large.bin", Cursor::new(pattern(700)), 1000);
+    let report = api.upload_files(vec![file], None, false).await.unwrap();
+
+    let error = report.results[0].result.as_ref().unwrap_err();
+    assert!(matches!(error, UtApiError::InvalidInput(_)), "{:?}", error);
+    assert!(fake.request_count(Endpoint::UploadPart) <= 2);
+    assert_eq!(fake.request_count(Endpoint::CompleteMultipart), 0);
+    assert_eq!(fake.request_count(Endpoint::FailureCallback), 1);
+}
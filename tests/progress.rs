@@ -0,0 +1,139 @@
+//! The `UploadEvent`s sent to `UploadFileOpts::progress` while uploading to
+//! `FakeUploadThing`.
+
+mod common;
+
+use common::{fast_client, pattern};
+use reqwest::StatusCode;
+use tokio::sync::mpsc;
+use utapi_rs::models::{FileObj, UploadEvent, UploadEventKind, UploadFileOpts};
+use utapi_rs::testing::{Endpoint, FakeUploadThing};
+use utapi_rs::UtApi;
+
+/// Uploads `files` and returns the kinds of the events received for each file, by index.
+async fn upload_with_progress(
+    api: &UtApi,
+    files: Vec<FileObj>,
+    wait_until_done: bool,
+) -> Vec<Vec<UploadEventKind>> {
+    let count = files.len();
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    let opts = UploadFileOpts {
+        progress: Some(sender),
+        ..UploadFileOpts::default()
+    };
+    api.upload_files(files, Some(opts), wait_until_done)
+        .await
+        .unwrap();
+
+    // The sender is dropped with the options once the upload returns.
+    let mut events = vec![Vec::new(); count];
+    while let Some(UploadEvent { index, kind, .. }) = receiver.recv().await {
+        events[index].push(kind);
+    }
+    events
+}
+
+/// Checks that the `BytesSent` events of a file count up to at most `total`, and returns
+/// the last count.
+fn assert_bytes_increase(events: &[UploadEventKind], total: u64) -> u64 {
+    let mut last = 0;
+    for event in events {
+        if let UploadEventKind::BytesSent { sent, total: size } = event {
+            assert_eq!(*size, total);
+            assert!(*sent > last, "{} bytes reported after {}", sent, last);
+            assert!(*sent <= total);
+            last = *sent;
+        }
+    }
+    last
+}
+
+#[tokio::test]
+async fn reports_each_step_of_an_upload_in_order() {
+    let fake = FakeUploadThing::start().unwrap();
+    let api = fast_client(&fake);
+
+    // Large enough to be sent in several chunks.
+    let files = vec![
+        FileObj::from_bytes("large.bin", pattern(200_000)),
+        FileObj::from_bytes("small.txt", "small"),
+    ];
+    let events = upload_with_progress(&api, files, true).await;
+
+    for (events, total) in events.iter().zip([200_000, 5]) {
+        assert!(
+            matches!(events[0], UploadEventKind::PresignReceived { .. }),
+            "{:?}",
+            events
+        );
+        let bytes = events
+            .iter()
+            .skip(1)
+            .take_while(|event| matches!(event, UploadEventKind::BytesSent { .. }))
+            .count();
+        assert!(bytes >= 1);
+        assert_eq!(assert_bytes_increase(events, total), total);
+        assert!(
+            matches!(
+                events[1 + bytes..],
+                [
+                    UploadEventKind::UploadFinished,
+                    UploadEventKind::PollingStarted,
+                    UploadEventKind::Done
+                ]
+            ),
+            "{:?}",
+            events
+        );
+    }
+    assert!(events[0].len() > events[1].len());
+}
+
+#[tokio::test]
+async fn reports_multipart_progress_in_order() {
+    let fake = FakeUploadThing::start().unwrap();
+    fake.enable_multipart(1000, 400);
+    let api = fast_client(&fake);
+
+    let file = FileObj::from_bytes("large.bin", pattern(1000));
+    let events = upload_with_progress(&api, vec![file], false).await;
+    let events = &events[0];
+
+    assert!(matches!(events[0], UploadEventKind::PresignReceived { .. }));
+    assert_eq!(assert_bytes_increase(events, 1000), 1000);
+    assert!(matches!(
+        events[events.len() - 2..],
+        [UploadEventKind::UploadFinished, UploadEventKind::Done]
+    ));
+}
+
+#[tokio::test]
+async fn ends_with_a_failure_event() {
+    let fake = FakeUploadThing::start().unwrap();
+    let api = fast_client(&fake);
+    fake.fail_next(Endpoint::PresignedPost, StatusCode::FORBIDDEN, 1);
+
+    let files = vec![
+        FileObj::from_bytes("rejected.txt", "rejected"),
+        FileObj::from_path("missing.txt", "/nonexistent/missing.txt"),
+    ];
+    let events = upload_with_progress(&api, files, true).await;
+
+    // The upload was rejected after the file was registered.
+    assert!(matches!(
+        events[0][0],
+        UploadEventKind::PresignReceived { .. }
+    ));
+    assert_bytes_increase(&events[0], 8);
+    assert!(matches!(
+        events[0].last(),
+        Some(UploadEventKind::Failed { .. })
+    ));
+    assert!(!events[0]
+        .iter()
+        .any(|event| matches!(event, UploadEventKind::UploadFinished)));
+
+    // The missing file was never registered.
+    assert!(matches!(events[1][..], [UploadEventKind::Failed { .. }]));
+}
@@ -0,0 +1,49 @@
+//! Rate-limited responses of `FakeUploadThing`, which carry `Retry-After` and
+//! `x-ratelimit-*` headers.
+
+mod common;
+
+use common::{fast_client, fast_config};
+use reqwest::StatusCode;
+use std::time::{Duration, Instant};
+use utapi_rs::testing::{Endpoint, FakeUploadThing};
+use utapi_rs::UtApi;
+
+#[tokio::test]
+async fn waits_as_long_as_the_server_asks_and_retries() {
+    let fake = FakeUploadThing::start().unwrap();
+    let api = fast_client(&fake);
+    assert!(api.rate_limit().is_none());
+
+    fake.fail_next(Endpoint::ListFiles, StatusCode::TOO_MANY_REQUESTS, 1);
+    let started = Instant::now();
+    api.list_files(None).await.unwrap();
+
+    // The fast retry policy would have retried after a millisecond.
+    assert!(started.elapsed() >= Duration::from_secs(1));
+    assert_eq!(fake.request_count(Endpoint::ListFiles), 2);
+
+    // The headers of the rejected attempt are remembered, and shared with clones.
+    let rate_limit = api.clone().rate_limit().unwrap();
+    assert_eq!(rate_limit.limit, Some(100));
+    assert_eq!(rate_limit.remaining, Some(0));
+    assert_eq!(rate_limit.retry_after, Some(Duration::from_secs(1)));
+    assert!(rate_limit.is_exhausted());
+}
+
+#[tokio::test]
+async fn gives_up_when_the_server_asks_to_wait_longer_than_max_retry_after() {
+    let fake = FakeUploadThing::start().unwrap();
+    let mut config = fast_config(&fake);
+    config.retry_policy.max_retry_after = Duration::from_millis(500);
+    let api = UtApi::from_config(config);
+
+    fake.fail_next(Endpoint::GetUsageInfo, StatusCode::TOO_MANY_REQUESTS, 1);
+    let started = Instant::now();
+    let error = api.get_usage_info().await.unwrap_err();
+
+    assert!(started.elapsed() < Duration::from_secs(1));
+    assert_eq!(error.status(), Some(StatusCode::TOO_MANY_REQUESTS));
+    assert_eq!(error.retry_after(), Some(Duration::from_secs(1)));
+    assert_eq!(fake.request_count(Endpoint::GetUsageInfo), 1);
+}
@@ -0,0 +1,152 @@
+//! Uploading each kind of `FileSource` to `FakeUploadThing`.
+
+mod common;
+
+use common::{fast_client, fast_config, pattern, temp_file};
+use reqwest::StatusCode;
+use std::io::Cursor;
+use utapi_rs::config::UploadProtocol;
+use utapi_rs::models::FileObj;
+use utapi_rs::testing::{Endpoint, FakeUploadThing};
+use utapi_rs::{UtApi, UtApiError};
+
+#[tokio::test]
+async fn streams_a_file_from_disk() {
+    let fake = FakeUploadThing::start().unwrap();
+    let api = fast_client(&fake);
+    let content = pattern(300_000);
+    let path = temp_file("photo.png", &content);
+
+    let file = FileObj::from_path("holiday", &path);
+    let report = api.upload_files(vec![file], None, true).await.unwrap();
+    let upload = report.results[0].result.as_ref().unwrap();
+
+    let stored = fake.file(&upload.key).unwrap();
+    assert_eq!(stored.content, content);
+    assert_eq!(stored.name, "holiday");
+    // The MIME type is guessed from the path, since the name has no extension.
+    assert_eq!(stored.content_type, "image/png");
+    assert_eq!(upload.size, 300_000);
+}
+
+#[tokio::test]
+async fn reads_a_file_from_disk_again_to_retry() {
+    let fake = FakeUploadThing::start().unwrap();
+    let api = fast_client(&fake);
+    let content = pattern(100_000);
+    let path = temp_file("retried.bin", &content);
+    fake.fail_next(Endpoint::PresignedPost, StatusCode::SERVICE_UNAVAILABLE, 1);
+
+    let file = FileObj::from_path("retried.bin", &path);
+    let report = api.upload_files(vec![file], None, false).await.unwrap();
+    let upload = report.results[0].result.as_ref().unwrap();
+
+    assert_eq!(fake.file(&upload.key).unwrap().content, content);
+    assert_eq!(fake.request_count(Endpoint::PresignedPost), 2);
+}
+
+#[tokio::test]
+async fn uploads_a_file_from_disk_in_parts() {
+    let fake = FakeUploadThing::start().unwrap();
+    fake.enable_multipart(100_000, 40_000);
+    let api = fast_client(&fake);
+    let content = pattern(100_000);
+    let path = temp_file("parts.bin", &content);
+
+    let file = FileObj::from_path("parts.bin", &path);
+    let report = api.upload_files(vec![file], None, true).await.unwrap();
+    let upload = report.results[0].result.as_ref().unwrap();
+
+    assert_eq!(fake.file(&upload.key).unwrap().content, content);
+    assert_eq!(fake.request_count(Endpoint::UploadPart), 3);
+}
+
+#[tokio::test]
+async fn uploads_bytes_and_vectors_from_memory() {
+    let fake = FakeUploadThing::start().unwrap();
+    let api = fast_client(&fake);
+
+    let files = vec![
+        FileObj::from_bytes("static.txt", bytes::Bytes::from_static(b"static")),
+        FileObj::from_bytes("vector.bin", pattern(1000)),
+    ];
+    let report = api.upload_files(files, None, true).await.unwrap();
+    let uploads: Vec<_> = report.successes().collect();
+
+    assert_eq!(uploads.len(), 2);
+    assert_eq!(fake.file(&uploads[0].key).unwrap().content, "static");
+    assert_eq!(
+        fake.file(&uploads[0].key).unwrap().content_type,
+        "text/plain"
+    );
+    assert_eq!(fake.file(&uploads[1].key).unwrap().content, pattern(1000));
+}
+
+#[tokio::test]
+async fn uploads_from_a_reader() {
+    let fake = FakeUploadThing::start().unwrap();
+    let api = fast_client(&fake);
+    let content = pattern(200_000);
+
+    let file = FileObj::from_reader("stream", Cursor::new(content.clone()), 200_000)
+        .with_content_type("application/x-custom");
+    let report = api.upload_files(vec![file], None, true).await.unwrap();
+    let upload = report.results[0].result.as_ref().unwrap();
+
+    let stored = fake.file(&upload.key).unwrap();
+    assert_eq!(stored.content, content);
+    assert_eq!(stored.content_type, "application/x-custom");
+}
+
+#[tokio::test]
+async fn does_not_retry_a_reader() {
+    let fake = FakeUploadThing::start().unwrap();
+    let api = fast_client(&fake);
+    fake.fail_next(Endpoint::PresignedPost, StatusCode::SERVICE_UNAVAILABLE, 1);
+
+    let file = FileObj::from_reader("stream", Cursor::new(b"once".to_vec()), 4);
+    let report = api.upload_files(vec![file], None, false).await.unwrap();
+
+    let error = report.results[0].result.as_ref().unwrap_err();
+    assert_eq!(error.status(), Some(StatusCode::SERVICE_UNAVAILABLE));
+    assert_eq!(fake.request_count(Endpoint::PresignedPost), 1);
+}
+
+/// Uploads a reader over `content` that claims to hold `size` bytes with `api`, and
+/// checks that the upload fails without storing anything.
+async fn assert_size_mismatch_rejected(
+    fake: &FakeUploadThing,
+    api: &UtApi,
+    content: Vec<u8>,
+    size: u64,
+) {
+    let file = FileObj::from_reader("wrong.bin", Cursor::new(content), size);
+    let report = api.upload_files(vec![file], None, true).await.unwrap();
+
+    let error = report.results[0].result.as_ref().unwrap_err();
+    assert!(matches!(error, UtApiError::InvalidInput(_)), "{:?}", error);
+    assert!(fake.files().iter().all(|file| !file.uploaded));
+}
+
+#[tokio::test]
+async fn rejects_a_reader_that_does_not_hold_its_size() {
+    let fake = FakeUploadThing::start().unwrap();
+    let api = fast_client(&fake);
+
+    // Longer, shorter, and longer by less than a chunk of the stream.
+    assert_size_mismatch_rejected(&fake, &api, pattern(100_000), 50_000).await;
+    assert_size_mismatch_rejected(&fake, &api, pattern(10), 100_000).await;
+    assert_size_mismatch_rejected(&fake, &api, b"hello world".to_vec(), 5).await;
+    assert_eq!(fake.request_count(Endpoint::PollUpload), 0);
+}
+
+#[tokio::test]
+async fn rejects_a_reader_that_does_not_hold_its_size_with_ingest() {
+    let fake = FakeUploadThing::start().unwrap();
+    let mut config = fast_config(&fake);
+    config.upload_protocol = UploadProtocol::Ingest;
+    let api = UtApi::from_config(config);
+
+    assert_size_mismatch_rejected(&fake, &api, pattern(100_000), 50_000).await;
+    assert_size_mismatch_rejected(&fake, &api, pattern(10), 100_000).await;
+}
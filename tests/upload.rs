@@ -0,0 +1,231 @@
+//! The `UploadReport` of `UtApi::upload_files` against `FakeUploadThing`: the order of
+//! its entries, how files are split into batches and uploaded concurrently, and how
+//! rejected batches, polling and unreadable files are reported.
+
+mod common;
+
+use common::{fast_client, fast_config};
+use reqwest::StatusCode;
+use std::time::{Duration, Instant};
+use utapi_rs::models::{FileObj, UploadFileOpts};
+use utapi_rs::retry::{Jitter, RetryPolicy};
+use utapi_rs::testing::{Endpoint, FakeUploadThing};
+use utapi_rs::{UtApi, UtApiError};
+
+/// `count` small files named `0.txt`, `1.txt` and so on.
+fn numbered_files(count: usize) -> Vec<FileObj> {
+    (0..count)
+        .map(|i| FileObj::from_bytes(format!("{}.txt", i), i.to_string()))
+        .collect()
+}
+
+#[tokio::test]
+async fn reports_files_in_input_order_across_batches() {
+    let fake = FakeUploadThing::start().unwrap();
+    let mut config = fast_config(&fake);
+    config.upload_batch_size = 2;
+    let api = UtApi::from_config(config);
+
+    let report = api
+        .upload_files(numbered_files(5), None, true)
+        .await
+        .unwrap();
+
+    assert!(report.is_success());
+    let names: Vec<&str> = report.results.iter().map(|r| r.name.as_str()).collect();
+    assert_eq!(names, ["0.txt", "1.txt", "2.txt", "3.txt", "4.txt"]);
+    for (i, upload) in report.successes().enumerate() {
+        assert_eq!(fake.file(&upload.key).unwrap().content, i.to_string());
+    }
+    assert_eq!(fake.request_count(Endpoint::UploadFiles), 3);
+}
+
+#[tokio::test]
+async fn registers_files_in_batches_of_upload_batch_size() {
+    let fake = FakeUploadThing::start().unwrap();
+    let mut config = fast_config(&fake);
+    config.upload_batch_size = 3;
+    let api = UtApi::from_config(config);
+
+    let report = api
+        .upload_files(numbered_files(7), None, false)
+        .await
+        .unwrap();
+
+    assert!(report.is_success());
+    assert_eq!(fake.request_count(Endpoint::UploadFiles), 3);
+    assert_eq!(fake.request_count(Endpoint::PresignedPost), 7);
+}
+
+#[tokio::test]
+async fn uploads_at_most_concurrency_files_at_once() {
+    let fake = FakeUploadThing::start().unwrap();
+    let mut config = fast_config(&fake);
+    config.max_concurrency = 8;
+    let api = UtApi::from_config(config);
+    fake.set_latency(Endpoint::PresignedPost, Duration::from_millis(100));
+
+    let opts = UploadFileOpts {
+        concurrency: Some(2),
+        ..UploadFileOpts::default()
+    };
+    let report = api
+        .upload_files(numbered_files(6), Some(opts), false)
+        .await
+        .unwrap();
+
+    assert!(report.is_success());
+    assert_eq!(fake.max_in_flight(Endpoint::PresignedPost), 2);
+}
+
+#[tokio::test]
+async fn caps_concurrency_at_max_concurrency() {
+    let fake = FakeUploadThing::start().unwrap();
+    let mut config = fast_config(&fake);
+    config.max_concurrency = 2;
+    let api = UtApi::from_config(config);
+    fake.set_latency(Endpoint::PresignedPost, Duration::from_millis(100));
+
+    let opts = UploadFileOpts {
+        concurrency: Some(10),
+        ..UploadFileOpts::default()
+    };
+    let report = api
+        .upload_files(numbered_files(6), Some(opts), false)
+        .await
+        .unwrap();
+
+    assert!(report.is_success());
+    assert_eq!(fake.max_in_flight(Endpoint::PresignedPost), 2);
+}
+
+#[tokio::test]
+async fn shares_max_concurrency_between_calls() {
+    let fake = FakeUploadThing::start().unwrap();
+    let mut config = fast_config(&fake);
+    config.max_concurrency = 3;
+    let api = UtApi::from_config(config);
+    fake.set_latency(Endpoint::PresignedPost, Duration::from_millis(100));
+
+    let other = api.clone();
+    let (first, second) = tokio::join!(
+        api.upload_files(numbered_files(4), None, false),
+        other.upload_files(numbered_files(4), None, false),
+    );
+
+    assert!(first.unwrap().is_success());
+    assert!(second.unwrap().is_success());
+    assert_eq!(fake.max_in_flight(Endpoint::PresignedPost), 3);
+}
+
+#[tokio::test]
+async fn reports_a_rejected_batch_in_the_report() {
+    let fake = FakeUploadThing::start().unwrap();
+    let mut config = fast_config(&fake);
+    config.upload_batch_size = 2;
+    let api = UtApi::from_config(config);
+
+    // The first batch is rejected the same way a later one would be.
+    fake.fail_next(Endpoint::UploadFiles, StatusCode::BAD_REQUEST, 1);
+    let report = api
+        .upload_files(numbered_files(4), None, false)
+        .await
+        .unwrap();
+
+    let failures: Vec<(usize, &str)> = report.failures().map(|(i, name, _)| (i, name)).collect();
+    assert_eq!(failures, [(0, "0.txt"), (1, "1.txt")]);
+    for (_, _, error) in report.failures() {
+        assert!(matches!(error, UtApiError::BatchRejected(_)), "{:?}", error);
+        assert_eq!(error.status(), Some(StatusCode::BAD_REQUEST));
+    }
+    assert_eq!(report.successes().count(), 2);
+    assert_eq!(fake.files().len(), 2);
+}
+
+#[tokio::test]
+async fn times_out_when_a_file_is_never_processed() {
+    let fake = FakeUploadThing::start().unwrap();
+    let api = fast_client(&fake);
+    fake.stall_processing(true);
+
+    let file = FileObj::from_bytes("a.txt", "a");
+    let report = api.upload_files(vec![file], None, true).await.unwrap();
+
+    let key = &fake.files()[0].key;
+    match &report.results[0].result {
+        Err(UtApiError::PollTimeout { key: timed_out }) => assert_eq!(timed_out, key),
+        other => panic!("expected a poll timeout, got {:?}", other),
+    }
+    assert_eq!(fake.request_count(Endpoint::PollUpload), 20);
+}
+
+#[tokio::test]
+async fn polls_at_most_poll_attempts_times_without_waiting_after_the_last() {
+    let fake = FakeUploadThing::start().unwrap();
+    let mut config = fast_config(&fake);
+    config.retry_policy = RetryPolicy {
+        poll_attempts: 3,
+        base_backoff: Duration::from_millis(200),
+        jitter: Jitter::None,
+        ..RetryPolicy::default()
+    };
+    let api = UtApi::from_config(config);
+    fake.stall_processing(true);
+
+    let started = Instant::now();
+    let file = FileObj::from_bytes("a.txt", "a");
+    let report = api.upload_files(vec![file], None, true).await.unwrap();
+    let elapsed = started.elapsed();
+
+    assert!(matches!(
+        report.results[0].result,
+        Err(UtApiError::PollTimeout { .. })
+    ));
+    assert_eq!(fake.request_count(Endpoint::PollUpload), 3);
+    // 200ms and 400ms between the polls, and no 800ms wait after the third.
+    assert!(elapsed >= Duration::from_millis(600), "{:?}", elapsed);
+    assert!(elapsed < Duration::from_millis(1200), "{:?}", elapsed);
+}
+
+#[tokio::test]
+async fn keeps_polling_with_a_policy_that_never_retries() {
+    let fake = FakeUploadThing::start().unwrap();
+    let mut config = fast_config(&fake);
+    config.retry_policy = RetryPolicy {
+        poll_attempts: 2,
+        base_backoff: Duration::from_millis(1),
+        ..RetryPolicy::none()
+    };
+    let api = UtApi::from_config(config);
+    fake.stall_processing(true);
+
+    let file = FileObj::from_bytes("a.txt", "a");
+    let report = api.upload_files(vec![file], None, true).await.unwrap();
+
+    assert!(matches!(
+        report.results[0].result,
+        Err(UtApiError::PollTimeout { .. })
+    ));
+    assert_eq!(fake.request_count(Endpoint::PollUpload), 2);
+}
+
+#[tokio::test]
+async fn fails_only_the_files_that_cannot_be_read() {
+    let fake = FakeUploadThing::start().unwrap();
+    let api = fast_client(&fake);
+
+    let files = vec![
+        FileObj::from_bytes("a.txt", "a"),
+        FileObj::from_path("missing.txt", "/nonexistent/missing.txt"),
+        FileObj::from_bytes("b.txt", "b"),
+    ];
+    let report = api.upload_files(files, None, true).await.unwrap();
+
+    let names: Vec<&str> = report.results.iter().map(|r| r.name.as_str()).collect();
+    assert_eq!(names, ["a.txt", "missing.txt", "b.txt"]);
+    assert!(report.results[0].result.is_ok());
+    assert!(matches!(report.results[1].result, Err(UtApiError::Io(_))));
+    assert!(report.results[2].result.is_ok());
+    // The unreadable file was never registered.
+    assert_eq!(fake.files().len(), 2);
+}